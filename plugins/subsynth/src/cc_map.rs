@@ -0,0 +1,363 @@
+//! MIDI CC mappings. Every mapping routes a controller to one of the mod matrix's destinations, so a
+//! hardware controller can push the synth's parameters around without any host automation. The
+//! mappings are edited on the editor's CC map page and saved with the patch.
+//!
+//! Mappings can also switch the waveform or the filter type instead. Those split the controller's
+//! range into equally sized zones, one for every option. The selected option only lasts until the
+//! parameter itself is changed again.
+
+use enum_iterator::Sequence;
+use nih_plug::params::enums::Enum;
+use serde::{Deserialize, Serialize};
+use subsynth_dsp::filter::FilterType;
+use subsynth_dsp::waveform::Waveform;
+
+use crate::modmatrix::{ModDestination, ModOffsets};
+
+/// The maximum number of CC mappings. These are stored in a fixed size array so the mappings can be
+/// copied to the audio thread without allocating.
+pub const MAX_CC_MAPPINGS: usize = 32;
+/// The mod wheel's controller number. The mod wheel is also available as a mod matrix source.
+pub const MOD_WHEEL_CC: u8 = 1;
+/// The sustain pedal's controller number. The pedal is also available as a mod matrix source.
+pub const SUSTAIN_PEDAL_CC: u8 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CcMapping {
+    /// The controller number, in `0..128`.
+    pub cc: u8,
+    /// Only respond to CCs on this channel, in `0..16`. `None` responds to all channels.
+    pub channel: Option<u8>,
+    pub destination: ModDestination,
+    /// Switch between a stepped parameter's options instead of modulating `destination`. The
+    /// minimum and maximum amounts don't apply to these.
+    #[serde(default)]
+    pub stepped: Option<SteppedDestination>,
+    /// The modulation amount when the controller is all the way down, between -1 and 1.
+    pub min: f32,
+    /// The modulation amount when the controller is all the way up, between -1 and 1.
+    pub max: f32,
+}
+
+/// The parameters a CC mapping can switch between options of. These override the parameter's value
+/// for every part once the controller has been moved, until the parameter gets changed.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence, Serialize, Deserialize)]
+pub enum SteppedDestination {
+    Waveform,
+    #[name = "Filter Type"]
+    FilterType,
+}
+
+/// The options the stepped CC mappings have selected. `None` if nothing has been selected yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SteppedOverrides {
+    pub waveform: Option<Waveform>,
+    pub filter_type: Option<FilterType>,
+}
+
+/// Tracks whether a stepped mapping's option still overrides a single part's parameter. The
+/// override ends as soon as the parameter changes, for instance through automation or the editor,
+/// and it only comes back once the controller selects another option.
+#[derive(Debug, Clone, Copy)]
+pub struct SteppedOverride<T> {
+    /// The option the controller selected last.
+    selected: Option<T>,
+    /// The parameter's value when that option was selected. `None` once the override has ended.
+    param_value: Option<T>,
+}
+
+/// The stepped overrides for a single part.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PartOverrides {
+    pub waveform: SteppedOverride<Waveform>,
+    pub filter_type: SteppedOverride<FilterType>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CcMap {
+    mappings: [CcMapping; MAX_CC_MAPPINGS],
+    num_mappings: usize,
+}
+
+/// The last received value for every controller on every channel, between 0 and 1.
+#[derive(Debug, Clone, Copy)]
+pub struct CcValues {
+    values: [[f32; 128]; 16],
+    /// The last received value for every controller regardless of the channel.
+    omni_values: [f32; 128],
+    /// Whether a controller has been received on a channel at all. Stepped mappings only take
+    /// over once their controller has been moved.
+    received: [[bool; 128]; 16],
+}
+
+impl Default for CcMapping {
+    /// The mod wheel, mapped to the cutoff.
+    fn default() -> Self {
+        Self {
+            cc: MOD_WHEEL_CC,
+            channel: None,
+            destination: ModDestination::Cutoff,
+            stepped: None,
+            min: 0.0,
+            max: 1.0,
+        }
+    }
+}
+
+impl Default for CcMap {
+    fn default() -> Self {
+        Self {
+            mappings: [CcMapping::default(); MAX_CC_MAPPINGS],
+            num_mappings: 0,
+        }
+    }
+}
+
+impl CcMap {
+    pub fn mappings(&self) -> &[CcMapping] {
+        // Saved state could contain anything, so this is clamped to stay in bounds
+        &self.mappings[..self.num_mappings.min(MAX_CC_MAPPINGS)]
+    }
+
+    /// Add a mapping. Returns `false` if the map is already full.
+    pub fn add(&mut self, mapping: CcMapping) -> bool {
+        let num_mappings = self.mappings().len();
+        if num_mappings >= MAX_CC_MAPPINGS {
+            return false;
+        }
+
+        self.mappings[num_mappings] = mapping.sanitized();
+        self.num_mappings = num_mappings + 1;
+
+        true
+    }
+
+    pub fn remove(&mut self, idx: usize) {
+        let num_mappings = self.mappings().len();
+        if idx < num_mappings {
+            self.mappings.copy_within(idx + 1..num_mappings, idx);
+            self.num_mappings = num_mappings - 1;
+        }
+    }
+
+    pub fn set(&mut self, idx: usize, mapping: CcMapping) {
+        if idx < self.mappings().len() {
+            self.mappings[idx] = mapping.sanitized();
+        }
+    }
+
+    /// Compute the modulation from all mappings for the current controller values.
+    pub fn evaluate(&self, values: &CcValues) -> ModOffsets {
+        let mut offsets = ModOffsets::default();
+        for mapping in self.mappings().iter().filter(|mapping| mapping.stepped.is_none()) {
+            let value = values.get(mapping.channel, mapping.cc);
+            offsets.add(
+                mapping.destination,
+                mapping.min + (mapping.max - mapping.min) * value,
+            );
+        }
+
+        offsets
+    }
+
+    /// The options selected by the stepped mappings. If several mappings switch the same parameter,
+    /// the last one wins.
+    pub fn stepped_overrides(&self, values: &CcValues) -> SteppedOverrides {
+        let mut overrides = SteppedOverrides::default();
+        for mapping in self.mappings() {
+            let value = values.get_received(mapping.channel, mapping.cc);
+            let (stepped, value) = match (mapping.stepped, value) {
+                (Some(stepped), Some(value)) => (stepped, value),
+                _ => continue,
+            };
+
+            match stepped {
+                SteppedDestination::Waveform => {
+                    overrides.waveform = Some(zone_variant(value));
+                }
+                SteppedDestination::FilterType => {
+                    overrides.filter_type = Some(zone_variant(value));
+                }
+            }
+        }
+
+        overrides
+    }
+}
+
+/// Pick one of an enum's variants by splitting the range from 0 to 1 into equally sized zones.
+fn zone_variant<T: Enum>(value: f32) -> T {
+    let num_variants = T::variants().len();
+    T::from_index(((value.clamp(0.0, 1.0) * num_variants as f32) as usize).min(num_variants - 1))
+}
+
+impl CcMapping {
+    /// The name of what this mapping controls, as shown on the CC map page.
+    pub fn destination_name(&self) -> &'static str {
+        match self.stepped {
+            Some(stepped) => SteppedDestination::variants()[stepped.to_index()],
+            None => ModDestination::variants()[self.destination.to_index()],
+        }
+    }
+
+    /// Move on to the next destination. This cycles through all mod matrix destinations followed
+    /// by the stepped destinations.
+    pub fn next_destination(&mut self) {
+        let num_mod_destinations = ModDestination::variants().len();
+        let idx = match self.stepped {
+            Some(stepped) => num_mod_destinations + stepped.to_index(),
+            None => self.destination.to_index(),
+        };
+        let next_idx = (idx + 1) % (num_mod_destinations + SteppedDestination::variants().len());
+        if next_idx < num_mod_destinations {
+            self.destination = ModDestination::from_index(next_idx);
+            self.stepped = None;
+        } else {
+            self.stepped = Some(SteppedDestination::from_index(next_idx - num_mod_destinations));
+        }
+    }
+
+    fn sanitized(self) -> Self {
+        Self {
+            cc: self.cc.min(127),
+            channel: self.channel.map(|channel| channel.min(15)),
+            min: self.min.clamp(-1.0, 1.0),
+            max: self.max.clamp(-1.0, 1.0),
+            ..self
+        }
+    }
+}
+
+impl<T> Default for SteppedOverride<T> {
+    fn default() -> Self {
+        Self {
+            selected: None,
+            param_value: None,
+        }
+    }
+}
+
+impl<T: Copy + PartialEq> SteppedOverride<T> {
+    /// The value the part should use, given the option the controller currently selects from
+    /// [`CcMap::stepped_overrides()`] and the parameter's current value.
+    pub fn apply(&mut self, selected: Option<T>, param_value: T) -> T {
+        if selected != self.selected {
+            self.selected = selected;
+            self.param_value = Some(param_value);
+        }
+
+        match self.selected {
+            Some(selected) if self.param_value == Some(param_value) => selected,
+            _ => {
+                self.param_value = None;
+                param_value
+            }
+        }
+    }
+}
+
+impl Default for CcValues {
+    fn default() -> Self {
+        Self {
+            values: [[0.0; 128]; 16],
+            omni_values: [0.0; 128],
+            received: [[false; 128]; 16],
+        }
+    }
+}
+
+impl CcValues {
+    /// The controller's value on a specific channel, or the last value received on any channel if
+    /// `channel` is `None`.
+    pub fn get(&self, channel: Option<u8>, cc: u8) -> f32 {
+        match channel {
+            Some(channel) => self.values[channel as usize % 16][cc as usize % 128],
+            None => self.omni_values[cc as usize % 128],
+        }
+    }
+
+    /// The same as [`get()`][Self::get()], but returns `None` if the controller hasn't been
+    /// received yet.
+    pub fn get_received(&self, channel: Option<u8>, cc: u8) -> Option<f32> {
+        let received = match channel {
+            Some(channel) => self.received[channel as usize % 16][cc as usize % 128],
+            None => self.received.iter().any(|received| received[cc as usize % 128]),
+        };
+
+        received.then(|| self.get(channel, cc))
+    }
+
+    pub fn set(&mut self, channel: u8, cc: u8, value: f32) {
+        let value = value.clamp(0.0, 1.0);
+        self.values[channel as usize % 16][cc as usize % 128] = value;
+        self.omni_values[cc as usize % 128] = value;
+        self.received[channel as usize % 16][cc as usize % 128] = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stepped_zones() {
+        let mut cc_map = CcMap::default();
+        cc_map.add(CcMapping {
+            cc: 20,
+            stepped: Some(SteppedDestination::FilterType),
+            ..CcMapping::default()
+        });
+
+        // Nothing changes until the controller has been moved
+        let mut values = CcValues::default();
+        assert_eq!(cc_map.stepped_overrides(&values), SteppedOverrides::default());
+        assert_eq!(cc_map.evaluate(&values).cutoff, 0.0);
+
+        let num_filter_types = FilterType::variants().len();
+        for idx in 0..num_filter_types {
+            values.set(3, 20, (idx as f32 + 0.5) / num_filter_types as f32);
+            assert_eq!(
+                cc_map.stepped_overrides(&values).filter_type,
+                Some(FilterType::from_index(idx))
+            );
+        }
+        values.set(3, 20, 1.0);
+        assert_eq!(
+            cc_map.stepped_overrides(&values).filter_type,
+            Some(FilterType::from_index(num_filter_types - 1))
+        );
+        assert_eq!(cc_map.stepped_overrides(&values).waveform, None);
+        // Stepped mappings don't modulate their mod matrix destination
+        assert_eq!(cc_map.evaluate(&values).cutoff, 0.0);
+    }
+
+    #[test]
+    fn test_stepped_override_ends_on_parameter_change() {
+        let mut filter_type = SteppedOverride::default();
+        assert_eq!(filter_type.apply(None, FilterType::Lowpass), FilterType::Lowpass);
+        assert_eq!(
+            filter_type.apply(Some(FilterType::Notch), FilterType::Lowpass),
+            FilterType::Notch
+        );
+        assert_eq!(
+            filter_type.apply(Some(FilterType::Notch), FilterType::Lowpass),
+            FilterType::Notch
+        );
+
+        // Changing the parameter ends the override, even when it's changed back
+        assert_eq!(
+            filter_type.apply(Some(FilterType::Notch), FilterType::Highpass),
+            FilterType::Highpass
+        );
+        assert_eq!(
+            filter_type.apply(Some(FilterType::Notch), FilterType::Lowpass),
+            FilterType::Lowpass
+        );
+
+        // Until the controller selects another option
+        assert_eq!(
+            filter_type.apply(Some(FilterType::Bandpass), FilterType::Lowpass),
+            FilterType::Bandpass
+        );
+    }
+}
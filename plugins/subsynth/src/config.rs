@@ -0,0 +1,16 @@
+//! SubSynth's compile time limits. The `small-footprint` feature lowers these for Raspberry Pi
+//! class hosts, where memory and CPU time are tight. The feature also shrinks the wavetables, see
+//! [`subsynth_dsp::config`].
+
+/// The maximum polyphony, shared by both parts.
+#[cfg(not(feature = "small-footprint"))]
+pub const NUM_VOICES: usize = 16;
+#[cfg(feature = "small-footprint")]
+pub const NUM_VOICES: usize = 8;
+
+/// The voices are rendered in blocks of at most this many samples. Every part keeps a set of
+/// buffers this long for its smoothed parameters, and every voice keeps a few more.
+#[cfg(not(feature = "small-footprint"))]
+pub const MAX_BLOCK_SIZE: usize = 64;
+#[cfg(feature = "small-footprint")]
+pub const MAX_BLOCK_SIZE: usize = 32;
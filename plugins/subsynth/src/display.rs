@@ -0,0 +1,137 @@
+//! Value formatters for SubSynth's parameters that are not covered by NIH-plug's `formatters`
+//! module.
+
+use nih_plug::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A parameter's value to string conversion, in the same form as NIH-plug's `formatters`.
+pub type ValueToString = Arc<dyn Fn(f32) -> String + Send + Sync>;
+/// A parameter's string to value conversion, in the same form as NIH-plug's `formatters`.
+pub type StringToValue = Arc<dyn Fn(&str) -> Option<f32> + Send + Sync>;
+
+/// Format a frequency in Hertz as the nearest note name plus the offset in cents, e.g. `A4, +12
+/// ct.`. This is the same format [`formatters::s2v_f32_hz_then_khz()`] accepts.
+pub fn note_name_with_cents(frequency: f32) -> String {
+    let fractional_note = util::freq_to_midi_note(frequency);
+    let note = fractional_note.round();
+    let cents = ((fractional_note - note) * 100.0).round() as i32;
+
+    let note_name = util::NOTES[(note as i32).rem_euclid(12) as usize];
+    let octave = (note / 12.0).floor() as i32 - 1;
+    if cents == 0 {
+        format!("{note_name}{octave}")
+    } else {
+        format!("{note_name}{octave}, {cents:+} ct.")
+    }
+}
+
+/// Format a frequency in Hertz/kilohertz followed by the nearest note, or as a note name with the
+/// offset in cents when `show_note_names` is set. The flag can be flipped at runtime, for instance
+/// from a display mode parameter's callback.
+pub fn v2s_f32_hz_or_note_name(digits: usize, show_note_names: Arc<AtomicBool>) -> ValueToString {
+    let hz_formatter = formatters::v2s_f32_hz_then_khz_with_note_name(digits, false);
+
+    Arc::new(move |value| {
+        // Note names don't make much sense for subsonic values
+        if show_note_names.load(Ordering::Relaxed) && value >= 1.0 {
+            note_name_with_cents(value)
+        } else {
+            hz_formatter(value)
+        }
+    })
+}
+
+/// How far a pitch offset in cents moves A4 at 440 Hz, in Hertz.
+fn a4_offset_hz(cents: f32) -> f32 {
+    440.0 * (2.0f32.powf(cents / 1200.0) - 1.0)
+}
+
+/// Format a pitch offset in cents along with the frequency offset it results in for A4, e.g. `+12.0
+/// ct (+3.06 Hz at A4)`. This already includes the unit.
+pub fn v2s_f32_cents_with_hz(digits: usize) -> ValueToString {
+    Arc::new(move |value| {
+        format!(
+            "{value:+.digits$} ct ({:+.2} Hz at A4)",
+            a4_offset_hz(value)
+        )
+    })
+}
+
+/// Format a pitch offset in semitones along with the frequency offset it results in for A4, e.g.
+/// `-2.00 st (-48.0 Hz at A4)`. This already includes the unit.
+pub fn v2s_f32_semitones_with_hz(digits: usize) -> ValueToString {
+    Arc::new(move |value| {
+        format!(
+            "{value:+.digits$} st ({:+.1} Hz at A4)",
+            a4_offset_hz(value * 100.0)
+        )
+    })
+}
+
+/// Parse the number at the start of a string, ignoring the unit and anything else that follows
+/// it. This accepts the output of [`v2s_f32_cents_with_hz()`] and [`v2s_f32_semitones_with_hz()`].
+pub fn s2v_f32_leading_number() -> StringToValue {
+    Arc::new(|string| {
+        let number = string
+            .trim_start()
+            .split(|c: char| !(c.is_ascii_digit() || matches!(c, '+' | '-' | '.')))
+            .next()?;
+
+        number.parse().ok()
+    })
+}
+
+/// Format a millisecond value as a rounded `ms` value below 1000 ms, and as a rounded `s` value
+/// above 1000 ms. This already includes the unit.
+pub fn v2s_f32_ms_then_s(digits: usize) -> ValueToString {
+    Arc::new(move |value| {
+        if value < 1000.0 {
+            format!("{value:.digits$} ms")
+        } else {
+            format!("{:.digits$} s", value / 1000.0, digits = digits.max(2))
+        }
+    })
+}
+
+/// Convert an input in the same format as that of [`v2s_f32_ms_then_s()`] to a millisecond value.
+/// Inputs without a unit are assumed to be in milliseconds.
+pub fn s2v_f32_ms_then_s() -> StringToValue {
+    Arc::new(|string| {
+        let string = string.trim();
+        let cleaned_string = string
+            .trim_end_matches([' ', 'm', 'M', 's', 'S'])
+            .parse()
+            .ok();
+
+        let unit = string.get(string.len().saturating_sub(2)..);
+        match unit {
+            Some(unit) if unit.eq_ignore_ascii_case("ms") => cleaned_string,
+            Some(unit) if unit.ends_with(['s', 'S']) => cleaned_string.map(|x| x * 1000.0),
+            // Even if there's no unit at all, just assume the input is in milliseconds
+            _ => cleaned_string,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ms_then_s_roundtrip() {
+        let v2s = v2s_f32_ms_then_s(1);
+        let s2v = s2v_f32_ms_then_s();
+
+        for time in [0.0, 0.5, 12.3, 999.9, 1250.0, 60000.0] {
+            let string = v2s(time);
+            let roundtrip_time = s2v(&string).unwrap();
+            assert_eq!(string, v2s(roundtrip_time));
+        }
+
+        assert_eq!(Some(12.5), s2v("12.5"));
+        assert_eq!(Some(1500.0), s2v("1.5 s"));
+        assert_eq!(Some(1500.0), s2v("1.5s"));
+        assert_eq!(Some(20.0), s2v("20ms"));
+    }
+}
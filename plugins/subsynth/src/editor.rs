@@ -1,21 +1,157 @@
-use nih_plug::prelude::{Editor};
+mod cc_map_page;
+mod mod_scope_view;
+mod mseg_editor;
+mod performance_page;
+
+use nih_plug::prelude::{nih_error, AsyncExecutor, Editor};
 use nih_plug_vizia::vizia::prelude::*;
 use nih_plug_vizia::widgets::*;
 use nih_plug_vizia::{assets, create_vizia_editor, ViziaState, ViziaTheming};
 
+use atomic_float::AtomicF32;
+use nih_plug::prelude::ParamPtr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+use subsynth_dsp::drawbar::DRAWBAR_NAMES;
 
-use crate::SubSynthParams;
+use crate::envelope_shape::EnvelopeShape;
+use crate::meter::MeterDisplay;
+use crate::mod_scope::ModScope;
+use crate::modmatrix::{NUM_MACROS, NUM_MOD_SLOTS};
+use crate::morph::MorphSlot;
+use crate::performance::PerformanceInput;
+use crate::preview::{PreviewNotes, PreviewTask};
+use crate::transport::{sync_readout, TransportDisplay};
+use crate::{SubSynth, SubSynthParams};
+use cc_map_page::CcMapPage;
+use mod_scope_view::ModScopeView;
+use mseg_editor::MsegEditor;
+use performance_page::PerformancePage;
 
 #[derive(Lens)]
 struct Data {
     params: Arc<SubSynthParams>,
+    /// The output meter's level and peak hold.
+    meter: Arc<MeterDisplay>,
+    clipped: Arc<AtomicBool>,
+    /// Set when the synth had to mute NaN or infinite samples.
+    dsp_error: Arc<AtomicBool>,
+    /// The output's stereo correlation, between -1 and 1.
+    correlation: Arc<AtomicF32>,
+    /// The host's tempo and position, shown in the header.
+    transport: Arc<TransportDisplay>,
+    /// Whether the CC map page is shown on top of the regular controls.
+    show_cc_map: bool,
+    /// Whether the performance page is shown instead of the regular controls. This is stored in
+    /// the plugin's state so the editor reopens on the same page.
+    show_performance_page: bool,
+    /// The index of the part whose controls are shown.
+    edited_part: usize,
+    /// Whether morph snapshots A and B have been stored.
+    morph_stored: [bool; 2],
+    /// Runs the preview exports in the background.
+    async_executor: AsyncExecutor<SubSynth>,
+    /// The notes the preview plays, see [`PreviewNotes::parse()`].
+    preview_notes: String,
+    /// The WAV file the preview is written to.
+    preview_path: String,
+}
+
+enum EditorEvent {
+    /// Turn off the clip indicator again.
+    ResetClip,
+    /// Turn off the DSP error indicator again.
+    ResetDspError,
+    ResetPeakHold,
+    ToggleCcMap,
+    TogglePerformancePage,
+    SelectPart(usize),
+    /// Store the current patch in one of the morph snapshots.
+    StoreMorph(MorphSlot),
+    ClearMorph,
+    /// Set the edited part's envelopes to one of the quick shapes.
+    ApplyEnvelopeShape(EnvelopeShape),
+    /// Pick a new random seed.
+    RandomizeSeed,
+    SetPreviewNotes(String),
+    SetPreviewPath(String),
+    /// Render a preview of the patch to the preview file in the background.
+    ExportPreview,
+}
+
+impl Model for Data {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|editor_event, _| match editor_event {
+            EditorEvent::ResetClip => self.clipped.store(false, Ordering::Relaxed),
+            EditorEvent::ResetDspError => self.dsp_error.store(false, Ordering::Relaxed),
+            EditorEvent::ResetPeakHold => self.meter.reset_peak_hold(),
+            EditorEvent::ToggleCcMap => self.show_cc_map = !self.show_cc_map,
+            EditorEvent::TogglePerformancePage => {
+                self.show_performance_page = !self.show_performance_page;
+                self.params
+                    .show_performance_page
+                    .store(self.show_performance_page, Ordering::Relaxed);
+            }
+            EditorEvent::SelectPart(part) => self.edited_part = *part,
+            EditorEvent::StoreMorph(slot) => {
+                let mut snapshots = self
+                    .params
+                    .morph_snapshots
+                    .write()
+                    .expect("Poisoned morph snapshots lock");
+                snapshots.store(*slot, &self.params);
+                self.morph_stored = [
+                    snapshots.is_stored(MorphSlot::A),
+                    snapshots.is_stored(MorphSlot::B),
+                ];
+            }
+            EditorEvent::ClearMorph => {
+                self.params
+                    .morph_snapshots
+                    .write()
+                    .expect("Poisoned morph snapshots lock")
+                    .clear();
+                self.morph_stored = [false; 2];
+            }
+            EditorEvent::ApplyEnvelopeShape(shape) => {
+                let values = shape.param_values(self.params.part(self.edited_part));
+                set_params_as_gesture(cx, &values);
+            }
+            EditorEvent::RandomizeSeed => {
+                set_params_as_gesture(cx, &[(self.params.seed.as_ptr(), rand::random())]);
+            }
+            EditorEvent::SetPreviewNotes(notes) => self.preview_notes = notes.clone(),
+            EditorEvent::SetPreviewPath(path) => self.preview_path = path.clone(),
+            EditorEvent::ExportPreview => match PreviewNotes::parse(&self.preview_notes) {
+                Some(notes) => self.async_executor.execute_background(PreviewTask {
+                    path: self.preview_path.clone().into(),
+                    notes,
+                }),
+                None => nih_error!("Could not parse the preview notes '{}'", self.preview_notes),
+            },
+        });
+    }
 }
 
-impl Model for Data {}
+/// Set parameters to new normalized values from the editor itself, rather than from a widget the
+/// user is dragging. Every change the editor makes on its own should go through here, so the host
+/// records it like any other edit for undo and automation. All gestures are started before any
+/// value changes so the host can treat the whole batch as a single edit.
+fn set_params_as_gesture(cx: &mut EventContext, values: &[(ParamPtr, f32)]) {
+    for (ptr, _) in values {
+        cx.emit(RawParamEvent::BeginSetParameter(*ptr));
+    }
+    for (ptr, normalized) in values {
+        cx.emit(RawParamEvent::SetParameterNormalized(*ptr, *normalized));
+    }
+    for (ptr, _) in values {
+        cx.emit(RawParamEvent::EndSetParameter(*ptr));
+    }
+}
 
 pub(crate) fn default_state() -> Arc<ViziaState> {
-    ViziaState::new(|| (840, 480))
+    ViziaState::new(|| (1120, 2450))
 }
 
 fn create_label<'a, T>(
@@ -35,16 +171,50 @@ fn create_label<'a, T>(
         .child_bottom(Pixels(child_bottom));
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn create(
     params: Arc<SubSynthParams>,
+    meter: Arc<MeterDisplay>,
+    clipped: Arc<AtomicBool>,
+    dsp_error: Arc<AtomicBool>,
+    correlation: Arc<AtomicF32>,
+    mod_scope: Arc<ModScope>,
+    performance_input: Arc<PerformanceInput>,
+    transport: Arc<TransportDisplay>,
     editor_state: Arc<ViziaState>,
+    async_executor: AsyncExecutor<SubSynth>,
 ) -> Option<Box<dyn Editor>> {
     create_vizia_editor(editor_state, ViziaTheming::Custom, move |cx, _| {
         assets::register_noto_sans_light(cx);
         assets::register_noto_sans_thin(cx);
+        cx.add_theme(include_str!("assets/editor.css"));
 
         Data {
             params: params.clone(),
+            meter: meter.clone(),
+            clipped: clipped.clone(),
+            dsp_error: dsp_error.clone(),
+            correlation: correlation.clone(),
+            transport: transport.clone(),
+            show_cc_map: false,
+            show_performance_page: params.show_performance_page.load(Ordering::Relaxed),
+            edited_part: 0,
+            morph_stored: params
+                .morph_snapshots
+                .read()
+                .map(|snapshots| {
+                    [
+                        snapshots.is_stored(MorphSlot::A),
+                        snapshots.is_stored(MorphSlot::B),
+                    ]
+                })
+                .unwrap_or_default(),
+            async_executor: async_executor.clone(),
+            preview_notes: String::new(),
+            preview_path: std::env::temp_dir()
+                .join("SubSynth Preview.wav")
+                .display()
+                .to_string(),
         }
         .build(cx);
 
@@ -58,184 +228,1172 @@ pub(crate) fn create(
             .width(Stretch(1.0))
             .child_top(Stretch(1.0))
             .child_bottom(Pixels(0.0));
+        Label::new(
+            cx,
+            Data::transport.map(|transport| transport.to_string()),
+        )
+        .class("transport")
+        .position_type(PositionType::SelfDirected)
+        .left(Pixels(20.0))
+        .top(Pixels(10.0));
+        Button::new(
+            cx,
+            |cx| cx.emit(EditorEvent::ToggleCcMap),
+            |cx| Label::new(cx, "CC Map"),
+        )
+        .class("cc-map-toggle")
+        .checked(Data::show_cc_map)
+        .position_type(PositionType::SelfDirected)
+        .left(Stretch(1.0))
+        .right(Pixels(20.0))
+        .top(Pixels(10.0));
+        Button::new(
+            cx,
+            |cx| cx.emit(EditorEvent::TogglePerformancePage),
+            |cx| Label::new(cx, "Perform"),
+        )
+        .class("cc-map-toggle")
+        .checked(Data::show_performance_page)
+        .position_type(PositionType::SelfDirected)
+        .left(Pixels(360.0))
+        .top(Pixels(10.0));
         HStack::new(cx, |cx| {
-            VStack::new(cx, |cx| {
-                Label::new(cx, "Gain")
-                    .height(Pixels(20.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.gain);
-                create_label(cx, "Waveform", 20.0, 100.0, 1.0, 0.0);
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.waveform);
-                create_label(cx, "Filter Type", 20.0, 100.0, 1.0, 0.0);
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.filter_type);
-                create_label(cx, "Filter Cut", 20.0, 100.0, 1.0, 0.0);
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.filter_cut);
-                create_label(cx, "Filter Res", 20.0, 100.0, 1.0, 0.0);
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.filter_res);
-                
-            });
+            ParamButton::new(cx, Data::params.clone(), |params| &params.multitimbral);
+            for (part, name) in ["Part A", "Part B"].into_iter().enumerate() {
+                Button::new(
+                    cx,
+                    move |cx| cx.emit(EditorEvent::SelectPart(part)),
+                    move |cx| Label::new(cx, name),
+                )
+                .class("part-select")
+                .checked(Data::edited_part.map(move |edited_part| *edited_part == part));
+            }
+        })
+        .position_type(PositionType::SelfDirected)
+        .height(Auto)
+        .width(Auto)
+        .col_between(Pixels(5.0))
+        .left(Stretch(1.0))
+        .right(Pixels(130.0))
+        .top(Pixels(10.0));
+        // Everything that belongs to a single part is rebuilt when switching between the parts
+        let part_params = params.clone();
+        Binding::new(cx, Data::edited_part, move |cx, edited_part| {
+            let part = edited_part.get(cx);
+            let params = part_params.clone();
+            VStack::new(cx, move |cx| {
+                HStack::new(cx, |cx| {
+                    Label::new(cx, "Envelope Shape")
+                        .height(Pixels(24.0))
+                        .child_top(Stretch(1.0))
+                        .child_bottom(Stretch(1.0));
+                    for shape in EnvelopeShape::ALL {
+                        Button::new(
+                            cx,
+                            move |cx| cx.emit(EditorEvent::ApplyEnvelopeShape(shape)),
+                            move |cx| Label::new(cx, shape.name()),
+                        )
+                        .class("envelope-shape");
+                    }
+                })
+                .height(Auto)
+                .col_between(Pixels(5.0))
+                .child_left(Stretch(1.0))
+                .child_right(Stretch(1.0));
+                // The rates the tempo synced features currently run at, to check they follow the
+                // host's tempo
+                let sync_params = params.clone();
+                Label::new(
+                    cx,
+                    Data::transport.map(move |transport| {
+                        let tempo = transport.tempo();
+                        let part_params = sync_params.part(part);
+                        [
+                            sync_readout("Vibrato", part_params.vibrato.sync.value(), tempo),
+                            sync_readout("Tremolo", part_params.tremolo.sync.value(), tempo),
+                            sync_readout("MSEG", part_params.mseg.sync.value(), tempo),
+                        ]
+                        .join("   ")
+                    }),
+                )
+                .height(Pixels(20.0))
+                .width(Stretch(1.0))
+                .child_left(Stretch(1.0))
+                .child_right(Stretch(1.0));
+                HStack::new(cx, |cx| {
+                    VStack::new(cx, |cx| {
+                        Label::new(cx, "Voice Gain")
+                            .height(Pixels(20.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
 
-            VStack::new(cx, |cx| {
-                create_label(cx, "Attack", 20.0, 100.0, 1.0, 0.0);
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.amp_attack_ms);
-                create_label(cx, "Decay", 20.0, 100.0, 1.0, 0.0);
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.amp_decay_ms);
-                create_label(cx, "Sustain", 20.0, 100.0, 1.0, 0.0);
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.amp_sustain_level);
-                create_label(cx, "Release", 20.0, 100.0, 1.0, 0.0);
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.amp_release_ms);
-                Label::new(cx, "Env Int")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.amp_envelope_level);
-            });
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).gain
+                        });
+                        create_label(cx, "Waveform", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).osc.waveform
+                        });
+                        create_label(cx, "Source", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).osc.source
+                        });
+                        create_label(cx, "Filter Type", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter.filter_type
+                        });
+                        create_label(cx, "Filter Cut", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter.cutoff
+                        });
+                        create_label(cx, "Filter Res", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter.resonance
+                        });
+                        create_label(cx, "Filter Morph", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter.morph
+                        });
+                        create_label(cx, "Env Amount", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter.envelope_amount
+                        });
+                        create_label(cx, "Env Res Ratio", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter.envelope_resonance_ratio
+                        });
+                        ParamButton::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter.cutoff_note_display
+                        })
+                        .top(Pixels(10.0));
+                        ParamButton::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter.release_freeze
+                        })
+                        .top(Pixels(10.0));
+
+                    });
+
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Attack", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).amp_env.attack_ms
+                        });
+                        create_label(cx, "Decay", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).amp_env.decay_ms
+                        });
+                        create_label(cx, "Sustain", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).amp_env.sustain_level
+                        });
+                        create_label(cx, "Release", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).amp_env.release_ms
+                        });
+                        Label::new(cx, "Env Int")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).amp_env.envelope_level
+                        });
+                        create_label(cx, "Vel Sens", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).amp_env.velocity_sensitivity
+                        });
+                        create_label(cx, "Vel Curve", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).amp_env.velocity_curve
+                        });
+                    });
+
+                    VStack::new(cx, |cx| {
+                        Label::new(cx, "Filter Cut Atk")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter_cut_env.attack_ms
+                        });
+                        Label::new(cx, "Filter Cut Dec")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter_cut_env.decay_ms
+                        });
+                        Label::new(cx, "Filter Cut Sus")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter_cut_env.sustain_level
+                        });
+                        Label::new(cx, "Filter Cut Rel")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter_cut_env.release_ms
+                        });
+                        Label::new(cx, "Amount")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter_cut_env.envelope_level
+                        });
+                        create_label(cx, "Vel Sens", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter_cut_env.velocity_sensitivity
+                        });
+                        create_label(cx, "Vel Curve", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter_cut_env.velocity_curve
+                        });
+                    });
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Filter Q Atk", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter_res_env.attack_ms
+                        });
+                        create_label(cx, "Filter Q Dec", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter_res_env.decay_ms
+                        });
+                        create_label(cx, "Filter Q Sus", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter_res_env.sustain_level
+                        });
+
+                        Label::new(cx, "Filter Q Rel")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter_res_env.release_ms
+                        });
+                        Label::new(cx, "Amount")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter_res_env.envelope_level
+                        });
+                        create_label(cx, "Vel Sens", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter_res_env.velocity_sensitivity
+                        });
+                        create_label(cx, "Vel Curve", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter_res_env.velocity_curve
+                        });
+                    });
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Pitch Env", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).pitch_env.amount
+                        });
+                        create_label(cx, "Pitch Source", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).pitch_env.source
+                        });
+                        create_label(cx, "Pitch Atk", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).pitch_env.attack_ms
+                        });
+                        create_label(cx, "Pitch Dec", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).pitch_env.decay_ms
+                        });
+                        create_label(cx, "Pan", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).pan
+                        });
+                    })
+                    .row_between(Pixels(0.0))
+                    .child_left(Stretch(1.0))
+                    .child_right(Stretch(1.0));
 
-            VStack::new(cx, |cx| {
-                Label::new(cx, "Filter Cut Atk")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                    
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.filter_cut_attack_ms);
-                Label::new(cx, "Filter Cut Dec")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                    
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.filter_cut_decay_ms);
-                Label::new(cx, "Filter Cut Sus")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                    
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.filter_cut_sustain_ms);
-                Label::new(cx, "Filter Cut Rel")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                    
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.filter_cut_release_ms);
-                Label::new(cx, "Amount")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.filter_cut_envelope_level);
-            });
-            VStack::new(cx, |cx| {
-                create_label(cx, "Filter Q Atk", 20.0, 100.0, 1.0, 0.0);
-                ParamSlider::new(cx, Data::params.clone(), |params| {
-                    &params.filter_res_attack_ms
-                });
-                create_label(cx, "Filter Q Dec", 20.0, 100.0, 1.0, 0.0);
-                ParamSlider::new(cx, Data::params.clone(), |params| {
-                    &params.filter_res_decay_ms
-                });
-                create_label(cx, "Filter Q Sus", 20.0, 100.0, 1.0, 0.0);
-                ParamSlider::new(cx, Data::params.clone(), |params| {
-                    &params.filter_res_sustain_ms
                 });
+                HStack::new(cx, |cx| {
+                    VStack::new(cx, |cx| {
 
-                Label::new(cx, "Filter Q Rel")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                    
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.filter_res_release_ms);
-                Label::new(cx, "Amount")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.filter_res_envelope_level);
-            })
-            .row_between(Pixels(0.0))
-            .child_left(Stretch(1.0))
-            .child_right(Stretch(1.0));
+                        Label::new(cx, "Vib Int")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).vibrato.intensity
+                        });
+
+                        Label::new(cx, "Vib Rate")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).vibrato.rate
+                        });
+
+                        create_label(cx, "Vib Scope", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).vibrato.scope
+                        });
+                    });
+                    VStack::new(cx, |cx| {
+
+                        Label::new(cx, "Vib Attack")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).vibrato.attack_ms
+                        });
+
+                        Label::new(cx, "Vib Shape")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).vibrato.shape
+                        });
+
+                        create_label(cx, "Vib Mode", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).vibrato.mode
+                        });
+
+                        create_label(cx, "Vib Sync", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).vibrato.sync
+                        });
+                    });
+                    VStack::new(cx, |cx| {
+
+                        Label::new(cx, "Trem Int")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).tremolo.intensity
+                        });
+
+                        Label::new(cx, "Tremo Rate")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).tremolo.rate
+                        });
+
+                        create_label(cx, "Tremo Scope", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).tremolo.scope
+                        });
+                    });
+                    VStack::new(cx, |cx| {
+
+                        Label::new(cx, "Tremo Atk")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).tremolo.attack_ms
+                        });
 
+
+                        Label::new(cx, "Tremo Shape")
+                            .height(Pixels(20.0))
+                            .width(Pixels(100.0))
+                            .child_top(Stretch(1.0))
+                            .child_bottom(Pixels(0.0));
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).tremolo.shape
+                        });
+
+                        create_label(cx, "Tremo Mode", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).tremolo.mode
+                        });
+
+                        create_label(cx, "Tremo Sync", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).tremolo.sync
+                        });
+
+                    });
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Glide Mode", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).glide.mode
+                        });
+                        create_label(cx, "Glide Time", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).glide.time_ms
+                        });
+                        create_label(cx, "Glide Key", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).glide.key_tracking
+                        });
+                        create_label(cx, "Glide Vel", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).glide.velocity_tracking
+                        });
+                        ParamButton::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).glide.poly
+                        })
+                        .top(Pixels(10.0));
+                        create_label(cx, "Strum Time", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).strum.time_ms
+                        });
+                        create_label(cx, "Strum Mode", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).strum.mode
+                        });
+                        create_label(cx, "Tempo", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.internal_tempo);
+                        ParamButton::new(cx, Data::params.clone(), |params| &params.lfo_transport_sync)
+                            .top(Pixels(20.0));
+                    });
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Saw Detune", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).osc.supersaw_detune
+                        });
+                        create_label(cx, "Saw Mix", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).osc.supersaw_mix
+                        });
+                        create_label(cx, "Quality", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.osc_quality);
+                        create_label(cx, "Polyphony", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.polyphony);
+                        ParamButton::new(cx, Data::params.clone(), |params| {
+                            &params.protect_lowest_note
+                        })
+                        .top(Pixels(10.0));
+                        create_label(cx, "Voice Reserve", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.voice_reserve);
+                        ParamButton::new(cx, Data::params.clone(), |params| &params.eco_idle)
+                            .top(Pixels(10.0));
+                        ParamButton::new(cx, Data::params.clone(), |params| &params.bypass)
+                            .top(Pixels(10.0));
+                        create_label(cx, "Random Seed", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.seed);
+                        Button::new(
+                            cx,
+                            |cx| cx.emit(EditorEvent::RandomizeSeed),
+                            |cx| Label::new(cx, "Randomize Seed"),
+                        )
+                        .top(Pixels(10.0));
+                        // An empty list plays a C major chord
+                        create_label(cx, "Preview Notes", 20.0, 100.0, 1.0, 0.0);
+                        Textbox::new(cx, Data::preview_notes).on_submit(|cx, text, success| {
+                            if success {
+                                cx.emit(EditorEvent::SetPreviewNotes(text));
+                            }
+                        });
+                        create_label(cx, "Preview File", 20.0, 100.0, 1.0, 0.0);
+                        Textbox::new(cx, Data::preview_path).on_submit(|cx, text, success| {
+                            if success {
+                                cx.emit(EditorEvent::SetPreviewPath(text));
+                            }
+                        });
+                        Button::new(
+                            cx,
+                            |cx| cx.emit(EditorEvent::ExportPreview),
+                            |cx| Label::new(cx, "Export Preview"),
+                        )
+                        .top(Pixels(10.0));
+                        create_label(cx, "Expression Glide", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| {
+                            &params.expression_glide_ms
+                        });
+                        create_label(cx, "Filter FM", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter.fm_amount
+                        });
+                        create_label(cx, "Tone", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter.tone
+                        });
+                        create_label(cx, "Tone Keytrack", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).filter.tone_key_tracking
+                        });
+                        create_label(cx, "Master", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.master_gain);
+                        create_label(cx, "Patch Trim", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.patch_trim);
+                        PeakMeter::new(
+                            cx,
+                            Data::meter.map(|meter| meter.level_db()),
+                            Some(Duration::from_millis(600)),
+                        )
+                        .top(Pixels(10.0));
+                        // The loudest sample so far, clicking it starts over
+                        Button::new(
+                            cx,
+                            |cx| cx.emit(EditorEvent::ResetPeakHold),
+                            |cx| Label::new(cx, Data::meter.map(|meter| meter.peak_hold_readout())),
+                        )
+                        .class("peak-hold")
+                        .top(Pixels(10.0));
+                        create_label(cx, "Meter", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.meter_mode);
+                        create_label(cx, "Meter Speed", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.meter_speed);
+                        // Lights up when the output goes over 0 dBFS, clicking it turns it off again
+                        Button::new(
+                            cx,
+                            |cx| cx.emit(EditorEvent::ResetClip),
+                            |cx| Label::new(cx, "CLIP"),
+                        )
+                        .class("clip-indicator")
+                        .checked(Data::clipped.map(|clipped| clipped.load(Ordering::Relaxed)))
+                        .top(Pixels(10.0));
+                        // Lights up when a filter blew up and its output had to be muted
+                        Button::new(
+                            cx,
+                            |cx| cx.emit(EditorEvent::ResetDspError),
+                            |cx| Label::new(cx, "DSP ERROR"),
+                        )
+                        .class("clip-indicator")
+                        .checked(Data::dsp_error.map(|error| error.load(Ordering::Relaxed)))
+                        .top(Pixels(10.0));
+                        create_label(cx, "Width", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.width);
+                        create_label(cx, "Haas Delay", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.haas_delay_ms);
+                        ParamButton::new(cx, Data::params.clone(), |params| &params.haas_mono_safe)
+                            .top(Pixels(10.0));
+                        // Negative correlations mean parts of the output cancel out when summed to
+                        // mono, so those are highlighted
+                        Label::new(
+                            cx,
+                            Data::correlation.map(|correlation| {
+                                format!("Correlation {:+.2}", correlation.load(Ordering::Relaxed))
+                            }),
+                        )
+                        .class("correlation-meter")
+                        .checked(
+                            Data::correlation
+                                .map(|correlation| correlation.load(Ordering::Relaxed) < 0.0),
+                        )
+                        .top(Pixels(10.0));
+                        ParamButton::new(cx, Data::params.clone(), |params| &params.latch)
+                            .top(Pixels(20.0));
+                        create_label(cx, "Pedal Mode", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.pedal_mode);
+                        ParamButton::new(cx, Data::params.clone(), |params| &params.fixed_velocity)
+                            .top(Pixels(10.0));
+                        create_label(cx, "Velocity", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| {
+                            &params.fixed_velocity_value
+                        });
+                        ParamButton::new(cx, Data::params.clone(), |params| &params.freeze)
+                            .top(Pixels(10.0));
+                        create_label(cx, "Drone Gain", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.drone_gain);
+                        create_label(cx, "MIDI Channel", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).midi_channel
+                        });
+                        create_label(cx, "Choke Group", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).choke_group
+                        });
+                    })
+                    .row_between(Pixels(0.0))
+                    .child_left(Stretch(1.0))
+                    .child_right(Stretch(1.0));
+
+                });
+                // The drawbar organ's controls, only used by the drawbar waveform
+                HStack::new(cx, |cx| {
+                    for (idx, name) in DRAWBAR_NAMES.into_iter().enumerate() {
+                        VStack::new(cx, |cx| {
+                            create_label(cx, name, 20.0, 100.0, 1.0, 0.0);
+                            ParamSlider::new(cx, Data::params.clone(), move |params| {
+                                &params.part(part).drawbars[idx].level
+                            })
+                            .width(Pixels(100.0));
+                        })
+                        .height(Auto)
+                        .width(Auto);
+                    }
+                })
+                .height(Auto)
+                .col_between(Pixels(5.0))
+                .child_left(Stretch(1.0))
+                .child_right(Stretch(1.0));
+                HStack::new(cx, |cx| {
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Percussion", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).percussion.harmonic
+                        });
+                    });
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Perc Level", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).percussion.level
+                        });
+                    });
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Perc Decay", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).percussion.decay_ms
+                        });
+                    });
+                })
+                .height(Auto)
+                .col_between(Pixels(20.0))
+                .child_left(Stretch(1.0))
+                .child_right(Stretch(1.0));
+                HStack::new(cx, |cx| {
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "MSEG Dest", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).mseg.destination
+                        });
+                        create_label(cx, "MSEG Amount", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).mseg.amount
+                        });
+                    });
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "MSEG Length", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).mseg.length_ms
+                        });
+                        create_label(cx, "MSEG Sync", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).mseg.sync
+                        });
+                    });
+                    MsegEditor::new(cx, params.part(part).mseg.shape.clone())
+                        .width(Pixels(560.0))
+                        .height(Pixels(150.0))
+                        .top(Pixels(20.0));
+                })
+                .col_between(Pixels(20.0))
+                .child_left(Stretch(1.0))
+                .child_right(Stretch(1.0));
+                HStack::new(cx, |cx| {
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "PD Curve", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).osc.pd_curve
+                        });
+                        create_label(cx, "PD Amount", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).osc.pd_amount
+                        });
+                        create_label(cx, "PD Env", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).osc.pd_env_amount
+                        });
+                    });
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Drift Depth", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).drift.depth
+                        });
+                        create_label(cx, "Drift Rate", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).drift.rate
+                        });
+                    });
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Front/Rear", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).surround.position_y
+                        });
+                        create_label(cx, "Spread X", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).surround.spread_x
+                        });
+                        create_label(cx, "Spread Y", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.part(part).surround.spread_y
+                        });
+                    });
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Trig Threshold", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| {
+                            &params.trigger.threshold_db
+                        });
+                        create_label(cx, "Trig Note Mode", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.trigger.note_mode);
+                        create_label(cx, "Trig Note", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.trigger.note);
+                        ParamButton::new(cx, Data::params.clone(), |params| &params.trigger.enabled)
+                            .top(Pixels(20.0));
+                    });
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Xfade Center", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| {
+                            &params.velocity_crossfade.center
+                        });
+                        create_label(cx, "Xfade Width", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| {
+                            &params.velocity_crossfade.width
+                        });
+                        create_label(cx, "Xfade Curve", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| {
+                            &params.velocity_crossfade.curve
+                        });
+                        ParamButton::new(cx, Data::params.clone(), |params| {
+                            &params.velocity_crossfade.enabled
+                        })
+                        .top(Pixels(20.0));
+                    });
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Morph", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| &params.morph);
+                        // The buttons light up once their snapshot has been stored
+                        let slots = [(MorphSlot::A, "Store A"), (MorphSlot::B, "Store B")];
+                        for (idx, (slot, name)) in slots.into_iter().enumerate() {
+                            Button::new(
+                                cx,
+                                move |cx| cx.emit(EditorEvent::StoreMorph(slot)),
+                                move |cx| Label::new(cx, name),
+                            )
+                            .class("morph-store")
+                            .checked(Data::morph_stored.map(move |stored| stored[idx]))
+                            .top(Pixels(10.0));
+                        }
+                        Button::new(
+                            cx,
+                            |cx| cx.emit(EditorEvent::ClearMorph),
+                            |cx| Label::new(cx, "Clear"),
+                        )
+                        .class("morph-store")
+                        .top(Pixels(10.0));
+                    });
+                })
+                .col_between(Pixels(20.0))
+                .child_left(Stretch(1.0))
+                .child_right(Stretch(1.0));
+                // Adapts the host's MIDI input to the controller
+                HStack::new(cx, |cx| {
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Vel Scale", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| {
+                            &params.input_transform.velocity_scale
+                        });
+                        create_label(cx, "Vel Offset", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| {
+                            &params.input_transform.velocity_offset
+                        });
+                    });
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Vel Curve", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| {
+                            &params.input_transform.velocity_curve
+                        });
+                        create_label(cx, "Channel Remap", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| {
+                            &params.input_transform.channel
+                        });
+                    });
+                    VStack::new(cx, |cx| {
+                        create_label(cx, "Transpose", 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), |params| {
+                            &params.input_transform.transpose
+                        });
+                    });
+                })
+                .height(Auto)
+                .col_between(Pixels(20.0))
+                .child_left(Stretch(1.0))
+                .child_right(Stretch(1.0));
+            })
+            .height(Auto);
         });
         HStack::new(cx, |cx| {
             VStack::new(cx, |cx| {
-            
-                Label::new(cx, "Vib Int")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.vibrato_intensity);
-
-                Label::new(cx, "Vib Rate")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.vibrato_rate);
+                for idx in 0..NUM_MACROS {
+                    create_label(cx, &format!("Macro {}", idx + 1), 20.0, 100.0, 1.0, 0.0);
+                    ParamSlider::new(cx, Data::params.clone(), move |params| {
+                        &params.macros[idx].value
+                    });
+                }
             });
             VStack::new(cx, |cx| {
-                
-                Label::new(cx, "Vib Attack")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.vibrato_attack);
-                
-                Label::new(cx, "Vib Shape")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.vibrato_shape);
-            });
-            VStack::new(cx, |cx| {
-            
-                Label::new(cx, "Trem Int")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.tremolo_intensity);
-
-                Label::new(cx, "Tremo Rate")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.tremolo_rate);
-            });
-            VStack::new(cx, |cx| {
-                
-                Label::new(cx, "Tremo Atk")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.tremolo_attack);
-
-                
-                Label::new(cx, "Tremo Shape")
-                    .height(Pixels(20.0))
-                    .width(Pixels(100.0))
-                    .child_top(Stretch(1.0))
-                    .child_bottom(Pixels(0.0));
-                ParamSlider::new(cx, Data::params.clone(), |params| &params.tremolo_shape);
-
+                HStack::new(cx, |cx| {
+                    for title in ["Source", "Destination", "Amount", "Curve"] {
+                        create_label(cx, title, 20.0, 130.0, 1.0, 0.0);
+                    }
+                })
+                .col_between(Pixels(5.0));
+                for idx in 0..NUM_MOD_SLOTS {
+                    HStack::new(cx, |cx| {
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.mod_slots[idx].source
+                        })
+                        .class("mod-slot");
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.mod_slots[idx].destination
+                        })
+                        .class("mod-slot");
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.mod_slots[idx].amount
+                        })
+                        .class("mod-slot");
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.mod_slots[idx].curve
+                        })
+                        .class("mod-slot");
+                    })
+                    .height(Auto)
+                    .col_between(Pixels(5.0));
+                }
+                // The combiner feeds the matrix's Combiner source
+                HStack::new(cx, |cx| {
+                    for title in ["Combiner A", "Operation", "Combiner B", "B Amount"] {
+                        create_label(cx, title, 20.0, 130.0, 1.0, 0.0);
+                    }
+                })
+                .col_between(Pixels(5.0))
+                .top(Pixels(10.0));
+                HStack::new(cx, |cx| {
+                    ParamSlider::new(cx, Data::params.clone(), |params| {
+                        &params.combiner.source_a
+                    })
+                    .class("mod-slot");
+                    ParamSlider::new(cx, Data::params.clone(), |params| {
+                        &params.combiner.operation
+                    })
+                    .class("mod-slot");
+                    ParamSlider::new(cx, Data::params.clone(), |params| {
+                        &params.combiner.source_b
+                    })
+                    .class("mod-slot");
+                    ParamSlider::new(cx, Data::params.clone(), |params| {
+                        &params.combiner.amount_b
+                    })
+                    .class("mod-slot");
+                })
+                .height(Auto)
+                .col_between(Pixels(5.0));
+                // Shows the selected signal of the newest voice over the last few seconds
+                HStack::new(cx, |cx| {
+                    create_label(cx, "Scope", 20.0, 130.0, 1.0, 0.0);
+                    ParamSlider::new(cx, Data::params.clone(), |params| &params.scope_signal)
+                        .class("mod-slot");
+                })
+                .height(Auto)
+                .col_between(Pixels(5.0))
+                .top(Pixels(10.0));
+                ModScopeView::new(cx, mod_scope.clone())
+                    .width(Pixels(535.0))
+                    .height(Pixels(100.0));
             })
-            .row_between(Pixels(0.0))
-            .child_left(Stretch(1.0))
-            .child_right(Stretch(1.0));
-            
-        });
+            .row_between(Pixels(2.0));
+        })
+        .col_between(Pixels(20.0))
+        .child_left(Stretch(1.0))
+        .child_right(Stretch(1.0));
 
+        // The performance page covers everything below the title while it's open, and the CC map
+        // page covers the performance page
+        let performance_input = performance_input.clone();
+        Binding::new(cx, Data::show_performance_page, move |cx, show_performance_page| {
+            if show_performance_page.get(cx) {
+                PerformancePage::new(cx, performance_input.clone())
+                    .position_type(PositionType::SelfDirected)
+                    .top(Pixels(60.0))
+                    .child_space(Pixels(20.0))
+                    .row_between(Pixels(20.0));
+            }
+        });
+        let cc_map = params.cc_map.clone();
+        Binding::new(cx, Data::show_cc_map, move |cx, show_cc_map| {
+            if show_cc_map.get(cx) {
+                CcMapPage::new(cx, cc_map.clone())
+                    .position_type(PositionType::SelfDirected)
+                    .top(Pixels(60.0))
+                    .child_space(Pixels(20.0))
+                    .row_between(Pixels(5.0));
+            }
+        });
     })
 }
                 
+
+#[cfg(test)]
+mod tests {
+    use nih_plug::prelude::{Param, ParamPtr, Params};
+    use std::collections::{BTreeSet, HashSet};
+
+    use crate::{SubSynthParams, NUM_PARTS};
+
+    /// Params that deliberately don't have a control in the editor.
+    const WITHOUT_CONTROLS: &[&str] = &[];
+
+    /// Add the parameters at the given field paths below `$params` to `$paths`, along with the
+    /// paths the editor accesses them by.
+    macro_rules! add_paths {
+        ($paths:ident, $prefix:expr, $params:expr, [$($($field:ident).+),* $(,)?]) => {
+            $(
+                $paths.push((
+                    format!("{}.{}", $prefix, stringify!($($field).+)).replace(' ', ""),
+                    $params.$($field).+.as_ptr(),
+                ));
+            )*
+        };
+    }
+
+    /// Every parameter along with its path the way the editor accesses it, like
+    /// `.part().osc.waveform`. Both parts are written as `part()` and array indices are left out,
+    /// since that's how the editor's closures are matched below.
+    fn param_paths(params: &SubSynthParams) -> Vec<(String, ParamPtr)> {
+        let mut paths = Vec::new();
+        add_paths!(
+            paths,
+            "",
+            params,
+            [
+                master_gain,
+                patch_trim,
+                width,
+                haas_delay_ms,
+                haas_mono_safe,
+                morph,
+                latch,
+                pedal_mode,
+                fixed_velocity,
+                fixed_velocity_value,
+                freeze,
+                drone_gain,
+                multitimbral,
+                polyphony,
+                protect_lowest_note,
+                voice_reserve,
+                osc_quality,
+                lfo_transport_sync,
+                internal_tempo,
+                eco_idle,
+                bypass,
+                seed,
+                expression_glide_ms,
+                meter_mode,
+                meter_speed,
+                scope_signal,
+                velocity_crossfade.enabled,
+                velocity_crossfade.center,
+                velocity_crossfade.width,
+                velocity_crossfade.curve,
+                trigger.enabled,
+                trigger.threshold_db,
+                trigger.note_mode,
+                trigger.note,
+                input_transform.velocity_scale,
+                input_transform.velocity_offset,
+                input_transform.velocity_curve,
+                input_transform.channel,
+                input_transform.transpose,
+                combiner.source_a,
+                combiner.source_b,
+                combiner.operation,
+                combiner.amount_b,
+            ]
+        );
+        for macro_params in &params.macros {
+            add_paths!(paths, ".macros[]", macro_params, [value]);
+        }
+        for slot in &params.mod_slots {
+            add_paths!(
+                paths,
+                ".mod_slots[]",
+                slot,
+                [source, destination, amount, curve]
+            );
+        }
+
+        for part in 0..NUM_PARTS {
+            let part_params = params.part(part);
+            add_paths!(
+                paths,
+                ".part()",
+                part_params,
+                [
+                    gain,
+                    pan,
+                    midi_channel,
+                    choke_group,
+                    osc.source,
+                    osc.waveform,
+                    osc.supersaw_detune,
+                    osc.supersaw_mix,
+                    osc.pd_curve,
+                    osc.pd_amount,
+                    osc.pd_env_amount,
+                    percussion.harmonic,
+                    percussion.level,
+                    percussion.decay_ms,
+                    pitch_env.amount,
+                    pitch_env.source,
+                    pitch_env.attack_ms,
+                    pitch_env.decay_ms,
+                    glide.mode,
+                    glide.time_ms,
+                    glide.key_tracking,
+                    glide.velocity_tracking,
+                    glide.poly,
+                    strum.time_ms,
+                    strum.mode,
+                    amp_env.attack_ms,
+                    amp_env.decay_ms,
+                    amp_env.sustain_level,
+                    amp_env.release_ms,
+                    amp_env.envelope_level,
+                    amp_env.velocity_sensitivity,
+                    amp_env.velocity_curve,
+                    filter.filter_type,
+                    filter.cutoff,
+                    filter.resonance,
+                    filter.morph,
+                    filter.fm_amount,
+                    filter.tone,
+                    filter.tone_key_tracking,
+                    filter.envelope_amount,
+                    filter.envelope_resonance_ratio,
+                    filter.release_freeze,
+                    filter.cutoff_note_display,
+                    filter_cut_env.attack_ms,
+                    filter_cut_env.decay_ms,
+                    filter_cut_env.sustain_level,
+                    filter_cut_env.release_ms,
+                    filter_cut_env.envelope_level,
+                    filter_cut_env.velocity_sensitivity,
+                    filter_cut_env.velocity_curve,
+                    filter_res_env.attack_ms,
+                    filter_res_env.decay_ms,
+                    filter_res_env.sustain_level,
+                    filter_res_env.release_ms,
+                    filter_res_env.envelope_level,
+                    filter_res_env.velocity_sensitivity,
+                    filter_res_env.velocity_curve,
+                    vibrato.attack_ms,
+                    vibrato.intensity,
+                    vibrato.rate,
+                    vibrato.shape,
+                    vibrato.mode,
+                    vibrato.scope,
+                    vibrato.sync,
+                    tremolo.attack_ms,
+                    tremolo.intensity,
+                    tremolo.rate,
+                    tremolo.shape,
+                    tremolo.mode,
+                    tremolo.scope,
+                    tremolo.sync,
+                    mseg.amount,
+                    mseg.destination,
+                    mseg.length_ms,
+                    mseg.sync,
+                    drift.depth,
+                    drift.rate,
+                    surround.spread_x,
+                    surround.spread_y,
+                    surround.position_y,
+                ]
+            );
+            for drawbar in &part_params.drawbars {
+                add_paths!(paths, ".part().drawbars[]", drawbar, [level]);
+            }
+        }
+
+        paths
+    }
+
+    /// The parameters the editor's widgets are created for, taken from the `&params.` closures.
+    /// Method arguments and indices are left out, so `&params.part(part).drawbars[idx].level`
+    /// becomes `.part().drawbars[].level`. These tests are skipped.
+    fn editor_paths(source: &str) -> BTreeSet<String> {
+        let source = source.split("#[cfg(test)]").next().unwrap();
+        let mut paths = BTreeSet::new();
+        for (idx, _) in source.match_indices("&params.") {
+            let mut path = String::new();
+            let mut chars = source[idx + "&params".len()..].chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '(' | '[' => {
+                        let close = if c == '(' { ')' } else { ']' };
+                        chars.by_ref().find(|&c| c == close);
+                        path.push(c);
+                        path.push(close);
+                    }
+                    '.' | '_' => path.push(c),
+                    c if c.is_alphanumeric() => path.push(c),
+                    _ => break,
+                }
+            }
+            paths.insert(path);
+        }
+
+        paths
+    }
+
+    #[test]
+    fn test_editor_covers_all_params() {
+        // The paths are checked against the plugin's own parameter list, so a new parameter can't
+        // slip past this test
+        let params = SubSynthParams::default();
+        let paths = param_paths(&params);
+        let named: HashSet<ParamPtr> = paths.iter().map(|(_, param_ptr)| *param_ptr).collect();
+        let unnamed: Vec<_> = params
+            .param_map()
+            .into_iter()
+            .filter(|(_, param_ptr, _)| !named.contains(param_ptr))
+            .map(|(id, _, _)| id)
+            .collect();
+        assert!(
+            unnamed.is_empty(),
+            "These params are missing from param_paths(): {unnamed:?}"
+        );
+        let params: BTreeSet<String> = paths.into_iter().map(|(path, _)| path).collect();
+
+        let mut editor = editor_paths(include_str!("editor.rs"));
+        editor.extend(editor_paths(include_str!("editor/cc_map_page.rs")));
+        editor.extend(editor_paths(include_str!("editor/mseg_editor.rs")));
+
+        let unknown: Vec<_> = editor.difference(&params).collect();
+        assert!(
+            unknown.is_empty(),
+            "The editor has controls for unknown params: {unknown:?}"
+        );
+        let missing: Vec<_> = params
+            .iter()
+            .filter(|path| !editor.contains(*path) && !WITHOUT_CONTROLS.contains(&path.as_str()))
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "These params have no control in the editor: {missing:?}"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! A page listing all MIDI CC mappings, where mappings can be added, edited and removed.
+
+use nih_plug_vizia::vizia::prelude::*;
+use std::sync::{Arc, RwLock};
+
+use crate::cc_map::{CcMap, CcMapping};
+
+/// Shows one row per mapping. Every field is a text box, except for the destination which cycles
+/// through the mod matrix's destinations and the stepped destinations when clicked. The channel is
+/// either a number from 1 to 16 or "Omni".
+#[derive(Lens)]
+pub struct CcMapPage {
+    cc_map: Arc<RwLock<CcMap>>,
+    /// A copy of the mappings for the widgets to display. Updated whenever the map changes.
+    mappings: Vec<CcMapping>,
+}
+
+enum CcMapEvent {
+    Add,
+    Remove(usize),
+    SetCc(usize, String),
+    SetChannel(usize, String),
+    NextDestination(usize),
+    SetMin(usize, String),
+    SetMax(usize, String),
+}
+
+impl CcMapPage {
+    pub fn new(cx: &mut Context, cc_map: Arc<RwLock<CcMap>>) -> Handle<Self> {
+        let mappings = cc_map
+            .read()
+            .map(|cc_map| cc_map.mappings().to_vec())
+            .unwrap_or_default();
+
+        Self { cc_map, mappings }.build(cx, |cx| {
+            HStack::new(cx, |cx| {
+                for title in ["CC", "Channel", "Destination", "Min", "Max"] {
+                    Label::new(cx, title).class("cc-map-field");
+                }
+            })
+            .height(Auto)
+            .col_between(Pixels(5.0));
+
+            // The rows are rebuilt whenever a mapping is added or removed
+            Binding::new(
+                cx,
+                CcMapPage::mappings.map(|mappings| mappings.len()),
+                |cx, num_mappings| {
+                    for idx in 0..num_mappings.get(cx) {
+                        Self::mapping_row(cx, idx);
+                    }
+                },
+            );
+
+            Button::new(
+                cx,
+                |cx| cx.emit(CcMapEvent::Add),
+                |cx| Label::new(cx, "Add Mapping"),
+            )
+            .class("cc-map-button");
+        })
+    }
+
+    fn mapping_row(cx: &mut Context, idx: usize) {
+        let field = move |format: fn(&CcMapping) -> String| {
+            CcMapPage::mappings.map(move |mappings| {
+                mappings.get(idx).map(format).unwrap_or_default()
+            })
+        };
+
+        HStack::new(cx, move |cx| {
+            Textbox::new(cx, field(|mapping| mapping.cc.to_string()))
+                .on_submit(move |cx, text, success| {
+                    if success {
+                        cx.emit(CcMapEvent::SetCc(idx, text));
+                    }
+                })
+                .class("cc-map-field");
+            Textbox::new(
+                cx,
+                field(|mapping| match mapping.channel {
+                    Some(channel) => (channel + 1).to_string(),
+                    None => String::from("Omni"),
+                }),
+            )
+            .on_submit(move |cx, text, success| {
+                if success {
+                    cx.emit(CcMapEvent::SetChannel(idx, text));
+                }
+            })
+            .class("cc-map-field");
+            Button::new(
+                cx,
+                move |cx| cx.emit(CcMapEvent::NextDestination(idx)),
+                |cx| {
+                    Label::new(cx, field(|mapping| mapping.destination_name().to_owned()))
+                },
+            )
+            .class("cc-map-field");
+            Textbox::new(cx, field(|mapping| format!("{:.2}", mapping.min)))
+                .on_submit(move |cx, text, success| {
+                    if success {
+                        cx.emit(CcMapEvent::SetMin(idx, text));
+                    }
+                })
+                .class("cc-map-field");
+            Textbox::new(cx, field(|mapping| format!("{:.2}", mapping.max)))
+                .on_submit(move |cx, text, success| {
+                    if success {
+                        cx.emit(CcMapEvent::SetMax(idx, text));
+                    }
+                })
+                .class("cc-map-field");
+            Button::new(
+                cx,
+                move |cx| cx.emit(CcMapEvent::Remove(idx)),
+                |cx| Label::new(cx, "Remove"),
+            )
+            .class("cc-map-button");
+        })
+        .height(Auto)
+        .col_between(Pixels(5.0));
+    }
+}
+
+/// Parse a channel as shown on the page. Returns `Some(None)` for omni, and `None` if the text isn't
+/// a valid channel.
+fn parse_channel(text: &str) -> Option<Option<u8>> {
+    let text = text.trim();
+    if text.is_empty() || text.eq_ignore_ascii_case("omni") {
+        return Some(None);
+    }
+
+    match text.parse::<u8>() {
+        Ok(channel @ 1..=16) => Some(Some(channel - 1)),
+        _ => None,
+    }
+}
+
+impl View for CcMapPage {
+    fn element(&self) -> Option<&'static str> {
+        Some("cc-map-page")
+    }
+
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|cc_map_event, meta| {
+            let mut cc_map = self.cc_map.write().expect("Poisoned CC map lock");
+            let mut update = |idx: usize, f: &dyn Fn(&mut CcMapping)| {
+                if let Some(mut mapping) = cc_map.mappings().get(idx).copied() {
+                    f(&mut mapping);
+                    cc_map.set(idx, mapping);
+                }
+            };
+
+            // Invalid input is simply ignored, the text box then reverts to the old value
+            match cc_map_event {
+                CcMapEvent::Add => {
+                    cc_map.add(CcMapping::default());
+                }
+                CcMapEvent::Remove(idx) => cc_map.remove(*idx),
+                CcMapEvent::SetCc(idx, text) => {
+                    if let Ok(cc) = text.trim().parse::<u8>() {
+                        update(*idx, &|mapping| mapping.cc = cc);
+                    }
+                }
+                CcMapEvent::SetChannel(idx, text) => {
+                    if let Some(channel) = parse_channel(text) {
+                        update(*idx, &|mapping| mapping.channel = channel);
+                    }
+                }
+                CcMapEvent::NextDestination(idx) => {
+                    update(*idx, &|mapping| mapping.next_destination())
+                }
+                CcMapEvent::SetMin(idx, text) => {
+                    if let Ok(min) = text.trim().parse::<f32>() {
+                        update(*idx, &|mapping| mapping.min = min);
+                    }
+                }
+                CcMapEvent::SetMax(idx, text) => {
+                    if let Ok(max) = text.trim().parse::<f32>() {
+                        update(*idx, &|mapping| mapping.max = max);
+                    }
+                }
+            }
+
+            self.mappings = cc_map.mappings().to_vec();
+            meta.consume();
+        });
+    }
+}
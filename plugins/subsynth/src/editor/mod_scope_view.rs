@@ -0,0 +1,66 @@
+//! Draws the modulation scope.
+
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use std::sync::Arc;
+
+use crate::mod_scope::{ModScope, SCOPE_SIZE};
+
+/// Shows the last few seconds of the selected modulation signal, with the newest values on the
+/// right. The vertical range goes from -1 to 1 so bipolar and unipolar signals can be compared.
+pub struct ModScopeView {
+    scope: Arc<ModScope>,
+}
+
+impl ModScopeView {
+    pub fn new(cx: &mut Context, scope: Arc<ModScope>) -> Handle<Self> {
+        Self { scope }.build(cx, |_| {})
+    }
+}
+
+impl View for ModScopeView {
+    fn element(&self) -> Option<&'static str> {
+        Some("mod-scope")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let background_color: vg::Color =
+            cx.background_color().copied().unwrap_or_default().into();
+        let font_color: vg::Color = cx.font_color().copied().unwrap_or(Color::white()).into();
+        let line_width = cx.logical_to_physical(1.5);
+
+        let mut path = vg::Path::new();
+        path.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&mut path, &vg::Paint::color(background_color));
+
+        let to_x = |idx: usize| bounds.x + idx as f32 / (SCOPE_SIZE - 1) as f32 * bounds.w;
+        let to_y = |value: f32| bounds.y + (1.0 - value.clamp(-1.0, 1.0)) * 0.5 * bounds.h;
+
+        // The zero line makes it easier to tell unipolar signals from bipolar ones
+        let mut zero_color = font_color;
+        zero_color.set_alphaf(0.25);
+        let mut path = vg::Path::new();
+        path.move_to(bounds.x, to_y(0.0));
+        path.line_to(bounds.x + bounds.w, to_y(0.0));
+        let mut paint = vg::Paint::color(zero_color);
+        paint.set_line_width(cx.logical_to_physical(1.0));
+        canvas.stroke_path(&mut path, &paint);
+
+        let mut path = vg::Path::new();
+        for (idx, value) in self.scope.values().enumerate() {
+            if idx == 0 {
+                path.move_to(to_x(idx), to_y(value));
+            } else {
+                path.line_to(to_x(idx), to_y(value));
+            }
+        }
+        let mut paint = vg::Paint::color(font_color);
+        paint.set_line_width(line_width);
+        canvas.stroke_path(&mut path, &paint);
+    }
+}
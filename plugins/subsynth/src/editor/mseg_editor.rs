@@ -0,0 +1,196 @@
+//! A graphical editor for the MSEG's breakpoints.
+
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use nih_plug_vizia::widgets::util::ModifiersExt;
+use std::sync::{Arc, RwLock};
+
+use crate::mseg::MsegShape;
+
+/// How close the cursor needs to be to a point to grab it, in logical pixels.
+const GRAB_RADIUS: f32 = 6.0;
+/// The radius of the handles drawn for every point, in logical pixels.
+const POINT_RADIUS: f32 = 3.5;
+/// How much a single scroll wheel step changes a segment's curvature.
+const CURVE_SCROLL_STEP: f32 = 0.1;
+
+/// Draws the MSEG's shape and lets the user edit it:
+///
+/// - Click on an empty spot to add a point, and drag points around to move them.
+/// - Double click a point to remove it.
+/// - Scroll over a segment to change its curvature.
+/// - Right click a point to start the loop there, and Shift+right click a point to end the loop
+///   there. Right clicking an empty spot removes the loop.
+pub struct MsegEditor {
+    shape: Arc<RwLock<MsegShape>>,
+    /// The index of the point that's currently being dragged, if any.
+    drag_point: Option<usize>,
+}
+
+impl MsegEditor {
+    pub fn new(cx: &mut Context, shape: Arc<RwLock<MsegShape>>) -> Handle<Self> {
+        Self {
+            shape,
+            drag_point: None,
+        }
+        .build(cx, |_| {})
+    }
+
+    /// Convert the cursor's position to a time and a value within the envelope.
+    fn cursor_to_envelope(cx: &EventContext) -> (f32, f32) {
+        let bounds = cx.cache.get_bounds(cx.current());
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        (
+            ((cx.mouse.cursorx - bounds.x) / bounds.w).clamp(0.0, 1.0),
+            (1.0 - (cx.mouse.cursory - bounds.y) / bounds.h).clamp(0.0, 1.0),
+        )
+    }
+
+    /// The index of the point under the cursor, if there is one.
+    fn point_under_cursor(cx: &EventContext, shape: &MsegShape) -> Option<usize> {
+        let bounds = cx.cache.get_bounds(cx.current());
+        let grab_radius = GRAB_RADIUS * cx.style.dpi_factor as f32;
+
+        shape.points().iter().position(|point| {
+            let x = bounds.x + point.time * bounds.w;
+            let y = bounds.y + (1.0 - point.value) * bounds.h;
+            (cx.mouse.cursorx - x).abs() <= grab_radius
+                && (cx.mouse.cursory - y).abs() <= grab_radius
+        })
+    }
+}
+
+impl View for MsegEditor {
+    fn element(&self) -> Option<&'static str> {
+        Some("mseg-editor")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| {
+            // The audio thread only ever tries to read the shape, so this won't block it
+            let mut shape = self.shape.write().expect("Poisoned MSEG shape lock");
+
+            match *window_event {
+                WindowEvent::MouseDown(MouseButton::Left) => {
+                    let (time, value) = Self::cursor_to_envelope(cx);
+                    self.drag_point = Self::point_under_cursor(cx, &shape)
+                        .or_else(|| shape.insert_point(time, value));
+
+                    cx.capture();
+                    cx.set_active(true);
+                    cx.needs_redraw();
+                    meta.consume();
+                }
+                WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                    if let Some(idx) = Self::point_under_cursor(cx, &shape) {
+                        shape.remove_point(idx);
+                        self.drag_point = None;
+
+                        cx.needs_redraw();
+                        meta.consume();
+                    }
+                }
+                WindowEvent::MouseDown(MouseButton::Right) => {
+                    let loop_region = shape.loop_region();
+                    match Self::point_under_cursor(cx, &shape) {
+                        Some(idx) if cx.modifiers.shift() => {
+                            let start = loop_region.map(|(start, _)| start).unwrap_or(0);
+                            shape.set_loop_region(Some((start, idx)));
+                        }
+                        Some(idx) => {
+                            let end = loop_region
+                                .map(|(_, end)| end)
+                                .unwrap_or(shape.points().len() - 1);
+                            shape.set_loop_region(Some((idx, end)));
+                        }
+                        None => shape.set_loop_region(None),
+                    }
+
+                    cx.needs_redraw();
+                    meta.consume();
+                }
+                WindowEvent::MouseUp(MouseButton::Left) => {
+                    if self.drag_point.take().is_some() {
+                        cx.release();
+                        cx.set_active(false);
+                    }
+                }
+                WindowEvent::MouseMove(_, _) => {
+                    if let Some(idx) = self.drag_point {
+                        let (time, value) = Self::cursor_to_envelope(cx);
+                        shape.move_point(idx, time, value);
+
+                        cx.needs_redraw();
+                    }
+                }
+                WindowEvent::MouseScroll(_, scroll_y) => {
+                    let (time, _) = Self::cursor_to_envelope(cx);
+                    let idx = shape.segment_at(time);
+                    let curve = shape.points()[idx].curve;
+                    shape.set_curve(idx, curve + scroll_y * CURVE_SCROLL_STEP);
+
+                    cx.needs_redraw();
+                    meta.consume();
+                }
+                _ => {}
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let background_color: vg::Color =
+            cx.background_color().copied().unwrap_or_default().into();
+        let font_color: vg::Color = cx.font_color().copied().unwrap_or(Color::white()).into();
+        let line_width = cx.logical_to_physical(1.5);
+        let point_radius = cx.logical_to_physical(POINT_RADIUS);
+
+        let mut path = vg::Path::new();
+        path.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&mut path, &vg::Paint::color(background_color));
+
+        let shape = match self.shape.read() {
+            Ok(shape) => *shape,
+            Err(_) => return,
+        };
+        let to_x = |time: f32| bounds.x + time * bounds.w;
+        let to_y = |value: f32| bounds.y + (1.0 - value) * bounds.h;
+
+        // The loop region is shaded so it stands out from the rest of the envelope
+        if let Some((start, end)) = shape.loop_region() {
+            let start_x = to_x(shape.points()[start].time);
+            let end_x = to_x(shape.points()[end].time);
+            let mut loop_color = font_color;
+            loop_color.set_alphaf(0.15);
+
+            let mut path = vg::Path::new();
+            path.rect(start_x, bounds.y, end_x - start_x, bounds.h);
+            canvas.fill_path(&mut path, &vg::Paint::color(loop_color));
+        }
+
+        // Curved segments are drawn as a series of short lines, one every couple of pixels
+        let mut path = vg::Path::new();
+        path.move_to(to_x(0.0), to_y(shape.value_at(0.0)));
+        let num_steps = (bounds.w / 2.0).ceil().max(1.0) as usize;
+        for step in 1..=num_steps {
+            let time = step as f32 / num_steps as f32;
+            path.line_to(to_x(time), to_y(shape.value_at(time)));
+        }
+        let mut paint = vg::Paint::color(font_color);
+        paint.set_line_width(line_width);
+        canvas.stroke_path(&mut path, &paint);
+
+        let mut path = vg::Path::new();
+        for point in shape.points() {
+            path.circle(to_x(point.time), to_y(point.value), point_radius);
+        }
+        canvas.fill_path(&mut path, &vg::Paint::color(font_color));
+    }
+}
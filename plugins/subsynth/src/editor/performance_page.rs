@@ -0,0 +1,341 @@
+//! A simplified page for live use, with only the macros, virtual pitch and mod wheels, and an
+//! on-screen keyboard.
+
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use nih_plug_vizia::widgets::ParamSlider;
+use std::sync::Arc;
+
+use super::{create_label, Data};
+use crate::modmatrix::NUM_MACROS;
+use crate::performance::{PerformanceInput, KEYBOARD_LOWEST_NOTE, KEYBOARD_NUM_KEYS};
+
+/// The notes within an octave that are black keys.
+const BLACK_KEYS: [u8; 5] = [1, 3, 6, 8, 10];
+/// How wide and long the black keys are compared to the white keys.
+const BLACK_KEY_WIDTH: f32 = 0.6;
+const BLACK_KEY_HEIGHT: f32 = 0.6;
+
+pub struct PerformancePage;
+
+impl PerformancePage {
+    pub fn new(cx: &mut Context, input: Arc<PerformanceInput>) -> Handle<Self> {
+        Self.build(cx, |cx| {
+            HStack::new(cx, |cx| {
+                VStack::new(cx, |cx| {
+                    for idx in 0..NUM_MACROS {
+                        create_label(cx, &format!("Macro {}", idx + 1), 20.0, 100.0, 1.0, 0.0);
+                        ParamSlider::new(cx, Data::params.clone(), move |params| {
+                            &params.macros[idx].value
+                        });
+                    }
+                })
+                .height(Auto);
+                for (wheel, name) in [(Wheel::Pitch, "Pitch"), (Wheel::Mod, "Mod")] {
+                    VStack::new(cx, |cx| {
+                        create_label(cx, name, 20.0, 60.0, 1.0, 0.0);
+                        WheelView::new(cx, input.clone(), wheel).class("performance-wheel");
+                    })
+                    .height(Auto)
+                    .width(Auto);
+                }
+            })
+            .height(Auto)
+            .col_between(Pixels(20.0));
+            KeyboardView::new(cx, input.clone()).class("performance-keyboard");
+        })
+    }
+}
+
+impl View for PerformancePage {
+    fn element(&self) -> Option<&'static str> {
+        Some("performance-page")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wheel {
+    /// Springs back to the center when released.
+    Pitch,
+    /// Stays where it's left.
+    Mod,
+}
+
+/// A virtual wheel, dragged up and down.
+struct WheelView {
+    input: Arc<PerformanceInput>,
+    wheel: Wheel,
+    dragging: bool,
+}
+
+impl WheelView {
+    fn new(cx: &mut Context, input: Arc<PerformanceInput>, wheel: Wheel) -> Handle<Self> {
+        Self {
+            input,
+            wheel,
+            dragging: false,
+        }
+        .build(cx, |_| {})
+    }
+
+    /// The wheel's value, between 0 at the bottom and 1 at the top.
+    fn position(&self) -> f32 {
+        match self.wheel {
+            Wheel::Pitch => (self.input.pitch_wheel() + 1.0) * 0.5,
+            Wheel::Mod => self.input.mod_wheel(),
+        }
+    }
+
+    fn set_position(&self, position: f32) {
+        match self.wheel {
+            Wheel::Pitch => self.input.set_pitch_wheel(position * 2.0 - 1.0),
+            Wheel::Mod => self.input.set_mod_wheel(position),
+        }
+    }
+
+    fn cursor_position(cx: &EventContext) -> f32 {
+        let bounds = cx.cache.get_bounds(cx.current());
+        if bounds.h == 0.0 {
+            return 0.0;
+        }
+
+        (1.0 - (cx.mouse.cursory - bounds.y) / bounds.h).clamp(0.0, 1.0)
+    }
+}
+
+impl View for WheelView {
+    fn element(&self) -> Option<&'static str> {
+        Some("performance-wheel")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| match *window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                self.dragging = true;
+                self.set_position(Self::cursor_position(cx));
+
+                cx.capture();
+                cx.set_active(true);
+                cx.needs_redraw();
+                meta.consume();
+            }
+            WindowEvent::MouseMove(_, _) => {
+                if self.dragging {
+                    self.set_position(Self::cursor_position(cx));
+                    cx.needs_redraw();
+                }
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.dragging {
+                    self.dragging = false;
+                    if self.wheel == Wheel::Pitch {
+                        self.set_position(0.5);
+                    }
+
+                    cx.release();
+                    cx.set_active(false);
+                    cx.needs_redraw();
+                }
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let background_color: vg::Color =
+            cx.background_color().copied().unwrap_or_default().into();
+        let font_color: vg::Color = cx.font_color().copied().unwrap_or(Color::white()).into();
+        let mut fill_color = font_color;
+        fill_color.set_alphaf(0.4);
+
+        let mut path = vg::Path::new();
+        path.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&mut path, &vg::Paint::color(background_color));
+
+        // The pitch wheel fills from the center, the mod wheel from the bottom
+        let to_y = |position: f32| bounds.y + (1.0 - position) * bounds.h;
+        let rest_y = match self.wheel {
+            Wheel::Pitch => to_y(0.5),
+            Wheel::Mod => to_y(0.0),
+        };
+        let value_y = to_y(self.position());
+        let mut path = vg::Path::new();
+        path.rect(
+            bounds.x,
+            value_y.min(rest_y),
+            bounds.w,
+            (value_y - rest_y).abs(),
+        );
+        canvas.fill_path(&mut path, &vg::Paint::color(fill_color));
+
+        let mut path = vg::Path::new();
+        path.move_to(bounds.x, value_y);
+        path.line_to(bounds.x + bounds.w, value_y);
+        let mut paint = vg::Paint::color(font_color);
+        paint.set_line_width(cx.logical_to_physical(2.0));
+        canvas.stroke_path(&mut path, &paint);
+    }
+}
+
+/// The on-screen keyboard. Dragging across the keys slides from note to note.
+struct KeyboardView {
+    input: Arc<PerformanceInput>,
+    /// The key that's currently held with the mouse, if any.
+    held_note: Option<u8>,
+}
+
+impl KeyboardView {
+    fn new(cx: &mut Context, input: Arc<PerformanceInput>) -> Handle<Self> {
+        Self {
+            input,
+            held_note: None,
+        }
+        .build(cx, |_| {})
+    }
+
+    fn notes() -> impl Iterator<Item = u8> {
+        KEYBOARD_LOWEST_NOTE..KEYBOARD_LOWEST_NOTE + KEYBOARD_NUM_KEYS
+    }
+
+    fn is_black(note: u8) -> bool {
+        BLACK_KEYS.contains(&(note % 12))
+    }
+
+    fn num_white_keys() -> usize {
+        Self::notes().filter(|&note| !Self::is_black(note)).count()
+    }
+
+    /// A key's left edge and width relative to the keyboard's width, and whether it's black.
+    fn key_rect(note: u8) -> (f32, f32, bool) {
+        let white_width = 1.0 / Self::num_white_keys() as f32;
+        let white_keys_before = (KEYBOARD_LOWEST_NOTE..note)
+            .filter(|&note| !Self::is_black(note))
+            .count() as f32;
+        if Self::is_black(note) {
+            // Black keys sit on the border between the white keys around them
+            let width = white_width * BLACK_KEY_WIDTH;
+            (white_keys_before * white_width - width / 2.0, width, true)
+        } else {
+            (white_keys_before * white_width, white_width, false)
+        }
+    }
+
+    fn note_under_cursor(cx: &EventContext) -> Option<u8> {
+        let bounds = cx.cache.get_bounds(cx.current());
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return None;
+        }
+
+        let x = (cx.mouse.cursorx - bounds.x) / bounds.w;
+        let y = (cx.mouse.cursory - bounds.y) / bounds.h;
+        if !(0.0..1.0).contains(&x) || !(0.0..1.0).contains(&y) {
+            return None;
+        }
+
+        // The black keys lie on top of the white keys
+        let contains = |note: u8| {
+            let (left, width, _) = Self::key_rect(note);
+            (left..left + width).contains(&x)
+        };
+        Self::notes()
+            .filter(|&note| Self::is_black(note) && y < BLACK_KEY_HEIGHT)
+            .find(|&note| contains(note))
+            .or_else(|| {
+                Self::notes()
+                    .filter(|&note| !Self::is_black(note))
+                    .find(|&note| contains(note))
+            })
+    }
+
+    fn hold_note(&mut self, note: Option<u8>) {
+        if note == self.held_note {
+            return;
+        }
+
+        if let Some(held_note) = self.held_note {
+            self.input.set_key_held(held_note, false);
+        }
+        if let Some(note) = note {
+            self.input.set_key_held(note, true);
+        }
+        self.held_note = note;
+    }
+}
+
+impl View for KeyboardView {
+    fn element(&self) -> Option<&'static str> {
+        Some("performance-keyboard")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| match *window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                self.hold_note(Self::note_under_cursor(cx));
+
+                cx.capture();
+                cx.set_active(true);
+                cx.needs_redraw();
+                meta.consume();
+            }
+            WindowEvent::MouseMove(_, _) => {
+                if self.held_note.is_some() {
+                    self.hold_note(Self::note_under_cursor(cx));
+                    cx.needs_redraw();
+                }
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                self.hold_note(None);
+
+                cx.release();
+                cx.set_active(false);
+                cx.needs_redraw();
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let background_color: vg::Color =
+            cx.background_color().copied().unwrap_or_default().into();
+        let font_color: vg::Color = cx.font_color().copied().unwrap_or(Color::white()).into();
+        let mut held_color = font_color;
+        held_color.set_alphaf(0.5);
+        let line_width = cx.logical_to_physical(1.0);
+
+        // White keys first so the black keys end up on top of them
+        let white_keys = Self::notes().filter(|&note| !Self::is_black(note));
+        let black_keys = Self::notes().filter(|&note| Self::is_black(note));
+        for note in white_keys.chain(black_keys) {
+            let (left, width, black) = Self::key_rect(note);
+            let height = if black {
+                bounds.h * BLACK_KEY_HEIGHT
+            } else {
+                bounds.h
+            };
+            let color = if self.input.is_key_held(note) {
+                held_color
+            } else if black {
+                background_color
+            } else {
+                font_color
+            };
+
+            let mut path = vg::Path::new();
+            path.rect(bounds.x + left * bounds.w, bounds.y, width * bounds.w, height);
+            canvas.fill_path(&mut path, &vg::Paint::color(color));
+            let mut paint = vg::Paint::color(background_color);
+            paint.set_line_width(line_width);
+            canvas.stroke_path(&mut path, &paint);
+        }
+    }
+}
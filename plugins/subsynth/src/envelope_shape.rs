@@ -0,0 +1,98 @@
+//! Quick envelope shapes. These set the amp and filter cutoff envelopes to common archetypes in one
+//! go, as a starting point for further tweaking.
+
+use crate::params::PatchParams;
+use nih_plug::prelude::*;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum EnvelopeShape {
+    /// Instant attack and a short decay down to silence.
+    Pluck,
+    /// Slow attack and a long release.
+    Pad,
+    /// On and off like a switch.
+    Organ,
+    /// A slightly soft attack that settles into a lower sustain.
+    Brass,
+    /// A reverse envelope that slowly swells in and cuts off quickly.
+    Swell,
+}
+
+/// An envelope's settings for a shape. The sustain is the sustain parameter's plain value.
+#[derive(Debug, Clone, Copy)]
+struct EnvelopeSettings {
+    attack_ms: f32,
+    decay_ms: f32,
+    sustain: f32,
+    release_ms: f32,
+}
+
+impl EnvelopeShape {
+    pub const ALL: [EnvelopeShape; 5] = [
+        EnvelopeShape::Pluck,
+        EnvelopeShape::Pad,
+        EnvelopeShape::Organ,
+        EnvelopeShape::Brass,
+        EnvelopeShape::Swell,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EnvelopeShape::Pluck => "Pluck",
+            EnvelopeShape::Pad => "Pad",
+            EnvelopeShape::Organ => "Organ",
+            EnvelopeShape::Brass => "Brass",
+            EnvelopeShape::Swell => "Swell",
+        }
+    }
+
+    /// The amp envelope's and the filter cutoff envelope's settings.
+    fn settings(self) -> (EnvelopeSettings, EnvelopeSettings) {
+        let settings = |attack_ms, decay_ms, sustain, release_ms| EnvelopeSettings {
+            attack_ms,
+            decay_ms,
+            sustain,
+            release_ms,
+        };
+
+        match self {
+            EnvelopeShape::Pluck => (
+                settings(1.0, 250.0, 0.0, 150.0),
+                settings(0.0, 200.0, 0.0, 150.0),
+            ),
+            EnvelopeShape::Pad => (
+                settings(800.0, 2000.0, 0.8, 2500.0),
+                settings(1200.0, 3000.0, 0.6, 2500.0),
+            ),
+            EnvelopeShape::Organ => (
+                settings(5.0, 10.0, 1.0, 30.0),
+                settings(0.0, 10.0, 1.0, 30.0),
+            ),
+            EnvelopeShape::Brass => (
+                settings(60.0, 400.0, 0.7, 200.0),
+                settings(80.0, 500.0, 0.4, 250.0),
+            ),
+            EnvelopeShape::Swell => (
+                settings(2500.0, 10.0, 1.0, 80.0),
+                settings(2500.0, 10.0, 1.0, 80.0),
+            ),
+        }
+    }
+
+    /// The parameters this shape sets, paired with their new normalized values.
+    pub fn param_values(self, params: &PatchParams) -> Vec<(ParamPtr, f32)> {
+        let (amp, filter_cut) = self.settings();
+        let value = |param: &FloatParam, plain: f32| (param.as_ptr(), param.preview_normalized(plain));
+
+        vec![
+            value(&params.amp_env.attack_ms, amp.attack_ms),
+            value(&params.amp_env.decay_ms, amp.decay_ms),
+            value(&params.amp_env.sustain_level, amp.sustain),
+            value(&params.amp_env.release_ms, amp.release_ms),
+            value(&params.filter_cut_env.attack_ms, filter_cut.attack_ms),
+            value(&params.filter_cut_env.decay_ms, filter_cut.decay_ms),
+            value(&params.filter_cut_env.sustain_level, filter_cut.sustain),
+            value(&params.filter_cut_env.release_ms, filter_cut.release_ms),
+        ]
+    }
+}
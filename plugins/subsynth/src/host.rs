@@ -0,0 +1,67 @@
+//! What the synth needs from the host while processing audio. NIH-plug's process context carries
+//! the host's transport, which only NIH-plug's wrappers can create. So the synth takes a copy of the
+//! parts of the transport it uses, and the rest of the context goes through [`HostContext`]. That
+//! way the tests can drive the synth without a plugin wrapper.
+
+use nih_plug::prelude::*;
+
+use crate::SubSynth;
+
+/// The parts of the host's transport the synth uses, as of the start of the buffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HostTransport {
+    pub playing: bool,
+    pub sample_rate: f32,
+    /// `None` if the host doesn't send a tempo.
+    pub tempo: Option<f64>,
+    pub time_sig_denominator: Option<i32>,
+    /// The position in the song in samples.
+    pub pos_samples: Option<i64>,
+    /// The position in the song in quarter notes.
+    pub pos_beats: Option<f64>,
+    /// The current bar's start position in quarter notes.
+    pub bar_start_pos_beats: Option<f64>,
+    /// The zero-based number of the current bar.
+    pub bar_number: Option<i32>,
+}
+
+impl From<&Transport> for HostTransport {
+    fn from(transport: &Transport) -> Self {
+        Self {
+            playing: transport.playing,
+            sample_rate: transport.sample_rate,
+            tempo: transport.tempo,
+            time_sig_denominator: transport.time_sig_denominator,
+            pos_samples: transport.pos_samples(),
+            pos_beats: transport.pos_beats(),
+            bar_start_pos_beats: transport.bar_start_pos_beats(),
+            bar_number: transport.bar_number(),
+        }
+    }
+}
+
+/// The rest of [`ProcessContext`] the synth uses.
+pub trait HostContext {
+    /// See [`ProcessContext::next_event()`].
+    fn next_event(&mut self) -> Option<PluginNoteEvent<SubSynth>>;
+
+    /// See [`ProcessContext::send_event()`].
+    fn send_event(&mut self, event: PluginNoteEvent<SubSynth>);
+
+    /// See [`ProcessContext::set_current_voice_capacity()`].
+    fn set_current_voice_capacity(&self, capacity: u32);
+}
+
+impl<C: ProcessContext<SubSynth>> HostContext for C {
+    fn next_event(&mut self) -> Option<PluginNoteEvent<SubSynth>> {
+        ProcessContext::next_event(self)
+    }
+
+    fn send_event(&mut self, event: PluginNoteEvent<SubSynth>) {
+        ProcessContext::send_event(self, event)
+    }
+
+    fn set_current_voice_capacity(&self, capacity: u32) {
+        ProcessContext::set_current_voice_capacity(self, capacity)
+    }
+}
@@ -0,0 +1,203 @@
+//! Adapts the host's MIDI input to the controller it comes from, before the synth sees it. The
+//! velocities can be scaled, offset and bent with a curve, every event can be moved to a single MIDI
+//! channel, and the notes can be transposed.
+
+use nih_plug::prelude::NoteEvent;
+use subsynth_dsp::velocity::VelocityCurve;
+
+/// The lowest velocity a note can end up with, the same as the lowest MIDI velocity. A note with a
+/// velocity of zero would keep its envelopes from ever finishing.
+const MIN_VELOCITY: f32 = 1.0 / 127.0;
+
+/// A snapshot of the input transform's settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransformSettings {
+    pub velocity_scale: f32,
+    pub velocity_offset: f32,
+    pub velocity_curve: VelocityCurve,
+    /// The channel every event is moved to, if any.
+    pub channel: Option<u8>,
+    /// In semitones.
+    pub transpose: i32,
+}
+
+impl TransformSettings {
+    fn velocity(&self, velocity: f32) -> f32 {
+        (self.velocity_curve.apply(velocity) * self.velocity_scale + self.velocity_offset)
+            .clamp(MIN_VELOCITY, 1.0)
+    }
+
+    fn channel(&self, channel: u8) -> u8 {
+        self.channel.unwrap_or(channel)
+    }
+
+    /// The channel and note a note ends up on. `None` if the note is transposed out of range.
+    fn note(&self, channel: u8, note: u8) -> Option<(u8, u8)> {
+        let note = note as i32 + self.transpose;
+        if (0..128).contains(&note) {
+            Some((self.channel(channel), note as u8))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InputTransform {
+    /// Where the held notes ended up, indexed by their original channel and note. The note's other
+    /// events follow its note on, even if the settings change while the note is held. Otherwise
+    /// changing the transpose would leave notes hanging.
+    held_notes: [[Option<(u8, u8)>; 128]; 16],
+}
+
+impl Default for InputTransform {
+    fn default() -> Self {
+        Self {
+            held_notes: [[None; 128]; 16],
+        }
+    }
+}
+
+impl InputTransform {
+    /// Apply the transform to one of the host's events. Returns `None` if the event should be
+    /// dropped because its note was transposed out of range.
+    pub fn transform<S>(
+        &mut self,
+        settings: &TransformSettings,
+        mut event: NoteEvent<S>,
+    ) -> Option<NoteEvent<S>> {
+        match &mut event {
+            NoteEvent::NoteOn {
+                channel,
+                note,
+                velocity,
+                ..
+            } => {
+                let held_note = &mut self.held_notes[*channel as usize % 16][*note as usize % 128];
+                *held_note = settings.note(*channel, *note);
+                (*channel, *note) = (*held_note)?;
+                *velocity = settings.velocity(*velocity);
+            }
+            NoteEvent::NoteOff { channel, note, .. } => {
+                (*channel, *note) = self.held_notes[*channel as usize % 16][*note as usize % 128]
+                    .take()
+                    .or_else(|| settings.note(*channel, *note))?;
+            }
+            NoteEvent::Choke { channel, note, .. }
+            | NoteEvent::PolyPressure { channel, note, .. }
+            | NoteEvent::PolyVolume { channel, note, .. }
+            | NoteEvent::PolyPan { channel, note, .. }
+            | NoteEvent::PolyTuning { channel, note, .. }
+            | NoteEvent::PolyVibrato { channel, note, .. }
+            | NoteEvent::PolyExpression { channel, note, .. }
+            | NoteEvent::PolyBrightness { channel, note, .. } => {
+                (*channel, *note) = self.held_notes[*channel as usize % 16][*note as usize % 128]
+                    .or_else(|| settings.note(*channel, *note))?;
+            }
+            NoteEvent::MidiChannelPressure { channel, .. }
+            | NoteEvent::MidiPitchBend { channel, .. }
+            | NoteEvent::MidiCC { channel, .. }
+            | NoteEvent::MidiProgramChange { channel, .. } => *channel = settings.channel(*channel),
+            _ => (),
+        }
+
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: TransformSettings = TransformSettings {
+        velocity_scale: 0.5,
+        velocity_offset: 0.25,
+        velocity_curve: VelocityCurve::Linear,
+        channel: Some(3),
+        transpose: 12,
+    };
+
+    fn note_on(note: u8, velocity: f32) -> NoteEvent<()> {
+        NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity,
+        }
+    }
+
+    fn note_off(note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOff {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_transform() {
+        let mut transform = InputTransform::default();
+        assert_eq!(
+            transform.transform(&SETTINGS, note_on(60, 1.0)),
+            Some(NoteEvent::NoteOn {
+                timing: 0,
+                voice_id: None,
+                channel: 3,
+                note: 72,
+                velocity: 0.75,
+            })
+        );
+        let pitch_bend: NoteEvent<()> = NoteEvent::MidiPitchBend {
+            timing: 0,
+            channel: 0,
+            value: 0.5,
+        };
+        assert_eq!(
+            transform.transform(&SETTINGS, pitch_bend),
+            Some(NoteEvent::MidiPitchBend {
+                timing: 0,
+                channel: 3,
+                value: 0.5,
+            })
+        );
+        // Notes transposed out of range are dropped
+        assert_eq!(transform.transform(&SETTINGS, note_on(120, 1.0)), None);
+        assert_eq!(transform.transform(&SETTINGS, note_off(120)), None);
+    }
+
+    #[test]
+    fn test_note_off_follows_note_on() {
+        let mut transform = InputTransform::default();
+        transform.transform(&SETTINGS, note_on(60, 1.0));
+
+        // Changing the settings while the note is held still releases the right note
+        let settings = TransformSettings {
+            channel: None,
+            transpose: 0,
+            ..SETTINGS
+        };
+        assert_eq!(
+            transform.transform(&settings, note_off(60)),
+            Some(NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel: 3,
+                note: 72,
+                velocity: 0.0,
+            })
+        );
+        assert_eq!(transform.transform(&settings, note_off(60)), Some(note_off(60)));
+    }
+
+    #[test]
+    fn test_velocity_stays_above_zero() {
+        let settings = TransformSettings {
+            velocity_offset: -1.0,
+            ..SETTINGS
+        };
+        assert_eq!(settings.velocity(1.0), MIN_VELOCITY);
+    }
+}
@@ -0,0 +1,41 @@
+//! Velocity layering for multitimbral mode. When both parts play the same notes, the note's velocity
+//! can crossfade between them so soft notes mostly play part A and hard notes mostly play part B.
+
+use enum_iterator::Sequence;
+use nih_plug::params::enums::Enum;
+use std::f32::consts::FRAC_PI_2;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum CrossfadeCurve {
+    Linear,
+    /// Keeps the combined loudness constant throughout the crossfade.
+    #[name = "Equal Power"]
+    EqualPower,
+}
+
+/// Part A's and part B's gains for a note with the given velocity. `center` is the velocity both
+/// parts are equally loud at, and `width` is the range of velocities the crossfade is spread over.
+/// A width of zero results in a hard velocity split.
+pub fn velocity_crossfade(
+    velocity: f32,
+    center: f32,
+    width: f32,
+    curve: CrossfadeCurve,
+) -> (f32, f32) {
+    let position = if width > 0.0 {
+        ((velocity - center) / width + 0.5).clamp(0.0, 1.0)
+    } else if velocity >= center {
+        1.0
+    } else {
+        0.0
+    };
+
+    match curve {
+        CrossfadeCurve::Linear => (1.0 - position, position),
+        // Both gains are computed with a sine so the ends of the crossfade are exactly zero
+        CrossfadeCurve::EqualPower => (
+            ((1.0 - position) * FRAC_PI_2).sin(),
+            (position * FRAC_PI_2).sin(),
+        ),
+    }
+}
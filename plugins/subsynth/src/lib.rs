@@ -1,96 +1,356 @@
+// Some of the displays' readouts are only used by the editor
+#[cfg_attr(not(feature = "editor"), allow(dead_code))]
+mod cc_map;
+mod config;
+mod display;
+#[cfg(feature = "editor")]
 mod editor;
-mod envelope;
-mod filter;
-mod waveform;
-mod modulator;
+#[cfg_attr(not(feature = "editor"), allow(dead_code))]
+mod envelope_shape;
+mod host;
+mod input_transform;
+mod layer;
+#[cfg_attr(not(feature = "editor"), allow(dead_code))]
+mod meter;
+#[cfg_attr(not(feature = "editor"), allow(dead_code))]
+mod mod_scope;
+mod midi_input;
+mod migration;
+mod modmatrix;
+#[cfg_attr(not(feature = "editor"), allow(dead_code))]
+mod morph;
+#[cfg_attr(not(feature = "editor"), allow(dead_code))]
+mod mseg;
+mod params;
+mod pedal;
+#[cfg_attr(not(feature = "editor"), allow(dead_code))]
+mod performance;
+#[cfg_attr(not(feature = "editor"), allow(dead_code))]
+mod preview;
+mod strum;
+#[cfg(test)]
+mod test_host;
+#[cfg_attr(not(feature = "editor"), allow(dead_code))]
+mod transport;
+mod trigger;
+mod voice_allocator;
 
-use nih_plug::params::enums::EnumParam;
 use nih_plug::prelude::*;
+#[cfg(feature = "editor")]
 use nih_plug_vizia::ViziaState;
 use rand::Rng;
 use rand_pcg::Pcg32;
-use std::sync::Arc;
+use atomic_float::AtomicF32;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, RwLock};
+use subsynth_dsp::drawbar::{drawbar_gain, DrawbarOrgan, PercussionSettings, NUM_DRAWBARS};
+use subsynth_dsp::drift::Drift;
+use subsynth_dsp::envelope::{
+    envelope_velocity, ADEnvelope, ADSREnvelope, ADSREnvelopeState, Envelope, PitchEnvelopeSource,
+};
+use subsynth_dsp::filter::{
+    filter_envelope_depths, generate_filter, DCBlocker, FilterType, MorphingSvf, OnePoleLowpass,
+};
+use subsynth_dsp::glide::{scaled_glide_time, Glide, GlideMode};
+use subsynth_dsp::modulator::{LfoMode, LfoScope, Modulator, OscillatorShape};
+use subsynth_dsp::stereo::{apply_width, CorrelationMeter, HaasDelay, MAX_HAAS_DELAY_MS};
+use subsynth_dsp::wavetable::WavetableBank;
+use subsynth_dsp::waveform::{
+    generate_waveform, phase_distortion, supersaw_detune_curve, OscQuality, OscSource, PdCurve,
+    Supersaw, Waveform,
+};
 
-use modulator::{Modulator, OscillatorShape};
-use envelope::{ADSREnvelope, Envelope, ADSREnvelopeState};
-use filter::{generate_filter, FilterType, Filter};
-use waveform::{generate_waveform, Waveform};
+use cc_map::{CcMap, PartOverrides, MOD_WHEEL_CC, SUSTAIN_PEDAL_CC};
+use config::{MAX_BLOCK_SIZE, NUM_VOICES};
+use modmatrix::{
+    ModMatrix, ModOffsets, ModSourceValues, MOD_CUTOFF_RANGE_OCTAVES, MOD_LFO_RATE_RANGE_OCTAVES,
+    MOD_PITCH_RANGE_SEMITONES, NUM_MACROS, NUM_MOD_SLOTS,
+};
+use morph::{Morph, MorphSnapshots};
+use input_transform::{InputTransform, TransformSettings};
+use host::{HostContext, HostTransport};
+use layer::velocity_crossfade;
+use meter::{MeterDisplay, MeterMode, MeterSpeed, OutputMeter};
+use mod_scope::{ModScope, ScopeSignal, ScopeTap};
+use mseg::{
+    Mseg, MsegDestination, MsegShape, MSEG_CUTOFF_RANGE_OCTAVES, MSEG_PITCH_RANGE_SEMITONES,
+};
+use pedal::{Pedal, PedalMode};
+use performance::{PerformanceEvents, PerformanceInput};
+use preview::PreviewTask;
+use params::{
+    CombinerParams, InputTransformParams, InputTriggerParams, MacroParams, ModSlotParams,
+    PatchParams, VelocityCrossfadeParams,
+};
+use strum::order_strum;
+use transport::TransportDisplay;
+use trigger::{InputTrigger, TriggerEvent, TriggerNote};
+use midi_input::{MidiInput, NoteExpression, VoiceEvent, PITCH_BEND_RANGE_SEMITONES};
+use voice_allocator::{event_targets_voice, VoiceAllocator};
 
-const NUM_VOICES: usize = 16;
-const MAX_BLOCK_SIZE: usize = 64;
+/// The number of patch parts in multitimbral mode. The voices are split evenly between the parts.
+const NUM_PARTS: usize = 2;
 const GAIN_POLY_MOD_ID: u32 = 0;
+const CUTOFF_POLY_MOD_ID: u32 = 1;
+const RESONANCE_POLY_MOD_ID: u32 = 2;
+const PAN_POLY_MOD_ID: u32 = 3;
+/// Every part has its own set of polyphonic modulation IDs. Part B's IDs are offset by this amount.
+const POLY_MOD_IDS_PER_PART: u32 = 4;
+const MIN_FILTER_CUTOFF_HZ: f32 = 20.0;
+const MAX_FILTER_CUTOFF_HZ: f32 = 20_000.0;
+/// How long it takes for a choked voice to fade out. Cutting voices off instantly would click.
+const CHOKE_FADE_MS: f32 = 5.0;
+/// How long the bypass parameter takes to crossfade between the synth and the bypassed signal.
+const BYPASS_FADE_MS: f32 = 10.0;
 
 struct SubSynth {
     params: Arc<SubSynthParams>,
     prng: Pcg32,
-    voices: [Option<Voice>; NUM_VOICES as usize],
-    next_voice_index: usize,
+    /// The seed `prng` was last seeded with. The generator is seeded again when the seed parameter
+    /// changes.
+    prng_seed: i32,
+    output_meter: OutputMeter,
+    /// The output meter's readings. This is stored as an [`Arc`] so we can share it between the GUI
+    /// and the audio processing parts.
+    meter: Arc<MeterDisplay>,
+    /// Set when the output exceeds 0 dBFS. Only the editor resets this.
+    clipped: Arc<AtomicBool>,
+    /// Set when a voice or the output produced a NaN or infinite sample, which gets replaced with
+    /// silence. Only the editor resets this.
+    dsp_error: Arc<AtomicBool>,
+    correlation_meter: CorrelationMeter,
+    /// The output's stereo correlation, shared with the editor like the output meter.
+    correlation: Arc<AtomicF32>,
+    scope_tap: ScopeTap,
+    /// The modulation scope's recent values, shared with the editor like the output meter.
+    mod_scope: Arc<ModScope>,
+    /// The performance page's keyboard and wheels, which the editor shares with the audio thread.
+    performance_input: Arc<PerformanceInput>,
+    performance_events: PerformanceEvents,
+    /// The host's tempo and position, shared with the editor like the output meter.
+    transport_display: Arc<TransportDisplay>,
+    voices: [Option<Voice>; NUM_VOICES],
+    /// The Haas effect's delay lines, one for every voice slot. These are allocated in
+    /// `initialize()`.
+    haas_delays: Vec<HaasDelay>,
+    /// Band-limited tables for the classic waveforms, built in `initialize()`.
+    wavetables: WavetableBank,
+    voice_allocator: VoiceAllocator,
     next_internal_voice_id: u64,
+    parts: [PartState; NUM_PARTS],
+    /// Where we expect the host's transport to be at the start of the next buffer if it keeps
+    /// playing normally. `None` if the transport is stopped. Used to detect loops and jumps.
+    expected_transport_pos: Option<i64>,
+    /// The notes that are currently being held by latch mode, indexed by channel and note number.
+    latched_notes: [[bool; 128]; 16],
+    /// The notes held by the sustain pedal.
+    pedal: Pedal,
+    /// Whether freeze was enabled during the previous buffer. Used to capture and release the drone
+    /// voices when it gets toggled.
+    frozen: bool,
+    /// The voice capacity that was last reported to the host. This changes with the polyphony and
+    /// multitimbral parameters.
+    reported_voice_capacity: u32,
+    /// Follows the audio input's level to trigger notes from it.
+    input_trigger: InputTrigger,
+    /// Remembers where the held notes ended up after the input transform.
+    input_transform: InputTransform,
+    /// A copy of the MIDI CC mappings, updated from the parameters at the start of every buffer.
+    cc_map: CcMap,
+    /// Whether the stepped CC mappings still override every part's waveform and filter type.
+    stepped_overrides: [PartOverrides; NUM_PARTS],
+    /// The MIDI controller state, like the CC values and the pitch wheel's position.
+    midi_input: MidiInput,
+    /// A copy of the morph snapshots, updated from the parameters at the start of every buffer.
+    /// All continuous parameters that can be morphed are read through this.
+    morph: Morph,
+    /// The bypass parameter's value during the previous buffer.
+    bypassed: bool,
+    /// How far the output has been crossfaded from the synth to the bypassed signal, between 0 and
+    /// 1.
+    bypass_fade: Smoother<f32>,
+    block_buffers: BlockBuffers,
 }
 
+/// The state that every patch part keeps for itself.
+struct PartState {
+    last_note: Option<u8>,
+    global_vibrato_lfo: Modulator,
+    global_tremolo_lfo: Modulator,
+    /// A copy of the part's MSEG shape, updated from the parameters at the start of every buffer.
+    /// The editor may hold a lock on the shape while it's being edited, in which case the previous
+    /// copy is used.
+    mseg_shape: MsegShape,
+}
+
+/// The editor's window state. This is its own group so the lite build, which doesn't have an
+/// editor, can leave it out. It's nested without an ID prefix, so the key stays the same.
+#[cfg(feature = "editor")]
 #[derive(Params)]
-struct SubSynthParams {
+struct EditorParams {
     #[persist = "editor-state"]
-    editor_state: Arc<ViziaState>,
-    #[id = "gain"]
-    gain: FloatParam,
-    #[id = "amp_atk"]
-    amp_attack_ms: FloatParam,
-    #[id = "amp_rel"]
-    amp_release_ms: FloatParam,
-    #[id = "waveform"]
-    waveform: EnumParam<Waveform>,
-
-    // New parameters for ADSR envelope
-    #[id = "amp_dec"]
-    amp_decay_ms: FloatParam,
-    #[id = "amp_sus"]
-    amp_sustain_level: FloatParam,
-    #[id = "filter_cut_atk"]
-    filter_cut_attack_ms: FloatParam,
-    #[id = "filter_cut_dec"]
-    filter_cut_decay_ms: FloatParam,
-    #[id = "filter_cut_sus"]
-    filter_cut_sustain_ms: FloatParam,
-    #[id = "filter_cut_rel"]
-    filter_cut_release_ms: FloatParam,
-    #[id = "filter_res_atk"]
-    filter_res_attack_ms: FloatParam,
-    #[id = "filter_res_dec"]
-    filter_res_decay_ms: FloatParam,
-    #[id = "filter_res_sus"]
-    filter_res_sustain_ms: FloatParam,
-    #[id = "filter_res_rel"]
-    filter_res_release_ms: FloatParam,
-    #[id = "filter_type"]
-    filter_type: EnumParam<FilterType>,
-    #[id = "filter_cut"]
-    filter_cut: FloatParam,
-    #[id = "filter_res"]
-    filter_res: FloatParam,
-    // New parameters for ADSR envelope levels
-    #[id = "amp_env_level"]
-    amp_envelope_level: FloatParam,
-    #[id = "filter_cut_env_level"]
-    filter_cut_envelope_level: FloatParam,
-    #[id = "filter_res_env_level"]
-    filter_res_envelope_level: FloatParam,
-    #[id = "vibrato_atk"]
-    vibrato_attack: FloatParam,
-    #[id = "vibrato_int"]
-    vibrato_intensity: FloatParam,
-    #[id = "vibrato_rate"]
-    vibrato_rate: FloatParam,
-    #[id = "tremolo_atk"]
-    tremolo_attack: FloatParam,
-    #[id = "tremolo_int"]
-    tremolo_intensity: FloatParam,
-    #[id = "tremolo_rate"]
-    tremolo_rate: FloatParam,
-    #[id = "vibrato_shape"]
-    vibrato_shape: EnumParam<OscillatorShape>,
-    #[id = "tremolo_shape"]
-    tremolo_shape: EnumParam<OscillatorShape>,
+    state: Arc<ViziaState>,
+}
+
+#[cfg(not(feature = "editor"))]
+#[derive(Params, Default)]
+struct EditorParams {}
+
+#[cfg(feature = "editor")]
+impl Default for EditorParams {
+    fn default() -> Self {
+        Self {
+            state: editor::default_state(),
+        }
+    }
+}
+
+impl EditorParams {
+    /// Whether the editor is open. The displays are only updated while it is.
+    #[cfg(feature = "editor")]
+    fn is_open(&self) -> bool {
+        self.state.is_open()
+    }
+
+    #[cfg(not(feature = "editor"))]
+    fn is_open(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Params)]
+struct SubSynthParams {
+    #[nested]
+    editor: EditorParams,
+    /// The schema version of the saved state, used to upgrade patches saved by older versions.
+    /// This is always set to the current version after loading.
+    #[persist = "state-version"]
+    state_version: Arc<AtomicU32>,
+    /// The MIDI CC mappings, edited on the editor's CC map page.
+    #[persist = "cc-map"]
+    cc_map: Arc<RwLock<CcMap>>,
+    /// The two snapshots the morph knob interpolates between, stored from the editor.
+    #[persist = "morph-snapshots"]
+    morph_snapshots: Arc<RwLock<MorphSnapshots>>,
+    /// Whether the editor shows the performance page instead of the full set of controls.
+    #[persist = "performance-page"]
+    show_performance_page: Arc<AtomicBool>,
+    /// The output gain, applied after all voices have been mixed together.
+    #[id = "master_gain"]
+    master_gain: FloatParam,
+    /// A gain trim stored with the patch, for balancing the loudness of patches against each other
+    /// without touching the master gain or its automation. Applied together with the master gain.
+    #[id = "patch_trim"]
+    patch_trim: FloatParam,
+    /// The stereo width of the output. 0% is mono, 100% leaves the output unchanged, and 200%
+    /// doubles the difference between the channels.
+    #[id = "width"]
+    width: FloatParam,
+    /// Delays one channel of every voice by a few milliseconds for width, alternating between the
+    /// left and the right channel from voice to voice. Zero turns the effect off.
+    #[id = "haas_delay"]
+    haas_delay_ms: FloatParam,
+    /// Only put the delayed signal in the difference between the channels so the Haas effect
+    /// cancels out completely when the output is summed to mono, instead of causing comb
+    /// filtering.
+    #[id = "haas_mono_safe"]
+    haas_mono_safe: BoolParam,
+    /// Interpolates between the two morph snapshots, once both have been stored.
+    #[id = "morph"]
+    morph: FloatParam,
+    /// Keep notes playing after they're released until the same note is pressed again or latch is
+    /// disabled.
+    #[id = "latch"]
+    latch: BoolParam,
+    /// Whether the sustain pedal sustains all notes, only the notes held when it was pressed, or
+    /// only acts as a mod matrix source.
+    #[id = "pedal_mode"]
+    pedal_mode: EnumParam<PedalMode>,
+    /// Play every note with the same velocity, for consistent organ and pluck sounds regardless of
+    /// how hard the keys are hit. The envelopes also stop scaling their times with the velocity.
+    #[id = "fixed_velocity"]
+    fixed_velocity: BoolParam,
+    /// The velocity every note is played with when fixed velocity is enabled.
+    #[id = "fixed_velocity_value"]
+    fixed_velocity_value: FloatParam,
+    /// Capture all sounding voices and hold them at their sustain level indefinitely, while new
+    /// notes keep playing normally. Turning freeze off releases the captured voices.
+    #[id = "freeze"]
+    freeze: BoolParam,
+    /// The gain applied to the voices captured by freeze.
+    #[id = "drone_gain"]
+    drone_gain: FloatParam,
+    /// Play part B alongside part A, with each part getting half of the voices. Each part only
+    /// responds to its own MIDI channel, so with different channels the parts can be played
+    /// separately.
+    #[id = "multitimbral"]
+    multitimbral: BoolParam,
+    /// The maximum number of voices that can play at the same time. In multitimbral mode every
+    /// part gets half of them.
+    #[id = "polyphony"]
+    polyphony: IntParam,
+    /// Never steal the voice playing the lowest held note, so a bassline stays intact when chords
+    /// need more voices than there are.
+    #[id = "protect_lowest_note"]
+    protect_lowest_note: BoolParam,
+    /// The number of voices kept free for new notes. Once fewer voices than this are free, new
+    /// notes take over releasing voices first, so long release tails and the sustain pedal don't
+    /// cause held notes to get stolen.
+    #[id = "voice_reserve"]
+    voice_reserve: IntParam,
+    /// The quality of the oscillators' anti-aliasing, and whether the filter runs in double
+    /// precision.
+    #[id = "osc_quality"]
+    osc_quality: EnumParam<OscQuality>,
+    #[id = "lfo_transport_sync"]
+    lfo_transport_sync: BoolParam,
+    /// The tempo used for tempo synced features when the host doesn't provide one.
+    #[id = "internal_tempo"]
+    internal_tempo: FloatParam,
+    /// Skip all processing and output silence while no voices are playing, so the host can suspend
+    /// the plugin.
+    #[id = "eco_idle"]
+    eco_idle: BoolParam,
+    /// Fades the synth out, or over to the audio input when a part uses it. This is the plugin's
+    /// bypass for hosts that support that.
+    #[id = "bypass"]
+    bypass: BoolParam,
+    /// Seeds the random number generator behind the noise, the analog drift, the supersaw's phases,
+    /// and the random strum order. The same seed always results in the same randomness after the
+    /// plugin is reset.
+    #[id = "seed"]
+    seed: IntParam,
+    /// How long per-note tuning expressions take to reach a new value. Hosts only send these every
+    /// so often, so without a glide pitch slides would step audibly.
+    #[id = "expr_glide"]
+    expression_glide_ms: FloatParam,
+    /// What the output meter in the editor shows. This only affects the display.
+    #[id = "meter_mode"]
+    meter_mode: EnumParam<MeterMode>,
+    /// How quickly the output meter falls back.
+    #[id = "meter_speed"]
+    meter_speed: EnumParam<MeterSpeed>,
+    /// The modulation signal shown on the editor's scope.
+    #[id = "scope_signal"]
+    scope_signal: EnumParam<ScopeSignal>,
+
+    #[nested(group = "Part A")]
+    part_a: PatchParams,
+    #[nested(id_prefix = "part_b", group = "Part B")]
+    part_b: PatchParams,
+    #[nested(id_prefix = "xfade", group = "Velocity Crossfade")]
+    velocity_crossfade: VelocityCrossfadeParams,
+    #[nested(id_prefix = "trigger", group = "Input Trigger")]
+    trigger: InputTriggerParams,
+    #[nested(id_prefix = "input", group = "Input Transform")]
+    input_transform: InputTransformParams,
+    #[nested(array, group = "Macro")]
+    macros: [MacroParams; NUM_MACROS],
+    #[nested(array, group = "Mod Slot")]
+    mod_slots: [ModSlotParams; NUM_MOD_SLOTS],
+    #[nested(id_prefix = "combiner", group = "Combiner")]
+    combiner: CombinerParams,
 }
 
 #[derive(Debug, Clone)]
@@ -99,35 +359,178 @@ struct Voice {
     channel: u8,
     note: u8,
     internal_voice_id: u64,
+    /// The index of the patch part this voice plays.
+    part: usize,
+    /// The part's share of the note from the velocity crossfade, computed when the note starts.
+    layer_gain: f32,
     velocity: f32,
     velocity_sqrt: f32,
-    phase: f32,
+    /// The oscillator's phase. This is accumulated in double precision so long notes don't drift
+    /// out of tune, while the waveforms themselves are computed in single precision.
+    phase: f64,
     phase_delta: f32,
-    releasing: bool,
     amp_envelope: ADSREnvelope,
+    /// The voice's polyphonic modulation for the gain, cutoff, resonance, and pan parameters, if
+    /// the host has sent any. These are pairs of `(normalized_offset, smoother)`.
     voice_gain: Option<(f32, Smoother<f32>)>,
+    voice_cutoff: Option<(f32, Smoother<f32>)>,
+    voice_resonance: Option<(f32, Smoother<f32>)>,
+    voice_pan: Option<(f32, Smoother<f32>)>,
     filter_cut_envelope: ADSREnvelope,
     filter_res_envelope: ADSREnvelope,
     filter: Option<FilterType>,
     pressure: f32,
     pan: f32,        // Added pan field
     tuning: f32,     // Add tuning field
+    /// Glides `tuning` towards the last tuning expression.
+    tuning_glide: Smoother<f32>,
     vibrato: f32,    // Add vibrato field
     expression: f32, // Add expression field
     brightness: f32, // Add brightness field
     vib_mod: Modulator,
     trem_mod: Modulator,
+    glide: Option<Glide>,
+    supersaw: Supersaw,
+    organ: DrawbarOrgan,
+    /// The filter's state, which every filter type runs on.
+    svf: MorphingSvf,
+    /// The lowpass for the tone control, which comes after the main filter.
+    tone_filter: OnePoleLowpass,
+    /// Removes the DC offset from the voice's output. Every voice starts with a fresh one.
+    dc_blocker: DCBlocker,
+    pitch_envelope: ADEnvelope,
+    mseg: Mseg,
+    drift: Drift,
+    /// The number of samples left before this voice starts playing. Used to strum chords.
+    onset_delay: u32,
+    /// Set when freeze captured this voice. Drone voices ignore note offs and keep sustaining until
+    /// freeze is turned off again.
+    drone: bool,
+    /// This voice's random position within the surround spread, with both axes between -1 and 1.
+    placement: (f32, f32),
+    /// The voice's own random number generator for the noise waveform, seeded from the synth's
+    /// generator.
+    noise: Pcg32,
+    /// The mod matrix's offsets from the previous sample. The LFO destinations are applied from
+    /// these so an LFO can modulate another LFO, or itself, without depending on its own output.
+    previous_mod_offsets: ModOffsets,
+    /// Set when another note in the same choke group cut this voice off. This is the gain of the
+    /// fade out, and the voice is terminated once it reaches zero.
+    choke_fade: Option<f32>,
+}
+
+/// Scratch buffers for a block's smoothed values, so adding another smoothed parameter only takes
+/// another field here. These are allocated in `initialize()` for the largest block the host can
+/// send, which is never more than `MAX_BLOCK_SIZE` samples.
+#[derive(Debug, Clone)]
+struct BlockBuffers {
+    /// The audio input mixed down to mono, for the parts that use it as their oscillator.
+    input: Vec<f32>,
+    /// The stereo audio input, which passes through while bypassed.
+    bypassed_input: [Vec<f32>; 2],
+    master_gain: Vec<f32>,
+    patch_trim: Vec<f32>,
+    bypass_fade: Vec<f32>,
+    haas_delay_ms: Vec<f32>,
+    width: Vec<f32>,
+    drone_gain: Vec<f32>,
+    macro_values: [Vec<f32>; NUM_MACROS],
+    /// The per-voice values, indexed by voice. These are either polyphonically modulated or copies
+    /// of the part's values.
+    voice_gain: Vec<Vec<f32>>,
+    voice_cutoff: Vec<Vec<f32>>,
+    voice_resonance: Vec<Vec<f32>>,
+    voice_pan: Vec<Vec<f32>>,
+    /// Every part's smoothed parameter values.
+    parts: [PartBuffers; NUM_PARTS],
+}
+
+/// The buffers for a part's smoothed parameter values, kept in [`BlockBuffers`].
+#[derive(Debug, Clone)]
+struct PartBuffers {
+    gain: Vec<f32>,
+    pan: Vec<f32>,
+    cutoff: Vec<f32>,
+    resonance: Vec<f32>,
+    filter_morph: Vec<f32>,
+    filter_fm_amount: Vec<f32>,
+    filter_env_amount: Vec<f32>,
+    filter_env_resonance_ratio: Vec<f32>,
+    tone: Vec<f32>,
+    supersaw_detune: Vec<f32>,
+    supersaw_mix: Vec<f32>,
+    pd_amount: Vec<f32>,
+    pd_env_amount: Vec<f32>,
+    pitch_env_amount: Vec<f32>,
+    vibrato_intensity: Vec<f32>,
+    tremolo_intensity: Vec<f32>,
+    amp_envelope_level: Vec<f32>,
+    filter_cut_envelope_level: Vec<f32>,
+    filter_res_envelope_level: Vec<f32>,
+    mseg_amount: Vec<f32>,
+    drift_depth: Vec<f32>,
+    surround_y: Vec<f32>,
+}
+
+/// A part's smoothed parameter values and other settings for the current block. The slices contain
+/// `block_len` values.
+struct PartBlock<'a> {
+    gain: &'a [f32],
+    pan: &'a [f32],
+    cutoff: &'a [f32],
+    resonance: &'a [f32],
+    filter_morph: &'a [f32],
+    filter_fm_amount: &'a [f32],
+    filter_env_amount: &'a [f32],
+    filter_env_resonance_ratio: &'a [f32],
+    tone: &'a [f32],
+    supersaw_detune: &'a [f32],
+    supersaw_mix: &'a [f32],
+    pd_amount: &'a [f32],
+    pd_env_amount: &'a [f32],
+    pitch_env_amount: &'a [f32],
+    vibrato_intensity: &'a [f32],
+    tremolo_intensity: &'a [f32],
+    amp_envelope_level: &'a [f32],
+    filter_cut_envelope_level: &'a [f32],
+    filter_res_envelope_level: &'a [f32],
+    mseg_amount: &'a [f32],
+    drift_depth: &'a [f32],
+    surround_y: &'a [f32],
+    /// The fraction of the MSEG's length that a single sample takes up.
+    mseg_delta: f32,
+    drift_rate: f32,
+    spread_x: f32,
+    spread_y: f32,
+    osc_source: OscSource,
+    /// The waveform and the filter type, after the stepped CC mappings' overrides.
+    waveform: Waveform,
+    filter_type: FilterType,
+    pd_curve: PdCurve,
+    tone_key_tracking: f32,
+    mseg_destination: MsegDestination,
+    pitch_env_source: PitchEnvelopeSource,
+    vibrato_scope: LfoScope,
+    tremolo_scope: LfoScope,
+    vibrato_shape: OscillatorShape,
+    tremolo_shape: OscillatorShape,
+    drawbar_gains: [f32; NUM_DRAWBARS],
+    percussion: PercussionSettings,
 }
 
 impl Default for SubSynth {
     fn default() -> Self {
-        Self {
-            params: Arc::new(SubSynthParams::default()),
+        Self::new(Arc::new(SubSynthParams::default()))
+    }
+}
 
-            prng: Pcg32::new(420, 1337),
-            voices: [0; NUM_VOICES as usize].map(|_| None),
-            next_internal_voice_id: 0,
-            next_voice_index: 0,
+impl Default for PartState {
+    fn default() -> Self {
+        Self {
+            last_note: None,
+            global_vibrato_lfo: SubSynth::construct_global_lfo(),
+            global_tremolo_lfo: SubSynth::construct_global_lfo(),
+            mseg_shape: MsegShape::default(),
         }
     }
 }
@@ -135,263 +538,228 @@ impl Default for SubSynth {
 impl Default for SubSynthParams {
     fn default() -> Self {
         Self {
-            editor_state: editor::default_state(),
-            gain: FloatParam::new(
-                "Gain",
-                util::db_to_gain(-36.0),
-                FloatRange::Linear {
+            editor: EditorParams::default(),
+            state_version: Arc::new(AtomicU32::new(migration::STATE_VERSION)),
+            cc_map: Arc::new(RwLock::new(CcMap::default())),
+            morph_snapshots: Arc::new(RwLock::new(MorphSnapshots::default())),
+            show_performance_page: Arc::new(AtomicBool::new(false)),
+            master_gain: FloatParam::new(
+                "Master Gain",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
                     min: util::db_to_gain(-36.0),
-                    max: util::db_to_gain(0.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-36.0, 12.0),
                 },
             )
-            .with_poly_modulation_id(GAIN_POLY_MOD_ID)
-            .with_smoother(SmoothingStyle::Logarithmic(5.0))
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
             .with_unit(" dB")
             .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
             .with_string_to_value(formatters::s2v_f32_gain_to_db()),
-            amp_attack_ms: FloatParam::new(
-                "Attack",
-                1.0,
-                FloatRange::Skewed {
-                    min: 0.0,
-                    max: 10.0,
-                    factor: FloatRange::skew_factor(-1.0),
-                },
-            )
-            .with_step_size(0.01)
-            .with_unit(" ms"),
-            amp_release_ms: FloatParam::new(
-                "Release",
-                1.0,
-                FloatRange::Skewed {
-                    min: 0.0,
-                    max: 10.0,
-                    factor: FloatRange::skew_factor(-1.0),
-                },
-            )
-            .with_step_size(0.01)
-            .with_unit(" ms"),
-            waveform: EnumParam::new("Waveform", Waveform::Sine),
-            amp_decay_ms: FloatParam::new(
-                "Decay",
-                10.0,
-                FloatRange::Skewed {
-                    min: 0.0,
-                    max: 100.0,
-                    factor: FloatRange::skew_factor(-1.0),
-                },
-            )
-            .with_step_size(0.01)
-            .with_unit(" ms"),
-            amp_sustain_level: FloatParam::new(
-                "Sustain",
-                1.0,
+            patch_trim: FloatParam::new(
+                "Patch Trim",
+                util::db_to_gain(0.0),
                 FloatRange::Skewed {
-                    min: 0.0,
-                    max: 1.0,
-                    factor: FloatRange::skew_factor(-1.0),
-                },
-            )
-            .with_step_size(0.01)
-            .with_unit(" units"),
-            filter_type: EnumParam::new("Filter Type", FilterType::None),
-            filter_cut: FloatParam::new(
-                "Filter Cutoff",
-                200.0,
-                FloatRange::Linear {
-                    min: 20.0,
-                    max: 10000.0,
+                    min: util::db_to_gain(-12.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-12.0, 12.0),
                 },
             )
-            .with_unit(" Hz"),
-            filter_res: FloatParam::new(
-                "Filter Resonance",
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db())
+            .non_automatable(),
+            width: FloatParam::new("Width", 1.0, FloatRange::Linear { min: 0.0, max: 2.0 })
+                .with_smoother(SmoothingStyle::Linear(10.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            haas_delay_ms: FloatParam::new(
+                "Haas Delay",
                 0.0,
                 FloatRange::Linear {
                     min: 0.0,
-                    max: 10.0,
-                },
-            )
-            .with_unit(" Q"),
-            filter_cut_attack_ms: FloatParam::new(
-                "Filter Cut Attack",
-                1.0,
-                FloatRange::Skewed {
-                    min: 0.0,
-                    max: 10.0,
-                    factor: FloatRange::skew_factor(-1.0),
-                },
-            )
-            .with_step_size(0.01)
-            .with_unit(" ms"),
-            filter_cut_decay_ms: FloatParam::new(
-                "Filter Cut Decay",
-                10.0,
-                FloatRange::Skewed {
-                    min: 0.0,
-                    max: 100.0,
-                    factor: FloatRange::skew_factor(-1.0),
-                },
-            )
-            .with_step_size(0.01)
-            .with_unit(" ms"),
-            filter_cut_sustain_ms: FloatParam::new(
-                "Filter Cut Sustain",
-                1.0,
-                FloatRange::Skewed {
-                    min: -1.0,
-                    max: 1.0,
-                    factor: FloatRange::skew_factor(-1.0),
-                },
-            )
-            .with_step_size(0.01)
-            .with_unit(" ms"),
-            filter_cut_release_ms: FloatParam::new(
-                "Filter Cut Release",
-                1.0,
-                FloatRange::Skewed {
-                    min: 0.0,
-                    max: 10.0,
-                    factor: FloatRange::skew_factor(-1.0),
-                },
-            )
-            .with_step_size(0.01)
-            .with_unit(" ms"),
-            filter_res_attack_ms: FloatParam::new(
-                "Filter Resonance Attack",
-                10.0,
-                FloatRange::Skewed {
-                    min: 0.0,
-                    max: 100.0,
-                    factor: FloatRange::skew_factor(-1.0),
-                },
-            )
-            .with_step_size(0.01)
-            .with_unit(" ms"),
-            filter_res_decay_ms: FloatParam::new(
-                "Filter Resonance Decay",
-                10.0,
-                FloatRange::Skewed {
-                    min: 0.0,
-                    max: 100.0,
-                    factor: FloatRange::skew_factor(-1.0),
+                    max: MAX_HAAS_DELAY_MS,
                 },
             )
-            .with_step_size(0.01)
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_step_size(0.1)
             .with_unit(" ms"),
-            filter_res_sustain_ms: FloatParam::new(
-                "Filter Resonance Sustain",
+            haas_mono_safe: BoolParam::new("Haas Mono Safe", true),
+            morph: FloatParam::new("Morph", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            latch: BoolParam::new("Latch", false),
+            pedal_mode: EnumParam::new("Pedal Mode", PedalMode::Sustain),
+            fixed_velocity: BoolParam::new("Fixed Velocity", false),
+            fixed_velocity_value: FloatParam::new(
+                "Velocity",
                 1.0,
-                FloatRange::Skewed {
-                    min: -1.0,
-                    max: 1.0,
-                    factor: FloatRange::skew_factor(-1.0),
-                },
+                FloatRange::Linear { min: 0.0, max: 1.0 },
             )
-            .with_step_size(0.01)
-            .with_unit(" ms"),
-            filter_res_release_ms: FloatParam::new(
-                "Filter Resonance Release",
-                1.0,
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            freeze: BoolParam::new("Freeze", false),
+            drone_gain: FloatParam::new(
+                "Drone Gain",
+                util::db_to_gain(0.0),
                 FloatRange::Skewed {
-                    min: 0.0,
-                    max: 10.0,
-                    factor: FloatRange::skew_factor(-1.0),
-                },
-            )
-            .with_step_size(0.01)
-            .with_unit(" ms"),
-            amp_envelope_level: FloatParam::new(
-                "Amplitude Envelope Level",
-                1.0,
-                FloatRange::Linear {
-                    min: 0.0,
-                    max: 1.0,
-                },
-            )
-            .with_step_size(0.01),
-            filter_cut_envelope_level: FloatParam::new(
-                "Filter Cutoff Envelope Level",
-                1.0,
-                FloatRange::Linear {
-                    min: -1.0,
-                    max: 1.0,
-                },
-            )
-            .with_step_size(0.01),
-            filter_res_envelope_level: FloatParam::new(
-                "Filter Resonance Envelope Level",
-                1.0,
-                FloatRange::Linear {
-                    min: -1.0,
-                    max: 1.0,
+                    min: util::db_to_gain(-36.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-36.0, 12.0),
                 },
             )
-            .with_step_size(0.01),
-            vibrato_attack: FloatParam::new(
-                "Vibrato Attack",
-                1.0,
-                FloatRange::Skewed {
-                    min: 0.0,
-                    max: 10.0,
-                    factor: FloatRange::skew_factor(-1.0),
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            multitimbral: BoolParam::new("Multitimbral", false),
+            polyphony: IntParam::new(
+                "Polyphony",
+                NUM_VOICES as i32,
+                IntRange::Linear {
+                    min: 1,
+                    max: NUM_VOICES as i32,
                 },
-            )
-            .with_step_size(0.01)
-            .with_unit(" ms"),
-            vibrato_intensity: FloatParam::new(
-                "Vibrato Intensity",
-                0.0,
-                FloatRange::Linear {
-                    min: 0.0,
-                    max: 1.0,
+            ),
+            protect_lowest_note: BoolParam::new("Protect Lowest Note", false),
+            voice_reserve: IntParam::new(
+                "Voice Reserve",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: (NUM_VOICES / 2) as i32,
                 },
-            )
-            .with_step_size(0.01)
-            .with_unit(""),
-            vibrato_rate: FloatParam::new(
-                "Vibrato Rate",
-                1.0,
+            ),
+            osc_quality: EnumParam::new("Quality", OscQuality::Standard),
+            lfo_transport_sync: BoolParam::new("LFO Transport Sync", false),
+            internal_tempo: FloatParam::new(
+                "Internal Tempo",
+                120.0,
                 FloatRange::Linear {
-                    min: 0.0,
-                    max: 32.0,
+                    min: 20.0,
+                    max: 300.0,
                 },
             )
-            .with_step_size(1.0)
-            .with_unit(" Hz"),
-            tremolo_attack: FloatParam::new(
-                "Tremolo Attack",
-                1.0,
+            .with_step_size(0.1)
+            .with_unit(" BPM"),
+            eco_idle: BoolParam::new("Eco Idle", false),
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+            seed: IntParam::new("Random Seed", 420, IntRange::Linear { min: 0, max: 9999 }),
+            expression_glide_ms: FloatParam::new(
+                "Expression Glide",
+                20.0,
                 FloatRange::Skewed {
                     min: 0.0,
-                    max: 10.0,
+                    max: 500.0,
                     factor: FloatRange::skew_factor(-1.0),
                 },
             )
-            .with_step_size(0.01)
+            .with_step_size(0.1)
             .with_unit(" ms"),
-            tremolo_intensity: FloatParam::new(
-                "Tremolo Intensity",
-                0.0,
-                FloatRange::Linear {
-                    min: -1.0,
-                    max: 1.0,
-                },
-            )
-            .with_step_size(0.01)
-            .with_unit(""),
-            tremolo_rate: FloatParam::new(
-                "Tremolo Rate",
-                1.0,
-                FloatRange::Linear {
-                    min: 0.0,
-                    max: 10.0,
-                },
-            )
-            .with_step_size(0.01)
-            .with_unit(" Hz"),
-            vibrato_shape: EnumParam::new("Vibrato Shape", OscillatorShape::Sine),
-            tremolo_shape: EnumParam::new("Tremolo Shape", OscillatorShape::Sine),
+            meter_mode: EnumParam::new("Meter", MeterMode::Peak).non_automatable(),
+            meter_speed: EnumParam::new("Meter Speed", MeterSpeed::Fast).non_automatable(),
+            scope_signal: EnumParam::new("Scope", ScopeSignal::VibratoLfo).non_automatable(),
+
+            part_a: PatchParams::new(0),
+            part_b: PatchParams::new(POLY_MOD_IDS_PER_PART),
+            velocity_crossfade: VelocityCrossfadeParams::default(),
+            trigger: InputTriggerParams::default(),
+            input_transform: InputTransformParams::default(),
+            macros: std::array::from_fn(|idx| MacroParams::new(idx + 1)),
+            mod_slots: std::array::from_fn(|idx| ModSlotParams::new(idx + 1)),
+            combiner: CombinerParams::default(),
+        }
+    }
+}
+
+impl SubSynthParams {
+    /// The parameters for the part with the given index. Out of range indices return part B.
+    fn part(&self, part: usize) -> &PatchParams {
+        match part {
+            0 => &self.part_a,
+            _ => &self.part_b,
+        }
+    }
+
+    /// The number of parts that are currently playing.
+    fn num_active_parts(&self) -> usize {
+        if self.multitimbral.value() {
+            NUM_PARTS
+        } else {
+            1
+        }
+    }
+
+    /// The MIDI channel a part listens to, zero-indexed like the events' channels. `None` means
+    /// omni.
+    fn part_midi_channel(&self, part: usize) -> Option<u8> {
+        match self.part(part).midi_channel.value() {
+            0 => None,
+            channel => Some(channel as u8 - 1),
+        }
+    }
+
+    /// A snapshot of the input transform's settings.
+    fn input_transform(&self) -> TransformSettings {
+        let params = &self.input_transform;
+        TransformSettings {
+            velocity_scale: params.velocity_scale.value(),
+            velocity_offset: params.velocity_offset.value(),
+            velocity_curve: params.velocity_curve.value(),
+            channel: match params.channel.value() {
+                0 => None,
+                channel => Some(channel as u8 - 1),
+            },
+            transpose: params.transpose.value(),
+        }
+    }
+
+    /// The choke group a part's notes belong to, if any.
+    fn part_choke_group(&self, part: usize) -> Option<u8> {
+        match self.part(part).choke_group.value() {
+            0 => None,
+            group => Some(group as u8),
+        }
+    }
+
+    /// How loud a part plays a note with the given velocity. This is always 1 unless the velocity
+    /// crossfade is used.
+    fn layer_gain(&self, part: usize, velocity: f32) -> f32 {
+        let crossfade = &self.velocity_crossfade;
+        if !self.multitimbral.value() || !crossfade.enabled.value() {
+            return 1.0;
+        }
+
+        let (gain_a, gain_b) = velocity_crossfade(
+            velocity,
+            crossfade.center.value(),
+            crossfade.width.value(),
+            crossfade.curve.value(),
+        );
+        match part {
+            0 => gain_a,
+            _ => gain_b,
+        }
+    }
+
+    /// The part index and the parameter for the given polyphonic modulation ID, if it exists.
+    fn poly_modulated_param(&self, poly_modulation_id: u32) -> Option<(usize, &FloatParam)> {
+        let part = (poly_modulation_id / POLY_MOD_IDS_PER_PART) as usize;
+        if part >= NUM_PARTS {
+            return None;
+        }
+
+        let params = self.part(part);
+        match poly_modulation_id % POLY_MOD_IDS_PER_PART {
+            GAIN_POLY_MOD_ID => Some((part, &params.gain)),
+            CUTOFF_POLY_MOD_ID => Some((part, &params.filter.cutoff)),
+            RESONANCE_POLY_MOD_ID => Some((part, &params.filter.resonance)),
+            PAN_POLY_MOD_ID => Some((part, &params.pan)),
+            _ => None,
         }
     }
 }
@@ -404,42 +772,103 @@ impl Plugin for SubSynth {
 
     const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
-    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
-        main_input_channels: NonZeroU32::new(2),
-        main_output_channels: NonZeroU32::new(2),
-        ..AudioIOLayout::const_default()
-    }];
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            ..AudioIOLayout::const_default()
+        },
+        // The channels are ordered front left, front right, rear left, rear right
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(4),
+            names: PortNames {
+                layout: Some("Quad"),
+                ..PortNames::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        },
+    ];
 
-    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
     type SysExMessage = ();
-    type BackgroundTask = ();
+    type BackgroundTask = PreviewTask;
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
-    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
-        editor::create(self.params.clone(), self.params.editor_state.clone())
+
+    fn filter_state(state: &mut PluginState) {
+        migration::migrate(state);
+    }
+
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let params = self.params.clone();
+        Box::new(move |task| preview::run(params.clone(), task))
+    }
+
+    #[cfg(feature = "editor")]
+    fn editor(&mut self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(
+            self.params.clone(),
+            self.meter.clone(),
+            self.clipped.clone(),
+            self.dsp_error.clone(),
+            self.correlation.clone(),
+            self.mod_scope.clone(),
+            self.performance_input.clone(),
+            self.transport_display.clone(),
+            self.params.editor.state.clone(),
+            async_executor,
+        )
     }
 
     fn initialize(
         &mut self,
         _audio_io_layout: &AudioIOLayout,
-        _buffer_config: &BufferConfig,
-        _context: &mut impl InitContext<Self>,
+        buffer_config: &BufferConfig,
+        context: &mut impl InitContext<Self>,
     ) -> bool {
-        // After `PEAK_METER_DECAY_MS` milliseconds of pure silence, the peak meter's value should
-        // have dropped by 12 dB
+        self.output_meter = OutputMeter::new(buffer_config.sample_rate);
+        self.input_trigger = InputTrigger::new(buffer_config.sample_rate);
+        self.correlation_meter = CorrelationMeter::new(buffer_config.sample_rate);
+        self.scope_tap = ScopeTap::new(buffer_config.sample_rate);
+        self.haas_delays = vec![HaasDelay::new(buffer_config.sample_rate); NUM_VOICES];
+        self.wavetables = WavetableBank::new(buffer_config.sample_rate);
+        self.block_buffers =
+            BlockBuffers::new((buffer_config.max_buffer_size as usize).clamp(1, MAX_BLOCK_SIZE));
+        // This is also called after loading a patch, which may have replaced the snapshots
+        self.morph.invalidate();
+        self.reported_voice_capacity = self.voice_capacity();
+        context.set_current_voice_capacity(self.reported_voice_capacity);
 
         true
     }
 
     fn reset(&mut self) {
-        self.prng = Pcg32::new(420, 1337);
+        self.prng_seed = self.params.seed.value();
+        self.prng = Pcg32::new(self.prng_seed as u64, 1337);
 
         self.voices.fill(None);
+        self.voice_allocator.reset();
         self.next_internal_voice_id = 0;
+        self.parts = std::array::from_fn(|_| PartState::default());
+        self.expected_transport_pos = None;
+        self.latched_notes = [[false; 128]; 16];
+        self.pedal = Pedal::default();
+        self.input_trigger.reset();
+        self.input_transform = InputTransform::default();
+        self.midi_input = MidiInput::default();
+        self.performance_events = PerformanceEvents::default();
+        self.output_meter.reset();
+        self.correlation_meter.reset();
+        for haas_delay in &mut self.haas_delays {
+            haas_delay.reset();
+        }
+        self.bypassed = self.params.bypass.value();
+        self.bypass_fade.reset(if self.bypassed { 1.0 } else { 0.0 });
     }
 
     fn process(
@@ -447,6 +876,280 @@ impl Plugin for SubSynth {
         buffer: &mut Buffer,
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        let transport = HostTransport::from(context.transport());
+        self.process_buffer(buffer, &transport, context)
+    }
+}
+
+impl BlockBuffers {
+    fn new(max_block_len: usize) -> Self {
+        let buffer = || vec![0.0; max_block_len];
+        Self {
+            input: buffer(),
+            bypassed_input: [buffer(), buffer()],
+            master_gain: buffer(),
+            patch_trim: buffer(),
+            bypass_fade: buffer(),
+            haas_delay_ms: buffer(),
+            width: buffer(),
+            drone_gain: buffer(),
+            macro_values: std::array::from_fn(|_| buffer()),
+            voice_gain: vec![buffer(); NUM_VOICES],
+            voice_cutoff: vec![buffer(); NUM_VOICES],
+            voice_resonance: vec![buffer(); NUM_VOICES],
+            voice_pan: vec![buffer(); NUM_VOICES],
+            parts: std::array::from_fn(|_| PartBuffers::new(max_block_len)),
+        }
+    }
+
+    /// The longest block these buffers can hold.
+    fn max_block_len(&self) -> usize {
+        self.master_gain.len()
+    }
+}
+
+impl PartBuffers {
+    fn new(max_block_len: usize) -> Self {
+        let buffer = || vec![0.0; max_block_len];
+        Self {
+            gain: buffer(),
+            pan: buffer(),
+            cutoff: buffer(),
+            resonance: buffer(),
+            filter_morph: buffer(),
+            filter_fm_amount: buffer(),
+            filter_env_amount: buffer(),
+            filter_env_resonance_ratio: buffer(),
+            tone: buffer(),
+            supersaw_detune: buffer(),
+            supersaw_mix: buffer(),
+            pd_amount: buffer(),
+            pd_env_amount: buffer(),
+            pitch_env_amount: buffer(),
+            vibrato_intensity: buffer(),
+            tremolo_intensity: buffer(),
+            amp_envelope_level: buffer(),
+            filter_cut_envelope_level: buffer(),
+            filter_res_envelope_level: buffer(),
+            mseg_amount: buffer(),
+            drift_depth: buffer(),
+            surround_y: buffer(),
+        }
+    }
+
+    /// Fill the first `block_len` values of every buffer with the part's smoothed values.
+    fn fill(&mut self, params: &PatchParams, morph: &Morph, block_len: usize) {
+        morph.next_block(&params.gain, &mut self.gain, block_len);
+        morph.next_block(&params.pan, &mut self.pan, block_len);
+        let filter_params = &params.filter;
+        morph.next_block(&filter_params.cutoff, &mut self.cutoff, block_len);
+        morph.next_block(&filter_params.resonance, &mut self.resonance, block_len);
+        morph.next_block(&filter_params.morph, &mut self.filter_morph, block_len);
+        morph.next_block(
+            &filter_params.fm_amount,
+            &mut self.filter_fm_amount,
+            block_len,
+        );
+        morph.next_block(
+            &filter_params.envelope_amount,
+            &mut self.filter_env_amount,
+            block_len,
+        );
+        morph.next_block(
+            &filter_params.envelope_resonance_ratio,
+            &mut self.filter_env_resonance_ratio,
+            block_len,
+        );
+        morph.next_block(&filter_params.tone, &mut self.tone, block_len);
+        let osc_params = &params.osc;
+        morph.next_block(
+            &osc_params.supersaw_detune,
+            &mut self.supersaw_detune,
+            block_len,
+        );
+        morph.next_block(&osc_params.supersaw_mix, &mut self.supersaw_mix, block_len);
+        morph.next_block(&osc_params.pd_amount, &mut self.pd_amount, block_len);
+        morph.next_block(
+            &osc_params.pd_env_amount,
+            &mut self.pd_env_amount,
+            block_len,
+        );
+        morph.next_block(
+            &params.pitch_env.amount,
+            &mut self.pitch_env_amount,
+            block_len,
+        );
+        morph.next_block(
+            &params.vibrato.intensity,
+            &mut self.vibrato_intensity,
+            block_len,
+        );
+        morph.next_block(
+            &params.tremolo.intensity,
+            &mut self.tremolo_intensity,
+            block_len,
+        );
+        morph.next_block(
+            &params.amp_env.envelope_level,
+            &mut self.amp_envelope_level,
+            block_len,
+        );
+        morph.next_block(
+            &params.filter_cut_env.envelope_level,
+            &mut self.filter_cut_envelope_level,
+            block_len,
+        );
+        morph.next_block(
+            &params.filter_res_env.envelope_level,
+            &mut self.filter_res_envelope_level,
+            block_len,
+        );
+        morph.next_block(&params.mseg.amount, &mut self.mseg_amount, block_len);
+        morph.next_block(&params.drift.depth, &mut self.drift_depth, block_len);
+        morph.next_block(&params.surround.position_y, &mut self.surround_y, block_len);
+    }
+}
+
+impl<'a> PartBlock<'a> {
+    fn new(
+        params: &PatchParams,
+        morph: &Morph,
+        buffers: &'a mut PartBuffers,
+        block_len: usize,
+        tempo: f64,
+        sample_rate: f32,
+    ) -> Self {
+        buffers.fill(params, morph, block_len);
+        let buffers: &'a PartBuffers = buffers;
+        Self {
+            gain: &buffers.gain[..block_len],
+            pan: &buffers.pan[..block_len],
+            cutoff: &buffers.cutoff[..block_len],
+            resonance: &buffers.resonance[..block_len],
+            filter_morph: &buffers.filter_morph[..block_len],
+            filter_fm_amount: &buffers.filter_fm_amount[..block_len],
+            filter_env_amount: &buffers.filter_env_amount[..block_len],
+            filter_env_resonance_ratio: &buffers.filter_env_resonance_ratio[..block_len],
+            tone: &buffers.tone[..block_len],
+            supersaw_detune: &buffers.supersaw_detune[..block_len],
+            supersaw_mix: &buffers.supersaw_mix[..block_len],
+            pd_amount: &buffers.pd_amount[..block_len],
+            pd_env_amount: &buffers.pd_env_amount[..block_len],
+            pitch_env_amount: &buffers.pitch_env_amount[..block_len],
+            vibrato_intensity: &buffers.vibrato_intensity[..block_len],
+            tremolo_intensity: &buffers.tremolo_intensity[..block_len],
+            amp_envelope_level: &buffers.amp_envelope_level[..block_len],
+            filter_cut_envelope_level: &buffers.filter_cut_envelope_level[..block_len],
+            filter_res_envelope_level: &buffers.filter_res_envelope_level[..block_len],
+            mseg_amount: &buffers.mseg_amount[..block_len],
+            drift_depth: &buffers.drift_depth[..block_len],
+            surround_y: &buffers.surround_y[..block_len],
+            mseg_delta: match params.mseg.sync.value().beats() {
+                Some(beats) => (tempo / 60.0 / beats) as f32 / sample_rate,
+                None => 1000.0 / (morph.value(&params.mseg.length_ms) * sample_rate),
+            },
+            drift_rate: morph.value(&params.drift.rate),
+            spread_x: morph.value(&params.surround.spread_x),
+            spread_y: morph.value(&params.surround.spread_y),
+            osc_source: params.osc.source.value(),
+            waveform: params.osc.waveform.value(),
+            filter_type: params.filter.filter_type.value(),
+            pd_curve: params.osc.pd_curve.value(),
+            tone_key_tracking: morph.value(&params.filter.tone_key_tracking),
+            mseg_destination: params.mseg.destination.value(),
+            pitch_env_source: params.pitch_env.source.value(),
+            vibrato_scope: params.vibrato.scope.value(),
+            tremolo_scope: params.tremolo.scope.value(),
+            vibrato_shape: params.vibrato.shape.value(),
+            tremolo_shape: params.tremolo.shape.value(),
+            drawbar_gains: std::array::from_fn(|idx| {
+                drawbar_gain(params.drawbars[idx].level.value())
+            }),
+            percussion: PercussionSettings {
+                percussion: params.percussion.harmonic.value(),
+                level: morph.value(&params.percussion.level),
+                // The envelope decays by 60 dB over the decay time
+                decay_per_sample: 0.001f32
+                    .powf(1000.0 / (morph.value(&params.percussion.decay_ms) * sample_rate)),
+            },
+        }
+    }
+}
+
+impl Voice {
+    /// The voice's polyphonic modulation state for the parameter with the given polyphonic
+    /// modulation ID, if it exists. This doesn't check whether the ID belongs to the voice's part.
+    fn poly_modulation(
+        &mut self,
+        poly_modulation_id: u32,
+    ) -> Option<&mut Option<(f32, Smoother<f32>)>> {
+        match poly_modulation_id % POLY_MOD_IDS_PER_PART {
+            GAIN_POLY_MOD_ID => Some(&mut self.voice_gain),
+            CUTOFF_POLY_MOD_ID => Some(&mut self.voice_cutoff),
+            RESONANCE_POLY_MOD_ID => Some(&mut self.voice_resonance),
+            PAN_POLY_MOD_ID => Some(&mut self.voice_pan),
+            _ => None,
+        }
+    }
+
+    /// Whether the voice's key is still held down, or held by latch or the pedal.
+    fn is_held(&self) -> bool {
+        !self.drone
+            && self.amp_envelope.get_state() != ADSREnvelopeState::Release
+            && self.amp_envelope.get_state() != ADSREnvelopeState::Idle
+    }
+}
+
+impl SubSynth {
+    /// Create the synth with its own state, playing the patch from `params`.
+    fn new(params: Arc<SubSynthParams>) -> Self {
+        Self {
+            morph: Morph::new(&params),
+            prng: Pcg32::new(params.seed.value() as u64, 1337),
+            prng_seed: params.seed.value(),
+            params,
+
+            output_meter: OutputMeter::new(44100.0),
+            meter: Arc::new(MeterDisplay::default()),
+            correlation_meter: CorrelationMeter::new(44100.0),
+            correlation: Arc::new(AtomicF32::new(1.0)),
+            scope_tap: ScopeTap::new(44100.0),
+            mod_scope: Arc::new(ModScope::default()),
+            performance_input: Arc::new(PerformanceInput::default()),
+            performance_events: PerformanceEvents::default(),
+            transport_display: Arc::new(TransportDisplay::default()),
+            clipped: Arc::new(AtomicBool::new(false)),
+            dsp_error: Arc::new(AtomicBool::new(false)),
+            voices: [0; NUM_VOICES].map(|_| None),
+            haas_delays: Vec::new(),
+            wavetables: WavetableBank::default(),
+            next_internal_voice_id: 0,
+            voice_allocator: VoiceAllocator::new(),
+            parts: std::array::from_fn(|_| PartState::default()),
+            expected_transport_pos: None,
+            latched_notes: [[false; 128]; 16],
+            pedal: Pedal::default(),
+            frozen: false,
+            reported_voice_capacity: NUM_VOICES as u32,
+            input_trigger: InputTrigger::new(44100.0),
+            input_transform: InputTransform::default(),
+            cc_map: CcMap::default(),
+            stepped_overrides: Default::default(),
+            midi_input: MidiInput::default(),
+            bypassed: false,
+            bypass_fade: Smoother::new(SmoothingStyle::Linear(BYPASS_FADE_MS)),
+            block_buffers: BlockBuffers::new(MAX_BLOCK_SIZE),
+        }
+    }
+
+    /// Everything [`Plugin::process()`] does, with the transport already copied out of the process
+    /// context.
+    fn process_buffer(
+        &mut self,
+        buffer: &mut Buffer,
+        transport: &HostTransport,
+        context: &mut impl HostContext,
     ) -> ProcessStatus {
         // NIH-plug has a block-splitting adapter for `Buffer`. While this works great for effect
         // plugins, for polyphonic synths the block size should be `min(MAX_BLOCK_SIZE,
@@ -454,110 +1157,275 @@ impl Plugin for SubSynth {
         // split on note events, it's easier to work with raw audio here and to do the splitting by
         // hand.
         let num_samples = buffer.samples();
-        let sample_rate = context.transport().sample_rate;
+        let sample_rate = transport.sample_rate;
         let output = buffer.as_slice();
 
-        let mut next_event = context.next_event();
-        let mut block_start: usize = 0;
-        let mut block_end: usize = MAX_BLOCK_SIZE.min(num_samples);
-        while block_start < num_samples {
-            // First of all, handle all note events that happen at the start of the block, and cut
-            // the block short if another event happens before the end of it. To handle polyphonic
-            // modulation for new notes properly, we'll keep track of the next internal note index
-            // at the block's start. If we receive polyphonic modulation that matches a voice that
-            // has an internal note ID that's great than or equal to this one, then we should start
-            // the note's smoother at the new value instead of fading in from the global value.
-            let this_sample_internal_voice_id_start = self.next_internal_voice_id;
-            'events: loop {
-                match next_event {
-                    // If the event happens now, then we'll keep processing events
-                    Some(event) if (event.timing() as usize) < block_end => {
-                        // This synth doesn't support any of the polyphonic expression events. A
-                        // real synth plugin, however, will want to support those.
-                        match event {
-                            NoteEvent::NoteOn {
-                                timing,
-                                voice_id,
-                                channel,
-                                note,
+        // Hosts without a tempo (or the standalone version) fall back to the internal tempo
+        let tempo = transport
+            .tempo
+            .unwrap_or_else(|| self.params.internal_tempo.value() as f64);
+        if self.params.editor.is_open() {
+            self.transport_display.update(transport, tempo);
+        }
+        let lfo_rates: [(f32, f32); NUM_PARTS] =
+            std::array::from_fn(|part| self.lfo_rates(part, tempo));
+        for (part, part_state) in self.parts.iter_mut().enumerate() {
+            if let Ok(shape) = self.params.part(part).mseg.shape.try_read() {
+                part_state.mseg_shape = *shape;
+            }
+        }
+        if let Ok(cc_map) = self.params.cc_map.try_read() {
+            self.cc_map = *cc_map;
+        }
+        let seed = self.params.seed.value();
+        if seed != self.prng_seed {
+            self.prng_seed = seed;
+            self.prng = Pcg32::new(seed as u64, 1337);
+        }
+        // CLAP hosts use the voice capacity to decide how many voices to keep per-voice modulation
+        // for
+        let voice_capacity = self.voice_capacity();
+        if voice_capacity != self.reported_voice_capacity {
+            self.reported_voice_capacity = voice_capacity;
+            context.set_current_voice_capacity(voice_capacity);
+        }
+        if let Ok(snapshots) = self.params.morph_snapshots.try_read() {
+            self.morph.update(&snapshots);
+        }
+        let bypass = self.params.bypass.value();
+        if bypass != self.bypassed {
+            self.bypassed = bypass;
+            self.bypass_fade.set_target(sample_rate, if bypass { 1.0 } else { 0.0 });
+        }
+        // When the transport starts playing, loops, or jumps, the LFOs can be reset to the phase
+        // they would have at the new song position so playback stays deterministic
+        match (transport.playing, transport.pos_samples) {
+            (true, Some(pos_samples)) => {
+                if self.params.lfo_transport_sync.value()
+                    && self.expected_transport_pos != Some(pos_samples)
+                {
+                    self.sync_lfos_to_transport(
+                        pos_samples as f64 / sample_rate as f64,
+                        &lfo_rates,
+                    );
+                }
+                self.expected_transport_pos = Some(pos_samples + num_samples as i64);
+            }
+            _ => self.expected_transport_pos = None,
+        }
+
+        // Turning latch off releases everything it was holding on to
+        let latch = self.params.latch.value();
+        let pedal_mode = self.params.pedal_mode.value();
+        let fixed_velocity = if self.params.fixed_velocity.value() {
+            Some(self.params.fixed_velocity_value.value())
+        } else {
+            None
+        };
+        let num_parts = self.params.num_active_parts();
+        let part_midi_channels: [Option<u8>; NUM_PARTS] =
+            std::array::from_fn(|part| self.params.part_midi_channel(part));
+        if !latch {
+            self.release_latched_notes(sample_rate);
+        }
+
+        // Freezing captures whatever is sounding at the start of the buffer
+        let freeze = self.params.freeze.value();
+        if freeze != self.frozen {
+            if freeze {
+                self.freeze_voices();
+            } else {
+                self.release_drone_voices();
+            }
+            self.frozen = freeze;
+        }
+
+        // The input trigger scans the input ahead of the blocks. Since the input shares its buffers
+        // with the output, it can only look at samples that haven't been rendered yet.
+        let trigger_enabled = self.params.trigger.enabled.value();
+        let trigger_threshold_db = self.params.trigger.threshold_db.value();
+        let mut trigger_pos: usize = 0;
+        let mut pending_trigger: Option<PluginNoteEvent<Self>> = None;
+        if !trigger_enabled {
+            if let Some(note) = self.input_trigger.note.take() {
+                self.start_release_for_voices(sample_rate, None, 0, note);
+            }
+        }
+
+        // While bypassing, the audio input passes through if any part uses it
+        let input_passthrough = (self.bypassed || self.bypass_fade.is_smoothing())
+            && (0..num_parts)
+                .any(|part| self.params.part(part).osc.source.value() == OscSource::AudioInput);
+
+        let input_transform = self.params.input_transform();
+        let mut next_event = self.next_host_event(context, &input_transform);
+        // Without any voices, events, or an input to listen to, the output stays silent and there's
+        // no point in rendering it
+        let eco_idle = self.params.eco_idle.value();
+        if eco_idle
+            && next_event.is_none()
+            && !self.performance_events.has_changes(&self.performance_input)
+            && !trigger_enabled
+            && !input_passthrough
+            && self.voices.iter().all(Option::is_none)
+        {
+            for channel in output.iter_mut() {
+                channel.fill(0.0);
+            }
+
+            return ProcessStatus::Normal;
+        }
+
+        let max_block_len = self.block_buffers.max_block_len();
+        let mut block_start: usize = 0;
+        let mut block_end: usize = max_block_len.min(num_samples);
+        while block_start < num_samples {
+            if trigger_enabled && pending_trigger.is_none() {
+                while trigger_pos < block_end {
+                    // The main input is always stereo
+                    let sample = (output[0][trigger_pos] + output[1][trigger_pos]) * 0.5;
+                    let event = self.input_trigger.next_sample(sample, trigger_threshold_db);
+                    trigger_pos += 1;
+                    if let Some(event) = event {
+                        pending_trigger =
+                            Some(self.input_trigger_event(event, trigger_pos as u32 - 1));
+                        break;
+                    }
+                }
+            }
+            // Notes triggered by the input start at the exact sample the threshold was crossed at
+            if let Some(event) = pending_trigger {
+                if event.timing() as usize > block_start {
+                    block_end = block_end.min(event.timing() as usize);
+                }
+            }
+
+            // First of all, handle all note events that happen at the start of the block, and cut
+            // the block short if another event happens before the end of it. To handle polyphonic
+            // modulation for new notes properly, we'll keep track of the next internal note index
+            // at the block's start. If we receive polyphonic modulation that matches a voice that
+            // has an internal note ID that's great than or equal to this one, then we should start
+            // the note's smoother at the new value instead of fading in from the global value.
+            let this_sample_internal_voice_id_start = self.next_internal_voice_id;
+            'events: loop {
+                // The performance page's keys and wheels and triggered notes are handled like any
+                // other note event, before the host's events for the same sample. The performance
+                // page is only checked at the start of the buffer.
+                let performance_event = if block_start == 0 {
+                    self.performance_events
+                        .next_event(&self.performance_input, 0)
+                } else {
+                    None
+                };
+                let from_trigger = performance_event.is_none()
+                    && pending_trigger
+                        .filter(|event| event.timing() as usize == block_start)
+                        .is_some();
+                let internal = performance_event.is_some() || from_trigger;
+                let event = if performance_event.is_some() {
+                    performance_event
+                } else if from_trigger {
+                    pending_trigger.take()
+                } else {
+                    next_event
+                };
+                match event.map(|event| {
+                    (event_position(event.timing(), block_start, block_end), event)
+                }) {
+                    // If the event happens now, then we'll keep processing events
+                    Some((EventPosition::Now, event)) => {
+                        // Events on channels none of the parts listen to are meant for other
+                        // instances. Notes triggered by the audio input or played on the
+                        // performance page don't have a real channel.
+                        let accepted = internal
+                            || part_midi_channels[..num_parts]
+                                .iter()
+                                .any(|&part_channel| accepts_channel(part_channel, event.channel()));
+                        let voice_event = if accepted {
+                            self.midi_input.handle(&event)
+                        } else {
+                            None
+                        };
+                        match voice_event {
+                            // Pressing a latched note again releases it
+                            Some(VoiceEvent::NoteOn { channel, note, .. })
+                                if latch && *self.latched_note(channel, note) =>
+                            {
+                                *self.latched_note(channel, note) = false;
+                                self.start_release_for_voices(sample_rate, None, channel, note);
+                            }
+                            Some(VoiceEvent::NoteOn {
+                                timing,
+                                voice_id,
+                                channel,
+                                note,
                                 velocity,
-                            } => {
-                                let pan: f32 = 0.5;
-                                let pressure: f32 = 1.0;
-                                let brightness: f32 = 1.0;
-                                let expression: f32 = 1.0;
-                                let vibrato: f32 = 0.0;
-                                let tuning: f32 = 0.0;
-                                let initial_phase: f32 = self.prng.gen();
-                                let mut vibrato_lfo = Modulator::new(
-                                    self.params.vibrato_rate.value(), 
-                                    self.params.vibrato_intensity.value(), 
-                                    self.params.vibrato_attack.value(), 
-                                    self.params.vibrato_shape.value(),
-                                );
-                                let mut tremolo_lfo = Modulator::new(
-                                    self.params.tremolo_rate.value(), 
-                                    self.params.tremolo_intensity.value(), 
-                                    self.params.tremolo_attack.value(), 
-                                    self.params.tremolo_shape.value(),
-                                );
-                                // This starts with the attack portion of the amplitude envelope
-                                let (amp_envelope, cutoff_envelope, resonance_envelope) =
-                                    self.construct_envelopes(sample_rate, velocity);
-                                let voice = self.start_voice(
-                                    context, timing, voice_id, channel, note,
-                                    velocity, // Add velocity parameter
-                                    pan, pressure, brightness, expression, // Add expression parameter
-                                    vibrato,    // Add vibrato parameter
-                                    tuning,
-                                    vibrato_lfo,
-                                    tremolo_lfo,
-                                    amp_envelope,
-                                    cutoff_envelope,
-                                    resonance_envelope,
-                                    self.params.filter_type.value(),
-                                );
-                                
-                                voice.vib_mod = vibrato_lfo.clone();
-                                voice.trem_mod = tremolo_lfo.clone();
-                                voice.velocity_sqrt = velocity.sqrt();
-                                voice.phase = initial_phase;
-                                voice.vib_mod.trigger();
-                                voice.trem_mod.trigger();
-                                let mut pitch = util::midi_note_to_freq(note)
-                                    * (2.0_f32).powf((tuning + voice.tuning ) / 12.0);
-                                voice.phase_delta = pitch / sample_rate;
-                                voice.amp_envelope = amp_envelope;
-                                voice.filter_cut_envelope = cutoff_envelope;
-                                voice.filter_res_envelope = resonance_envelope;
-                                voice.velocity = velocity;
-                                voice.pan = pan;
-
-                                
+                            }) => {
+                                if latch {
+                                    *self.latched_note(channel, note) = true;
+                                }
+                                self.pedal.note_on(channel, note);
+                                let velocity = fixed_velocity.unwrap_or(velocity);
+                                // Every part listening to the note's channel plays the note. Notes
+                                // triggered by the audio input or played on the performance page
+                                // are played by all parts.
+                                let plays_note = |part: usize| {
+                                    internal
+                                        || accepts_channel(part_midi_channels[part], Some(channel))
+                                };
+                                // The choke groups are handled first so a note played on both parts
+                                // doesn't choke itself
+                                for part in 0..num_parts {
+                                    if plays_note(part) {
+                                        if let Some(group) = self.params.part_choke_group(part) {
+                                            self.choke_group(group);
+                                        }
+                                    }
+                                }
+                                for (part, &part_lfo_rates) in
+                                    lfo_rates.iter().enumerate().take(num_parts)
+                                {
+                                    if plays_note(part) {
+                                        self.start_note(
+                                            context,
+                                            part,
+                                            timing,
+                                            voice_id,
+                                            channel,
+                                            note,
+                                            velocity,
+                                            sample_rate,
+                                            part_lfo_rates,
+                                        );
+                                    }
+                                }
                             }
-                            NoteEvent::NoteOff {
-                                timing: _,
+                            Some(VoiceEvent::NoteOff {
                                 voice_id,
                                 channel,
                                 note,
-                                velocity: _,
-                            } => {
-                                self.start_release_for_voices(sample_rate, voice_id, channel, note);
+                            }) => {
+                                // Latched notes keep playing until they're pressed again, and
+                                // notes held by the pedal until it's lifted
+                                let held = (latch && *self.latched_note(channel, note))
+                                    || self.pedal.hold_note_off(pedal_mode, channel, note);
+                                if !held {
+                                    self.start_release_for_voices(sample_rate, voice_id, channel, note);
+                                }
                             }
-                            NoteEvent::Choke {
+                            Some(VoiceEvent::Choke {
                                 timing,
                                 voice_id,
                                 channel,
                                 note,
-                            } => {
+                            }) => {
                                 self.choke_voices(context, timing, voice_id, channel, note);
                             }
-                            NoteEvent::PolyModulation {
-                                timing: _,
+                            Some(VoiceEvent::PolyModulation {
                                 voice_id,
                                 poly_modulation_id,
                                 normalized_offset,
-                            } => {
+                            }) => {
                                 // Polyphonic modulation events are matched to voices using the
                                 // voice ID, and to parameters using the poly modulation ID. The
                                 // host will probably send a modulation event every N samples. This
@@ -567,390 +1435,575 @@ impl Plugin for SubSynth {
                                 // when we can't find the voice index here.
                                 if let Some(voice_idx) = self.get_voice_idx(voice_id) {
                                     let voice = self.voices[voice_idx].as_mut().unwrap();
+                                    let voice_part = voice.part;
+                                    let started_this_block = voice.internal_voice_id
+                                        >= this_sample_internal_voice_id_start;
 
-                                    match poly_modulation_id {
-                                        GAIN_POLY_MOD_ID => {
+                                    match (
+                                        self.params.poly_modulated_param(poly_modulation_id),
+                                        voice.poly_modulation(poly_modulation_id),
+                                    ) {
+                                        // The other part's parameters don't affect this voice
+                                        (Some((part, _)), _) if part != voice_part => (),
+                                        (Some((_, param)), Some(modulation)) => {
                                             // This should either create a smoother for this
                                             // modulated parameter or update the existing one.
                                             // Notice how this uses the parameter's unmodulated
                                             // normalized value in combination with the normalized
                                             // offset to create the target plain value
-                                            let target_plain_value = self
-                                                .params
-                                                .gain
-                                                .preview_modulated(normalized_offset);
-                                            let (_, smoother) =
-                                                voice.voice_gain.get_or_insert_with(|| {
-                                                    (
-                                                        normalized_offset,
-                                                        self.params.gain.smoothed.clone(),
-                                                    )
+                                            let target_plain_value =
+                                                param.preview_modulated(normalized_offset);
+                                            let (stored_offset, smoother) = modulation
+                                                .get_or_insert_with(|| {
+                                                    (normalized_offset, param.smoothed.clone())
                                                 });
+                                            *stored_offset = normalized_offset;
 
                                             // If this `PolyModulation` events happens on the
                                             // same sample as a voice's `NoteOn` event, then it
                                             // should immediately use the modulated value
                                             // instead of slowly fading in
-                                            if voice.internal_voice_id
-                                                >= this_sample_internal_voice_id_start
-                                            {
+                                            if started_this_block {
                                                 smoother.reset(target_plain_value);
                                             } else {
                                                 smoother
                                                     .set_target(sample_rate, target_plain_value);
                                             }
                                         }
-                                        n => nih_debug_assert_failure!(
+                                        _ => nih_debug_assert_failure!(
                                             "Polyphonic modulation sent for unknown poly \
                                             modulation ID {}",
-                                            n
+                                            poly_modulation_id
                                         ),
                                     }
                                 }
                             }
-                            NoteEvent::MonoAutomation {
-                                timing: _,
+                            Some(VoiceEvent::MonoAutomation {
                                 poly_modulation_id,
                                 normalized_value,
-                            } => {
+                            }) => {
                                 // Modulation always acts as an offset to the parameter's current
                                 // automated value. So if the host sends a new automation value for
                                 // a modulated parameter, the modulated values/smoothing targets
                                 // need to be updated for all polyphonically modulated voices.
-                                for voice in self.voices.iter_mut().filter_map(|v| v.as_mut()) {
-                                    match poly_modulation_id {
-                                        GAIN_POLY_MOD_ID => {
+                                match self.params.poly_modulated_param(poly_modulation_id) {
+                                    Some((part, param)) => {
+                                        for voice in self
+                                            .voices
+                                            .iter_mut()
+                                            .filter_map(|v| v.as_mut())
+                                            .filter(|v| v.part == part)
+                                        {
                                             let (normalized_offset, smoother) =
-                                                match voice.voice_gain.as_mut() {
-                                                    Some((o, s)) => (o, s),
+                                                match voice.poly_modulation(poly_modulation_id) {
+                                                    Some(Some((o, s))) => (o, s),
                                                     // If the voice does not have existing
                                                     // polyphonic modulation, then there's nothing
                                                     // to do here. The global automation/monophonic
                                                     // modulation has already been taken care of by
                                                     // the framework.
-                                                    None => continue,
+                                                    _ => continue,
                                                 };
-                                            let target_plain_value =
-                                                self.params.gain.preview_plain(
-                                                    normalized_value + *normalized_offset,
-                                                );
-                                            smoother.set_target(sample_rate, target_plain_value);
-                                        }
-                                        n => nih_debug_assert_failure!(
-                                            "Automation event sent for unknown poly modulation ID \
-                                            {}",
-                                            n
-                                        ),
-                                    }
-                                }
-                            }
-                            NoteEvent::PolyPressure {
-                                timing,
-                                voice_id,
-                                channel,
-                                note,
-                                pressure,
-                            } => {
-                                if let Some(voice_idx) = self.get_voice_idx(voice_id.unwrap_or_default()) {
-                                    if let Some(voice) = self.voices.get_mut(voice_idx) {
-                                        if let Some(voice_inner) = voice.as_mut() {
-                                            let velocity_sqrt = voice_inner.velocity_sqrt;
-                                            let pan = voice_inner.pan;
-                                            let brightness = voice_inner.brightness;
-                                            let expression = voice_inner.expression;
-                                            let tuning = voice_inner.tuning;
-                                            let vibrato = voice_inner.vibrato;
-                                            let amp_envelope = voice_inner.amp_envelope.clone();
-                                            let filter_cut_envelope = voice_inner.filter_cut_envelope.clone();
-                                            let filter_res_envelope = voice_inner.filter_res_envelope.clone();
-                                            let vib_mod = voice_inner.vib_mod.clone();
-                                            let trem_mod = voice_inner.trem_mod.clone();
-                            
-                                            self.handle_poly_event(
-                                                timing,
-                                                voice_id,
-                                                channel,
-                                                note,
-                                                0.0,
-                                                pan,
-                                                brightness,
-                                                expression,
-                                                tuning,
-                                                pressure,
-                                                vibrato,
-                                                Some(&amp_envelope),
-                                                Some(&filter_cut_envelope),
-                                                Some(&filter_res_envelope),
-                                                Some(&vib_mod),
-                                                Some(&trem_mod),
-                                            );
-                                        }
-                                    }
-                                }
-                            }
-                            NoteEvent::PolyVolume {
-                                timing,
-                                voice_id,
-                                channel,
-                                note,
-                                gain,
-                            } => {
-                                if let Some(voice_idx) = self.get_voice_idx(voice_id.unwrap_or_default()) {
-                                    if let Some(voice) = self.voices.get_mut(voice_idx) {
-                                        if let Some(voice_inner) = voice {
-                                            let pan = voice_inner.pan;
-                                            let brightness = voice_inner.brightness;
-                                            let expression = voice_inner.expression;
-                                            let tuning = voice_inner.tuning;
-                                            let vibrato = voice_inner.vibrato;
-                                            let amp_envelope = voice_inner.amp_envelope.clone();
-                                            let filter_cut_envelope = voice_inner.filter_cut_envelope.clone();
-                                            let filter_res_envelope = voice_inner.filter_res_envelope.clone();
-                                            let vib_mod = voice_inner.vib_mod.clone();
-                                            let trem_mod = voice_inner.trem_mod.clone();
-                                            let pressure = voice_inner.pressure;
-                            
-                                            self.handle_poly_event(
-                                                timing,
-                                                voice_id,
-                                                channel,
-                                                note,
-                                                gain,
-                                                pan,
-                                                brightness,
-                                                expression,
-                                                tuning,
-                                                pressure,
-                                                vibrato,
-                                                Some(&amp_envelope),
-                                                Some(&filter_cut_envelope),
-                                                Some(&filter_res_envelope),
-                                                Some(&vib_mod),
-                                                Some(&trem_mod),
+                                            let target_plain_value = param.preview_plain(
+                                                normalized_value + *normalized_offset,
                                             );
+                                            smoother.set_target(sample_rate, target_plain_value);
                                         }
                                     }
+                                    None => nih_debug_assert_failure!(
+                                        "Automation event sent for unknown poly modulation ID {}",
+                                        poly_modulation_id
+                                    ),
                                 }
                             }
-                            NoteEvent::PolyPan {
-                                timing,
+                            Some(VoiceEvent::Expression {
                                 voice_id,
                                 channel,
                                 note,
-                                pan,
-                            } => {
-                                if let Some(voice_idx) = self.get_voice_idx(voice_id.unwrap_or_default()) {
-                                    if let Some(voice) = self.voices.get_mut(voice_idx) {
-                                        if let Some(voice_inner) = voice {
-                                            let gain = voice_inner.velocity;
-                                            let brightness = voice_inner.brightness;
-                                            let expression = voice_inner.expression;
-                                            let tuning = voice_inner.tuning;
-                                            let vibrato = voice_inner.vibrato;
-                                            let amp_envelope = voice_inner.amp_envelope.clone();
-                                            let filter_cut_envelope = voice_inner.filter_cut_envelope.clone();
-                                            let filter_res_envelope = voice_inner.filter_res_envelope.clone();
-                                            let vib_mod = voice_inner.vib_mod.clone();
-                                            let trem_mod = voice_inner.trem_mod.clone();
-                                            let pressure = voice_inner.pressure;
-                            
-                                            self.handle_poly_event(
-                                                timing,
-                                                voice_id,
-                                                channel,
-                                                note,
-                                                gain,
-                                                pan,
-                                                brightness,
-                                                expression,
-                                                tuning,
-                                                pressure,
-                                                vibrato,
-                                                Some(&amp_envelope),
-                                                Some(&filter_cut_envelope),
-                                                Some(&filter_res_envelope),
-                                                Some(&vib_mod),
-                                                Some(&trem_mod),
-                                            );
-                                        }
-                                    }
-                                }
+                                expression,
+                            }) => {
+                                self.apply_voice_expression(
+                                    voice_id,
+                                    channel,
+                                    note,
+                                    expression,
+                                    sample_rate,
+                                );
                             }
-                            NoteEvent::PolyTuning {
-                                timing,
-                                voice_id,
+                            Some(VoiceEvent::Pedal {
                                 channel,
-                                note,
-                                tuning,
-                            } => {
-                                if let Some(voice_idx) = self.get_voice_idx(voice_id.unwrap_or_default()) {
-                                    if let Some(voice) = self.voices.get_mut(voice_idx) {
-                                        if let Some(voice_inner) = voice {
-                                            let gain = voice_inner.velocity;
-                                            let pan = voice_inner.pan;
-                                            let brightness = voice_inner.brightness;
-                                            let expression = voice_inner.expression;
-                                            let vibrato = voice_inner.vibrato;
-                                            let amp_envelope = voice_inner.amp_envelope.clone();
-                                            let filter_cut_envelope = voice_inner.filter_cut_envelope.clone();
-                                            let filter_res_envelope = voice_inner.filter_res_envelope.clone();
-                                            let vib_mod = voice_inner.vib_mod.clone();
-                                            let trem_mod = voice_inner.trem_mod.clone();
-                                            let pressure = voice_inner.pressure;
-                            
-                                            self.handle_poly_event(
-                                                timing,
-                                                voice_id,
-                                                channel,
-                                                note,
-                                                gain,
-                                                pan,
-                                                brightness,
-                                                expression,
-                                                tuning,
-                                                pressure,
-                                                vibrato,
-                                                Some(&amp_envelope),
-                                                Some(&filter_cut_envelope),
-                                                Some(&filter_res_envelope),
-                                                Some(&vib_mod),
-                                                Some(&trem_mod),
-                                            );
-                                        }
-                                    }
-                                }
+                                down: true,
+                            }) => {
+                                // Sostenuto holds on to the notes whose keys are down right now
+                                let held_notes = self
+                                    .voices
+                                    .iter()
+                                    .flatten()
+                                    .filter(|voice| {
+                                        voice.channel == channel
+                                            && !voice.drone
+                                            && voice.amp_envelope.get_state()
+                                                != ADSREnvelopeState::Release
+                                            && voice.amp_envelope.get_state()
+                                                != ADSREnvelopeState::Idle
+                                    })
+                                    .map(|voice| voice.note);
+                                self.pedal.press(pedal_mode, channel, held_notes);
                             }
-                            NoteEvent::PolyVibrato {
-                                timing,
-                                voice_id,
+                            Some(VoiceEvent::Pedal {
                                 channel,
-                                note,
-                                vibrato,
-                            } => {
-                                if let Some(voice_idx) = self.get_voice_idx(voice_id.unwrap_or_default()) {
-                                    if let Some(voice) = self.voices.get_mut(voice_idx) {
-                                        if let Some(voice_inner) = voice {
-                                            let gain = voice_inner.velocity;
-                                            let pan = voice_inner.pan;
-                                            let brightness = voice_inner.brightness;
-                                            let expression = voice_inner.expression;
-                                            let tuning = voice_inner.tuning;
-                                            let amp_envelope = voice_inner.amp_envelope.clone();
-                                            let filter_cut_envelope = voice_inner.filter_cut_envelope.clone();
-                                            let filter_res_envelope = voice_inner.filter_res_envelope.clone();
-                                            let vib_mod = voice_inner.vib_mod.clone();
-                                            let trem_mod = voice_inner.trem_mod.clone();
-                                            let pressure = voice_inner.pressure;
-                            
-                                            self.handle_poly_event(
-                                                timing,
-                                                voice_id,
-                                                channel,
-                                                note,
-                                                gain,
-                                                pan,
-                                                brightness,
-                                                expression,
-                                                tuning,
-                                                pressure,
-                                                vibrato,
-                                                Some(&amp_envelope),
-                                                Some(&filter_cut_envelope),
-                                                Some(&filter_res_envelope),
-                                                Some(&vib_mod),
-                                                Some(&trem_mod),
-                                            );
-                                        }
-                                    }
+                                down: false,
+                            }) => {
+                                for note in self.pedal.lift(channel) {
+                                    self.start_release_for_voices(sample_rate, None, channel, note);
                                 }
                             }
-                            
-                            
-                            // Handle other MIDI events if needed
-                            _ => (),
+                            // Controller changes have already been handled by the MIDI input
+                            None => (),
                         };
 
-                        next_event = context.next_event();
+                        if !internal {
+                            next_event = self.next_host_event(context, &input_transform);
+                        }
                     }
                     // If the event happens before the end of the block, then the block should be cut
                     // short so the next block starts at the event
-                    Some(event) if (event.timing() as usize) < block_end => {
-                        block_end = event.timing() as usize;
+                    Some((EventPosition::SplitAt(timing), _)) => {
+                        block_end = timing;
                         break 'events;
                     }
                     _ => break 'events,
                 }
             }
 
+            // Chords only exist at the start of a block, since blocks are split on every event
+            self.strum_new_voices(this_sample_internal_voice_id_start, sample_rate);
+
+            let block_len = block_end - block_start;
+            let BlockBuffers {
+                input,
+                bypassed_input,
+                master_gain,
+                patch_trim,
+                bypass_fade,
+                haas_delay_ms,
+                width,
+                drone_gain,
+                macro_values,
+                voice_gain,
+                voice_cutoff,
+                voice_resonance,
+                voice_pan,
+                parts,
+            } = &mut self.block_buffers;
+
+            // The input shares its buffers with the output, so it needs to be copied before the
+            // output is cleared. The voices process the input in mono. The main input is always
+            // stereo, even with the quad output layout.
+            input[..block_len].fill(0.0);
+            if (0..num_parts)
+                .any(|part| self.params.part(part).osc.source.value() == OscSource::AudioInput)
+            {
+                for channel in output.iter().take(2) {
+                    for (input_sample, sample) in
+                        input.iter_mut().zip(&channel[block_start..block_end])
+                    {
+                        *input_sample += sample * 0.5;
+                    }
+                }
+            }
+
+            if input_passthrough {
+                for (bypassed_channel, channel) in bypassed_input.iter_mut().zip(output.iter()) {
+                    bypassed_channel[..block_len].copy_from_slice(&channel[block_start..block_end]);
+                }
+            }
+
             // We'll start with silence, and then add the output from the active voices
-            output[0][block_start..block_end].fill(0.0);
-            output[1][block_start..block_end].fill(0.0);
+            for channel in output.iter_mut() {
+                channel[block_start..block_end].fill(0.0);
+            }
 
             // These are the smoothed global parameter values. These are used for voices that do not
             // have polyphonic modulation applied to them. With a plugin as simple as this it would
             // be possible to avoid this completely by simply always copying the smoother into the
             // voice's struct, but that may not be realistic when the plugin has hundreds of
-            // parameters. The `voice_*` arrays are scratch arrays that an individual voice can use.
-            let block_len = block_end - block_start;
-            let mut gain = [0.0; MAX_BLOCK_SIZE];
-            let mut voice_gain = [0.0; MAX_BLOCK_SIZE];
-            self.params.gain.smoothed.next_block(&mut gain, block_len);
+            // parameters. The `voice_*` buffers contain the values for each individual voice.
+            self.morph.set_amount(self.params.morph.value());
+            self.morph.next_block(&self.params.master_gain, master_gain, block_len);
+            self.params.patch_trim.smoothed.next_block(patch_trim, block_len);
+            self.bypass_fade.next_block(bypass_fade, block_len);
+            self.params.haas_delay_ms.smoothed.next_block(haas_delay_ms, block_len);
+            let haas_mono_safe = self.params.haas_mono_safe.value();
+            self.morph.next_block(&self.params.width, width, block_len);
+            let osc_quality = self.params.osc_quality.value();
+            let meter_mode = self.params.meter_mode.value();
+            let meter_speed = self.params.meter_speed.value();
+            self.params.drone_gain.smoothed.next_block(drone_gain, block_len);
+            // Part B's parameters are also smoothed while multitimbral mode is off, since its
+            // voices may still be releasing
+            let cc_overrides = self.cc_map.stepped_overrides(self.midi_input.cc_values());
+            let mut part_buffers = parts.iter_mut();
+            let part_blocks: [PartBlock; NUM_PARTS] = std::array::from_fn(|part| {
+                let buffers = part_buffers.next().unwrap();
+                let mut part_block = PartBlock::new(
+                    self.params.part(part),
+                    &self.morph,
+                    buffers,
+                    block_len,
+                    tempo,
+                    sample_rate,
+                );
+                let overrides = &mut self.stepped_overrides[part];
+                part_block.waveform =
+                    overrides.waveform.apply(cc_overrides.waveform, part_block.waveform);
+                part_block.filter_type =
+                    overrides.filter_type.apply(cc_overrides.filter_type, part_block.filter_type);
+
+                part_block
+            });
+            for (macro_params, values) in self.params.macros.iter().zip(macro_values.iter_mut()) {
+                macro_params.value.smoothed.next_block(values, block_len);
+            }
+            let mod_matrix = ModMatrix::from_params(&self.params.mod_slots, &self.params.combiner);
+            let mod_matrix_active = mod_matrix.is_active();
+            let cc_offsets = self.cc_map.evaluate(self.midi_input.cc_values());
+            let mod_wheel = self.midi_input.cc_values().get(None, MOD_WHEEL_CC);
+            let sustain_pedal = self.midi_input.cc_values().get(None, SUSTAIN_PEDAL_CC);
+            let pitch_bend: [f32; 16] =
+                std::array::from_fn(|channel| self.midi_input.pitch_bend(channel as u8));
+            let channel_pressure: [f32; 16] =
+                std::array::from_fn(|channel| self.midi_input.channel_pressure(channel as u8));
+
+            // Depending on whether a voice has polyphonic modulation applied to it, either the
+            // global parameter values are used, or the voice's smoother is used to generate unique
+            // modulated values for that voice
+            for (voice_idx, voice) in self.voices.iter().enumerate() {
+                if let Some(voice) = voice {
+                    let part_block = &part_blocks[voice.part];
+                    poly_modulated_block(
+                        &voice.voice_gain,
+                        part_block.gain,
+                        &mut voice_gain[voice_idx],
+                        block_len,
+                    );
+                    poly_modulated_block(
+                        &voice.voice_cutoff,
+                        part_block.cutoff,
+                        &mut voice_cutoff[voice_idx],
+                        block_len,
+                    );
+                    poly_modulated_block(
+                        &voice.voice_resonance,
+                        part_block.resonance,
+                        &mut voice_resonance[voice_idx],
+                        block_len,
+                    );
+                    poly_modulated_block(
+                        &voice.voice_pan,
+                        part_block.pan,
+                        &mut voice_pan[voice_idx],
+                        block_len,
+                    );
+                }
+            }
+
+            // The modulation scope follows the newest voice, and only while the editor is open
+            let scope_signal = self.params.scope_signal.value();
+            let scope_voice_idx = if self.params.editor.is_open() {
+                self.voices
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(idx, voice)| voice.as_ref().map(|v| (idx, v.internal_voice_id)))
+                    .max_by_key(|&(_, internal_voice_id)| internal_voice_id)
+                    .map(|(idx, _)| idx)
+            } else {
+                None
+            };
+            let mut scope_value = None;
 
-            // TODO: Some form of band limiting
-            // TODO: Filter
             for (value_idx, sample_idx) in (block_start..block_end).enumerate() {
+                // The global LFOs always keep running, since free running per-voice LFOs also
+                // take their phase from them
+                let mut global_vibrato = [0.0; NUM_PARTS];
+                let mut global_tremolo = [0.0; NUM_PARTS];
+                for (part, part_state) in self.parts.iter_mut().enumerate() {
+                    let (vibrato_rate, tremolo_rate) = lfo_rates[part];
+                    let part_block = &part_blocks[part];
+                    part_state.global_vibrato_lfo.set_parameters(
+                        vibrato_rate,
+                        part_block.vibrato_intensity[value_idx],
+                        part_block.vibrato_shape,
+                    );
+                    part_state.global_tremolo_lfo.set_parameters(
+                        tremolo_rate,
+                        part_block.tremolo_intensity[value_idx],
+                        part_block.tremolo_shape,
+                    );
+                    global_vibrato[part] = part_state.global_vibrato_lfo.get_modulation(sample_rate);
+                    global_tremolo[part] = part_state.global_tremolo_lfo.get_modulation(sample_rate);
+                }
+                let macros: [f32; NUM_MACROS] =
+                    std::array::from_fn(|idx| macro_values[idx][value_idx]);
+
                 // Get mutable reference to the voice at sample_idx
-                for voice in self.voices.iter_mut() {
+                for (voice_idx, voice) in self.voices.iter_mut().enumerate() {
                     if let Some(voice) = voice {
-                        // Depending on whether the voice has polyphonic modulation applied to it,
-                        // either the global parameter values are used, or the voice's smoother is used
-                        // to generate unique modulated values for that voice
-                        let gain = match &voice.voice_gain {
-                            Some((_, smoother)) => {
-                                smoother.next_block(&mut voice_gain, block_len);
-                                &voice_gain
-                            }
-                            None => &gain,
-                        };
+                        // Strummed voices stay silent and frozen until it's their turn
+                        if voice.onset_delay > 0 {
+                            voice.onset_delay -= 1;
+                            continue;
+                        }
 
-                        // This is an exponential smoother repurposed as an AR envelope with values between
-                        // 0 and 1. When a note off event is received, this envelope will start fading out
-                        // again. When it reaches 0, we will terminate the voice.
-                        
-                        
-                        let mut dc_blocker = filter::DCBlocker::new();
+                        let part_block = &part_blocks[voice.part];
+                        let osc_source = part_block.osc_source;
+                        let mseg_destination = part_block.mseg_destination;
                         // Apply filter
-                        let filter_type = self.params.filter_type.value();
-                        let vib_shape =  self.params.vibrato_shape.value();
-                        let trem_shape =  self.params.tremolo_shape.value();
+                        let filter_type = part_block.filter_type;
                         voice.filter = Some(filter_type);
-                        let cutoff = self.params.filter_cut.value();
-                        let resonance = self.params.filter_res.value();
-                        let waveform = self.params.waveform.value();
-                        let vib_int: f32 = self.params.vibrato_intensity.value();
-                        let vib_rate: f32 = self.params.vibrato_rate.value();
-                        // Calculate panning based on voice's pan value
-                        let pan = voice.pan;
+                        let cutoff = voice_cutoff[voice_idx][value_idx];
+                        let resonance = voice_resonance[voice_idx][value_idx];
+                        let filter_morph = part_block.filter_morph[value_idx];
+                        let filter_fm_amount = part_block.filter_fm_amount[value_idx];
+                        let (cutoff_envelope_depth, resonance_envelope_depth) =
+                            filter_envelope_depths(
+                                part_block.filter_env_amount[value_idx],
+                                part_block.filter_env_resonance_ratio[value_idx],
+                            );
+                        let pitch_env_amount = part_block.pitch_env_amount[value_idx];
+                        let waveform = part_block.waveform;
+                        let vib_int: f32 = part_block.vibrato_intensity[value_idx];
+                        // Calculate panning based on voice's pan value, offset by the pan parameter
+                        // and the voice's place within the spread
+                        let pan = (voice.pan
+                            + (voice_pan[voice_idx][value_idx] + voice.placement.0 * part_block.spread_x)
+                                * 0.5)
+                            .clamp(0.0, 1.0);
                         let left_amp = (1.0 - pan).sqrt() as f32;
                         let right_amp = pan.sqrt() as f32;
-                        // Vibrato modulation (LFO-based)
-                        let vibrato_modulation = voice.vib_mod.get_modulation(sample_rate);
+                        // With the quad layout the same equal power law is used between the
+                        // front and the rear speakers, where 0 is the front
+                        let rear = ((part_block.surround_y[value_idx] + voice.placement.1 * part_block.spread_y) * 0.5
+                            + 0.5)
+                            .clamp(0.0, 1.0);
+                        let front_amp = (1.0 - rear).sqrt();
+                        let rear_amp = rear.sqrt();
+                        // Tuning expressions glide to their new value, which also changes the base
+                        // pitch every sample
+                        if voice.tuning_glide.is_smoothing() {
+                            voice.tuning = voice.tuning_glide.next();
+                            if voice.glide.is_none() {
+                                voice.phase_delta = util::midi_note_to_freq(voice.note)
+                                    * (2.0_f32).powf(voice.tuning / 12.0)
+                                    / sample_rate;
+                            }
+                        }
+                        // Glides recompute the base pitch every sample until the target note
+                        // has been reached
+                        if let Some(glide) = voice.glide.as_mut() {
+                            let pitch = util::f32_midi_note_to_freq(glide.next_note())
+                                * (2.0_f32).powf(voice.tuning / 12.0);
+                            voice.phase_delta = pitch / sample_rate;
+                            if glide.is_finished() {
+                                voice.glide = None;
+                            }
+                        }
+                        // Vibrato modulation (LFO-based). The mod matrix's LFO destinations
+                        // lag a sample behind so the LFOs can modulate each other.
+                        let lfo_offsets = voice.previous_mod_offsets;
+                        voice.vib_mod.set_rate_scale(
+                            (2.0_f32).powf(lfo_offsets.vibrato_rate * MOD_LFO_RATE_RANGE_OCTAVES),
+                        );
+                        voice.trem_mod.set_rate_scale(
+                            (2.0_f32).powf(lfo_offsets.tremolo_rate * MOD_LFO_RATE_RANGE_OCTAVES),
+                        );
+                        let (vibrato_modulation, vibrato_lfo_value) = match part_block.vibrato_scope {
+                            LfoScope::PerVoice => (
+                                voice.vib_mod.get_modulation(sample_rate),
+                                voice.vib_mod.last_value(),
+                            ),
+                            LfoScope::Global => (
+                                global_vibrato[voice.part],
+                                self.parts[voice.part].global_vibrato_lfo.last_value(),
+                            ),
+                        };
+                        let (tremolo_modulation, tremolo_lfo_value) = match part_block.tremolo_scope {
+                            LfoScope::PerVoice => (
+                                voice.trem_mod.get_modulation(sample_rate),
+                                voice.trem_mod.last_value(),
+                            ),
+                            LfoScope::Global => (
+                                global_tremolo[voice.part],
+                                self.parts[voice.part].global_tremolo_lfo.last_value(),
+                            ),
+                        };
+                        let vibrato_modulation =
+                            vibrato_modulation * (1.0 + lfo_offsets.vibrato_amount).max(0.0);
+                        let tremolo_modulation =
+                            tremolo_modulation * (1.0 + lfo_offsets.tremolo_amount).max(0.0);
+                        // The pitch envelope bends the voice's pitch by up to the envelope amount
+                        let pitch_envelope_value = match part_block.pitch_env_source {
+                            PitchEnvelopeSource::Dedicated => {
+                                voice.pitch_envelope.next_value(sample_rate)
+                            }
+                            PitchEnvelopeSource::FilterCutoff => {
+                                voice.filter_cut_envelope.previous_value()
+                            }
+                        };
+                        // The drift keeps running even at zero depth so turning it up doesn't
+                        // cause every voice to start from the same spot
+                        let drift = voice.drift.next_value(part_block.drift_rate, sample_rate);
+                        let mut pitch_semitones = pitch_env_amount * pitch_envelope_value
+                            + drift * part_block.drift_depth[value_idx] / 100.0
+                            + pitch_bend[voice.channel as usize % 16] * PITCH_BEND_RANGE_SEMITONES;
+                        let mut cutoff_octaves = 0.0;
+                        let mut mseg_gain = 1.0;
+                        // The MSEG always keeps running since the mod matrix can also use it
+                        let mseg_value = voice.mseg.next_value(&self.parts[voice.part].mseg_shape, part_block.mseg_delta);
+                        if mseg_destination != MsegDestination::Off {
+                            let mseg_amount = part_block.mseg_amount[value_idx];
+                            match mseg_destination {
+                                MsegDestination::Off => (),
+                                MsegDestination::Pitch => {
+                                    pitch_semitones +=
+                                        mseg_amount * mseg_value * MSEG_PITCH_RANGE_SEMITONES
+                                }
+                                MsegDestination::Cutoff => {
+                                    cutoff_octaves =
+                                        mseg_amount * mseg_value * MSEG_CUTOFF_RANGE_OCTAVES
+                                }
+                                // Negative amounts duck the voice with the inverted envelope
+                                MsegDestination::Amplitude => {
+                                    let mseg_value = if mseg_amount >= 0.0 {
+                                        mseg_value
+                                    } else {
+                                        1.0 - mseg_value
+                                    };
+                                    mseg_gain = 1.0 + mseg_amount.abs() * (mseg_value - 1.0);
+                                }
+                            }
+                        }
+                        let mod_sources = ModSourceValues {
+                            macros,
+                            mseg: mseg_value,
+                            velocity: voice.velocity,
+                            // The LFOs are bipolar, the sources are unipolar like the others
+                            vibrato_lfo: (vibrato_lfo_value + 1.0) * 0.5,
+                            tremolo_lfo: (tremolo_lfo_value + 1.0) * 0.5,
+                            mod_wheel,
+                            sustain_pedal,
+                            channel_pressure: channel_pressure[voice.channel as usize % 16],
+                            key_position: voice.note as f32 / 127.0,
+                            key_gate: match voice.amp_envelope.get_state() {
+                                ADSREnvelopeState::Release | ADSREnvelopeState::Idle => 0.0,
+                                _ => 1.0,
+                            },
+                        };
+                        let mod_offsets = if mod_matrix_active {
+                            mod_matrix.evaluate(&mod_sources) + cc_offsets
+                        } else {
+                            cc_offsets
+                        };
+                        voice.previous_mod_offsets = mod_offsets;
+                        // The scope is taped at control rate, so once per block is enough
+                        if value_idx == 0 && scope_voice_idx == Some(voice_idx) {
+                            scope_value = Some(match scope_signal {
+                                ScopeSignal::VibratoLfo => vibrato_lfo_value,
+                                ScopeSignal::TremoloLfo => tremolo_lfo_value,
+                                ScopeSignal::Mseg => mseg_value,
+                                ScopeSignal::AmpEnvelope => voice.amp_envelope.previous_value(),
+                                ScopeSignal::FilterEnvelope => {
+                                    voice.filter_cut_envelope.previous_value()
+                                }
+                                ScopeSignal::Combiner => mod_matrix.combiner_output(&mod_sources),
+                                ScopeSignal::MatrixPitch => mod_offsets.pitch,
+                                ScopeSignal::MatrixCutoff => mod_offsets.cutoff,
+                                ScopeSignal::MatrixAmplitude => mod_offsets.amplitude,
+                            });
+                        }
+                        pitch_semitones += mod_offsets.pitch * MOD_PITCH_RANGE_SEMITONES;
+                        cutoff_octaves += mod_offsets.cutoff * MOD_CUTOFF_RANGE_OCTAVES;
+                        let resonance = (resonance + mod_offsets.resonance).clamp(0.0, 1.0);
+                        let filter_morph =
+                            (filter_morph + mod_offsets.filter_morph).clamp(0.0, 1.0);
+                        let pitch_envelope_ratio = if pitch_semitones != 0.0 {
+                            (2.0_f32).powf(pitch_semitones / 12.0)
+                        } else {
+                            1.0
+                        };
                         // Apply vibrato to the voice's phase_delta (which affects pitch)
-                        let vibrato_phase_delta = voice.phase_delta * (1.0 + (vib_int * vibrato_modulation)); 
-                        //filtered_sample.set_sample_rate(sample_rate);
-                        voice.filter_cut_envelope.advance();
-                        voice.filter_res_envelope.advance();
+                        let vibrato_phase_delta = voice.phase_delta
+                            * pitch_envelope_ratio
+                            * (1.0 + (vib_int * vibrato_modulation));
+                        // The filter envelopes are advanced by the filter itself
                         voice.amp_envelope.advance();
-                        //voice.vib_mod.trigger();
-                        //voice.trem_mod.trigger();
 
                         // Generate waveform for voice
-                        let generated_sample = generate_waveform(waveform, voice.phase);
-                        voice.filter_cut_envelope.set_scale(self.params.filter_cut_envelope_level.value());
-                        voice.filter_res_envelope.set_scale(self.params.filter_res_envelope_level.value());
-                        voice.amp_envelope.set_scale(self.params.amp_envelope_level.value());
+                        let generated_sample = match waveform {
+                            _ if osc_source == OscSource::AudioInput => input[value_idx],
+                            Waveform::Supersaw => voice.supersaw.next_sample(
+                                vibrato_phase_delta,
+                                supersaw_detune_curve(
+                                    (part_block.supersaw_detune[value_idx] + mod_offsets.supersaw_detune)
+                                        .clamp(0.0, 1.0),
+                                ),
+                                (part_block.supersaw_mix[value_idx] + mod_offsets.supersaw_mix)
+                                    .clamp(0.0, 1.0),
+                                osc_quality,
+                            ),
+                            // The filter cutoff envelope doubles as the phase distortion's envelope
+                            Waveform::PhaseDistortion => phase_distortion(
+                                voice.phase as f32,
+                                part_block.pd_amount[value_idx]
+                                    + part_block.pd_env_amount[value_idx]
+                                        * voice.filter_cut_envelope.previous_value(),
+                                part_block.pd_curve,
+                            ),
+                            Waveform::Noise => voice.noise.gen_range(-1.0..1.0),
+                            Waveform::Drawbar => voice.organ.next_sample(
+                                vibrato_phase_delta,
+                                &part_block.drawbar_gains,
+                                part_block.percussion,
+                            ),
+                            _ => self
+                                .wavetables
+                                .sample(waveform, voice.phase as f32, vibrato_phase_delta)
+                                .unwrap_or_else(|| {
+                                    generate_waveform(
+                                        waveform,
+                                        voice.phase as f32,
+                                        vibrato_phase_delta,
+                                        osc_quality,
+                                    )
+                                }),
+                        };
+                        voice.filter_cut_envelope.set_scale(part_block.filter_cut_envelope_level[value_idx]);
+                        voice.filter_res_envelope.set_scale(part_block.filter_res_envelope_level[value_idx]);
+                        voice.amp_envelope.set_scale(part_block.amp_envelope_level[value_idx]);
                         
                         
+                        // Filter FM modulates the cutoff with the oscillator's output at audio
+                        // rate
+                        let cutoff_octaves = cutoff_octaves + filter_fm_amount * generated_sample;
+                        // When filtering the audio input the cutoff tracks the note's pitch,
+                        // with the cutoff parameter setting the cutoff for middle C
+                        let cutoff = if osc_source == OscSource::AudioInput {
+                            (cutoff * vibrato_phase_delta * sample_rate
+                                / util::midi_note_to_freq(60))
+                            .clamp(MIN_FILTER_CUTOFF_HZ, MAX_FILTER_CUTOFF_HZ)
+                        } else {
+                            cutoff
+                        };
+                        let cutoff = if cutoff_octaves != 0.0 {
+                            (cutoff * (2.0_f32).powf(cutoff_octaves))
+                                .clamp(MIN_FILTER_CUTOFF_HZ, MAX_FILTER_CUTOFF_HZ)
+                        } else {
+                            cutoff
+                        };
+
+                        // The voice gain is applied before the filter
+                        let generated_sample = generated_sample * voice_gain[voice_idx][value_idx];
+
                         // Apply filters to the generated sample
                         let filtered_sample= generate_filter(
                                 voice.filter.unwrap(),
@@ -958,46 +2011,178 @@ impl Plugin for SubSynth {
                                 resonance,
                                 &mut voice.filter_cut_envelope,
                                 &mut voice.filter_res_envelope,
+                                &mut voice.svf,
+                                filter_morph,
+                                (
+                                    cutoff_envelope_depth
+                                        * (1.0 + mod_offsets.filter_env_depth).max(0.0),
+                                    resonance_envelope_depth,
+                                ),
+                                osc_quality.double_precision_filter(),
                                 generated_sample,
                                 sample_rate,
                             );
+                        // The tone control is a keytracked lowpass that tames the highs regardless
+                        // of the main filter's settings
+                        let tone = part_block.tone[value_idx];
+                        let filtered_sample = if tone < MAX_FILTER_CUTOFF_HZ {
+                            let tone_cutoff = tone
+                                * (2.0_f32).powf(
+                                    (voice.note as f32 - 60.0) / 12.0 * part_block.tone_key_tracking,
+                                );
+                            voice.tone_filter.process(filtered_sample, tone_cutoff, sample_rate)
+                        } else {
+                            filtered_sample
+                        };
                         
 
 
                         
 
                         // Calculate amplitude for voice
-                        let amp = voice.velocity_sqrt * gain[value_idx] * voice.amp_envelope.get_value() * 0.5 *(voice.trem_mod.get_modulation(sample_rate)+1.0) ;
+                        let amp = voice.velocity_sqrt * voice.amp_envelope.get_value() * 0.5 *(tremolo_modulation+1.0) * mseg_gain
+                            * (1.0 + mod_offsets.amplitude).max(0.0)
+                            * voice.layer_gain;
+                        let amp = if voice.drone {
+                            amp * drone_gain[value_idx]
+                        } else {
+                            amp
+                        };
+                        let amp = match &mut voice.choke_fade {
+                            Some(fade) => {
+                                let gain = *fade;
+                                *fade = (gain - 1000.0 / (CHOKE_FADE_MS * sample_rate)).max(0.0);
+                                amp * gain
+                            }
+                            None => amp,
+                        };
             
-                        // Apply voice-specific processing
-                        let naive_waveform = filtered_sample;
-                        let corrected_waveform = naive_waveform - SubSynth::poly_blep(voice.phase, voice.phase_delta);
-                        let generated_sample = corrected_waveform * amp;
+                        let generated_sample = filtered_sample * amp;
 
-                        // Calculate panning based on voice's pan value
-                        // Apply panning and process the sample
-                        let processed_sample = filter::DCBlocker::new().process(generated_sample);
-                        let processed_left_sample = (1.0 - voice.pan).sqrt() as f32 * processed_sample;
-                        let processed_right_sample = voice.pan.sqrt() as f32 * processed_sample;
+                        let processed_sample = voice.dc_blocker.process(generated_sample);
+                        // Filters at extreme settings can blow up. Their state is reset so the
+                        // voice can recover instead of staying silent.
+                        let processed_sample = if processed_sample.is_finite() {
+                            processed_sample
+                        } else {
+                            voice.svf = MorphingSvf::new();
+                            voice.tone_filter = OnePoleLowpass::new();
+                            voice.dc_blocker = DCBlocker::new();
+                            self.dsp_error.store(true, Ordering::Relaxed);
+                            0.0
+                        };
+                        // The Haas effect delays the left channel for every other voice and the
+                        // right channel for the rest
+                        let haas_delay_samples = haas_delay_ms[value_idx] / 1000.0 * sample_rate;
+                        let (left_sample, right_sample) = if haas_delay_samples > 0.0 {
+                            let delayed_sample = self.haas_delays[voice_idx].process(
+                                voice.internal_voice_id,
+                                processed_sample,
+                                haas_delay_samples,
+                            );
+                            let delay_left = voice.internal_voice_id % 2 == 1;
+                            match (haas_mono_safe, delay_left) {
+                                (true, _) => {
+                                    let side = if delay_left {
+                                        -0.5 * delayed_sample
+                                    } else {
+                                        0.5 * delayed_sample
+                                    };
+                                    (processed_sample + side, processed_sample - side)
+                                }
+                                (false, true) => (delayed_sample, processed_sample),
+                                (false, false) => (processed_sample, delayed_sample),
+                            }
+                        } else {
+                            (processed_sample, processed_sample)
+                        };
+                        let processed_left_sample = left_amp * left_sample;
+                        let processed_right_sample = right_amp * right_sample;
 
                         // Add the processed sample to the output channels
-                        output[0][sample_idx] += processed_left_sample;
-                        output[1][sample_idx] += processed_right_sample;
+                        if let [front_left, front_right, rear_left, rear_right, ..] = output {
+                            front_left[sample_idx] += front_amp * processed_left_sample;
+                            front_right[sample_idx] += front_amp * processed_right_sample;
+                            rear_left[sample_idx] += rear_amp * processed_left_sample;
+                            rear_right[sample_idx] += rear_amp * processed_right_sample;
+                        } else {
+                            output[0][sample_idx] += processed_left_sample;
+                            output[1][sample_idx] += processed_right_sample;
+                        }
 
                         // Update voice phase
-                        voice.phase += vibrato_phase_delta;
+                        voice.phase += vibrato_phase_delta as f64;
                         if voice.phase >= 1.0 {
                             voice.phase -= 1.0;
                         }
                     }
                 }
+
+                // The width is applied to both the front and the rear pair of channels
+                for pair in output.chunks_exact_mut(2) {
+                    if let [left, right] = pair {
+                        (left[sample_idx], right[sample_idx]) = apply_width(
+                            left[sample_idx],
+                            right[sample_idx],
+                            width[value_idx],
+                        );
+                    }
+                }
+
+                // The master gain and the patch trim are applied after all voices have been mixed
+                // together. Bypassing then crossfades to the input, which is silent unless it
+                // passes through.
+                for (channel_idx, channel) in output.iter_mut().enumerate() {
+                    channel[sample_idx] *= master_gain[value_idx] * patch_trim[value_idx];
+                    if bypass_fade[value_idx] > 0.0 {
+                        let bypassed_sample = bypassed_input
+                            .get(channel_idx)
+                            .map_or(0.0, |channel| channel[value_idx]);
+                        channel[sample_idx] +=
+                            (bypassed_sample - channel[sample_idx]) * bypass_fade[value_idx];
+                    }
+                    // Nothing that isn't a number leaves the plugin, and denormals are flushed to
+                    // zero
+                    if !channel[sample_idx].is_finite() {
+                        channel[sample_idx] = 0.0;
+                        self.dsp_error.store(true, Ordering::Relaxed);
+                    } else if channel[sample_idx].is_subnormal() {
+                        channel[sample_idx] = 0.0;
+                    }
+                    if channel[sample_idx].abs() > 1.0 {
+                        self.clipped.store(true, Ordering::Relaxed);
+                    }
+                }
+
+                let left = output[0][sample_idx];
+                let right = output[1][sample_idx];
+
+                // To save resources, a plugin can (and probably should!) only perform expensive
+                // calculations that are only displayed on the GUI while the GUI is open
+                if self.params.editor.is_open() {
+                    let amplitude = ((left + right) / 2.0).abs();
+                    let level = self.output_meter.process(amplitude, meter_mode, meter_speed);
+                    // The peak hold shows the loudest sample on either channel, like the clip
+                    // indicator
+                    self.meter.update(level, left.abs().max(right.abs()));
+
+                    self.correlation_meter.process(left, right);
+                    self.correlation
+                        .store(self.correlation_meter.correlation(), Ordering::Relaxed);
+                }
+            }
+
+            // Without any voices the scope shows a flat line
+            if self.params.editor.is_open() {
+                self.scope_tap
+                    .process(&self.mod_scope, scope_value.unwrap_or(0.0), block_len);
             }
 
-            // Terminate voices whose release period has fully ended. This could be done as part of
-            // the previous loop but this is simpler.
+            // Terminate voices whose amp envelope or choke fade has run out. This could be done as
+            // part of the previous loop but this is simpler.
             for voice in &mut self.voices {
                 if let Some(v) = voice {
-                    if v.releasing && v.amp_envelope.previous_value() == 0.0 {
+                    if v.amp_envelope.is_finished() || v.choke_fade == Some(0.0) {
                         context.send_event(NoteEvent::VoiceTerminated {
                             timing: block_end as u32,
                             voice_id: Some(v.voice_id),
@@ -1011,59 +2196,336 @@ impl Plugin for SubSynth {
 
             // And then just keep processing blocks until we've run out of buffer to fill
             block_start = block_end;
-            block_end = (block_start + MAX_BLOCK_SIZE).min(num_samples);
+            block_end = (block_start + max_block_len).min(num_samples);
         }
 
-        ProcessStatus::Normal
+        // Held notes can go on forever, but once every note has been released the host should
+        // keep processing until the releases and the Haas delay have died out
+        let holding_notes = self.voices.iter().flatten().any(|voice| {
+            voice.drone
+                || (voice.amp_envelope.get_state() != ADSREnvelopeState::Release
+                    && voice.amp_envelope.get_state() != ADSREnvelopeState::Idle)
+        });
+        if holding_notes {
+            ProcessStatus::KeepAlive
+        } else {
+            ProcessStatus::Tail(self.tail_samples(sample_rate))
+        }
+    }
+
+    /// The global LFOs are never retriggered and don't fade in. Their settings are updated from the
+    /// parameters at the start of every block.
+    fn construct_global_lfo() -> Modulator {
+        Modulator::new(1.0, 0.0, 0.0, OscillatorShape::Sine, LfoMode::Free)
+    }
+
+    /// A part's vibrato and tremolo LFO rates in Hertz. Tempo synced LFOs derive their rate from
+    /// `tempo`, the others use their rate parameters.
+    fn lfo_rates(&self, part: usize, tempo: f64) -> (f32, f32) {
+        let params = self.params.part(part);
+        (
+            params
+                .vibrato
+                .sync
+                .value()
+                .rate_hz(tempo)
+                .unwrap_or_else(|| self.morph.value(&params.vibrato.rate)),
+            params
+                .tremolo
+                .sync
+                .value()
+                .rate_hz(tempo)
+                .unwrap_or_else(|| self.morph.value(&params.tremolo.rate)),
+        )
+    }
+
+    /// Reset the global LFOs, and the per-voice LFOs that follow them, to the phase they would have
+    /// if they had been running since the start of the song. `lfo_rates` contains every part's
+    /// vibrato and tremolo rates.
+    fn sync_lfos_to_transport(&mut self, pos_seconds: f64, lfo_rates: &[(f32, f32); NUM_PARTS]) {
+        for (part, &(vibrato_rate, tremolo_rate)) in lfo_rates.iter().enumerate() {
+            let vibrato_phase = (vibrato_rate as f64 * pos_seconds).fract() as f32;
+            let tremolo_phase = (tremolo_rate as f64 * pos_seconds).fract() as f32;
+            self.parts[part].global_vibrato_lfo.set_phase(vibrato_phase);
+            self.parts[part].global_tremolo_lfo.set_phase(tremolo_phase);
+
+            let params = self.params.part(part);
+            let vibrato_free = params.vibrato.mode.value() == LfoMode::Free;
+            let tremolo_free = params.tremolo.mode.value() == LfoMode::Free;
+            for voice in self.voices.iter_mut().flatten() {
+                if voice.part != part {
+                    continue;
+                }
+                if vibrato_free {
+                    voice.vib_mod.set_phase(vibrato_phase);
+                }
+                if tremolo_free {
+                    voice.trem_mod.set_phase(tremolo_phase);
+                }
+            }
+        }
     }
-}
 
-impl SubSynth {
     fn get_voice_idx(&mut self, voice_id: i32) -> Option<usize> {
         self.voices
             .iter_mut()
             .position(|voice| matches!(voice, Some(voice) if voice.voice_id == voice_id))
     }
 
+    /// Find the voice a per-voice event is meant for. See [`event_targets_voice()`].
+    fn find_voice_idx(&self, voice_id: Option<i32>, channel: u8, note: u8) -> Option<usize> {
+        self.voices.iter().position(|voice| {
+            matches!(voice, Some(voice) if event_targets_voice(
+                voice_id,
+                channel,
+                note,
+                voice.voice_id,
+                voice.channel,
+                voice.note,
+            ))
+        })
+    }
+
     fn construct_envelopes(
         &self,
+        part: usize,
         sample_rate: f32,
         velocity: f32,
     ) -> (ADSREnvelope, ADSREnvelope, ADSREnvelope) {
+        let params = self.params.part(part);
+        // None of the envelopes have a hold stage. Their levels are smoothed, so the voices apply
+        // those every sample with `set_scale()`.
         (
             ADSREnvelope::new(
-                self.params.amp_attack_ms.value(),
-                self.params.amp_envelope_level.value(),
-                self.params.amp_decay_ms.value(),
-                self.params.amp_sustain_level.value(),
-                self.params.amp_release_ms.value(),
+                self.morph.value(&params.amp_env.attack_ms) / 1000.0,
+                0.0,
+                self.morph.value(&params.amp_env.decay_ms) / 1000.0,
+                self.morph.value(&params.amp_env.sustain_level),
+                self.morph.value(&params.amp_env.release_ms) / 1000.0,
                 sample_rate,
-                velocity,
+                envelope_velocity(
+                    velocity,
+                    self.morph.value(&params.amp_env.velocity_sensitivity),
+                    params.amp_env.velocity_curve.value(),
+                ),
             ),
             ADSREnvelope::new(
-                self.params.filter_cut_attack_ms.value(),
-                self.params.filter_cut_envelope_level.value(),
-                self.params.filter_cut_decay_ms.value(),
-                self.params.filter_cut_sustain_ms.value(),
-                self.params.filter_cut_release_ms.value(),
+                self.morph.value(&params.filter_cut_env.attack_ms) / 1000.0,
+                0.0,
+                self.morph.value(&params.filter_cut_env.decay_ms) / 1000.0,
+                self.morph.value(&params.filter_cut_env.sustain_level),
+                self.morph.value(&params.filter_cut_env.release_ms) / 1000.0,
                 sample_rate,
-                velocity,
+                envelope_velocity(
+                    velocity,
+                    self.morph.value(&params.filter_cut_env.velocity_sensitivity),
+                    params.filter_cut_env.velocity_curve.value(),
+                ),
             ),
             ADSREnvelope::new(
-                self.params.filter_res_attack_ms.value(),
-                self.params.filter_res_envelope_level.value(),
-                self.params.filter_res_decay_ms.value(),
-                self.params.filter_res_sustain_ms.value(),
-                self.params.filter_res_release_ms.value(),
+                self.morph.value(&params.filter_res_env.attack_ms) / 1000.0,
+                0.0,
+                self.morph.value(&params.filter_res_env.decay_ms) / 1000.0,
+                self.morph.value(&params.filter_res_env.sustain_level),
+                self.morph.value(&params.filter_res_env.release_ms) / 1000.0,
                 sample_rate,
-                velocity,
+                envelope_velocity(
+                    velocity,
+                    self.morph.value(&params.filter_res_env.velocity_sensitivity),
+                    params.filter_res_env.velocity_curve.value(),
+                ),
             ),
         )
     }
 
+    fn construct_pitch_envelope(&self, part: usize) -> ADEnvelope {
+        let params = self.params.part(part);
+        ADEnvelope::new(
+            self.morph.value(&params.pitch_env.attack_ms) / 1000.0,
+            self.morph.value(&params.pitch_env.decay_ms) / 1000.0,
+        )
+    }
+
+    /// Start a voice for a note on one of the patch parts. `lfo_rates` are the part's vibrato and
+    /// tremolo rates.
+    #[allow(clippy::too_many_arguments)]
+    fn start_note(
+        &mut self,
+        context: &mut impl HostContext,
+        part: usize,
+        timing: u32,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+        velocity: f32,
+        sample_rate: f32,
+        (vibrato_rate, tremolo_rate): (f32, f32),
+    ) {
+        // Parts that are entirely faded out by the velocity crossfade don't need a voice
+        let layer_gain = self.params.layer_gain(part, velocity);
+        if layer_gain <= 0.0 {
+            return;
+        }
+
+        let params = self.params.part(part);
+        let pan: f32 = 0.5;
+        let pressure: f32 = 1.0;
+        let brightness: f32 = 1.0;
+        let expression: f32 = 1.0;
+        let vibrato: f32 = 0.0;
+        let tuning: f32 = 0.0;
+        let initial_phase: f32 = self.prng.gen();
+        let mut vibrato_lfo = Modulator::new(
+            vibrato_rate,
+            self.morph.value(&params.vibrato.intensity),
+            self.morph.value(&params.vibrato.attack_ms) / 1000.0,
+            params.vibrato.shape.value(),
+            params.vibrato.mode.value(),
+        );
+        vibrato_lfo.set_phase(self.parts[part].global_vibrato_lfo.phase());
+        let mut tremolo_lfo = Modulator::new(
+            tremolo_rate,
+            self.morph.value(&params.tremolo.intensity),
+            self.morph.value(&params.tremolo.attack_ms) / 1000.0,
+            params.tremolo.shape.value(),
+            params.tremolo.mode.value(),
+        );
+        tremolo_lfo.set_phase(self.parts[part].global_tremolo_lfo.phase());
+        // Glide only kicks in when playing legato, i.e. when another note on the same part is
+        // still being held while this one starts. With poly glide every note glides from wherever
+        // the most recently played voice currently is, even if that voice is already releasing.
+        let glide_mode = params.glide.mode.value();
+        let glide_from = if glide_mode == GlideMode::Off {
+            None
+        } else if params.glide.poly.value() {
+            self.voices
+                .iter()
+                .flatten()
+                .filter(|v| v.part == part && !v.drone)
+                .max_by_key(|v| v.internal_voice_id)
+                .map(|v| match &v.glide {
+                    Some(glide) => glide.current_note(),
+                    None => v.note as f32,
+                })
+                .or(self.parts[part].last_note.map(|note| note as f32))
+        } else {
+            match self.parts[part].last_note {
+                Some(last_note)
+                    if self.voices.iter().flatten().any(|v| {
+                        v.part == part
+                            && !v.drone
+                            && v.amp_envelope.get_state() != ADSREnvelopeState::Release
+                            && v.amp_envelope.get_state() != ADSREnvelopeState::Idle
+                    }) =>
+                {
+                    Some(last_note as f32)
+                }
+                _ => None,
+            }
+        };
+        let glide_time = scaled_glide_time(
+            self.morph.value(&params.glide.time_ms) / 1000.0,
+            glide_from.unwrap_or(note as f32),
+            note as f32,
+            velocity,
+            self.morph.value(&params.glide.key_tracking),
+            self.morph.value(&params.glide.velocity_tracking),
+        );
+        let filter_type = params.filter.filter_type.value();
+        self.parts[part].last_note = Some(note);
+        // This starts with the attack portion of the amplitude envelope. The envelopes run faster
+        // for higher velocities, except with fixed velocity where they use the times as set.
+        let envelope_velocity = if self.params.fixed_velocity.value() {
+            1.0
+        } else {
+            velocity
+        };
+        let (amp_envelope, cutoff_envelope, resonance_envelope) =
+            self.construct_envelopes(part, sample_rate, envelope_velocity);
+        let pitch_envelope = self.construct_pitch_envelope(part);
+        let voice = self.start_voice(
+            context, part, timing, voice_id, channel, note,
+            velocity, // Add velocity parameter
+            pan, pressure, brightness, expression, // Add expression parameter
+            vibrato,    // Add vibrato parameter
+            tuning,
+            vibrato_lfo,
+            tremolo_lfo,
+            amp_envelope,
+            cutoff_envelope,
+            resonance_envelope,
+            filter_type,
+        );
+
+        voice.vib_mod = vibrato_lfo;
+        voice.trem_mod = tremolo_lfo;
+        voice.velocity_sqrt = velocity.sqrt();
+        voice.phase = initial_phase as f64;
+        voice.vib_mod.trigger();
+        voice.trem_mod.trigger();
+        let pitch = util::midi_note_to_freq(note)
+            * (2.0_f32).powf((tuning + voice.tuning ) / 12.0);
+        voice.phase_delta = pitch / sample_rate;
+        voice.glide = glide_from.map(|from_note| {
+            Glide::new(
+                glide_mode,
+                from_note,
+                note as f32,
+                glide_time,
+                sample_rate,
+            )
+        });
+        voice.pitch_envelope = pitch_envelope;
+        voice.velocity = velocity;
+        voice.pan = pan;
+        voice.layer_gain = layer_gain;
+    }
+
+    /// How many samples it takes for the output to go silent once all notes have been released.
+    /// This is the longest remaining amp release of the voices, plus the Haas delay. Soft notes can
+    /// take far longer than the release parameter since the velocity slows down their envelopes.
+    fn tail_samples(&self, sample_rate: f32) -> u32 {
+        let release_seconds = self
+            .voices
+            .iter()
+            .flatten()
+            .map(|voice| voice.amp_envelope.remaining_release())
+            .fold(0.0, f32::max);
+        let tail_seconds = release_seconds + self.params.haas_delay_ms.value() / 1000.0;
+
+        (tail_seconds * sample_rate).ceil() as u32
+    }
+
+    /// The voices a part can use. In multitimbral mode every part gets its own slice of the voice
+    /// pool so one part can't steal the other part's voices.
+    fn part_voices(&self, part: usize) -> Range<usize> {
+        let polyphony = self.params.polyphony.value() as usize;
+        if self.params.multitimbral.value() {
+            let voices_per_part = NUM_VOICES / NUM_PARTS;
+            let start = part * voices_per_part;
+            start..start + (polyphony / NUM_PARTS).clamp(1, voices_per_part)
+        } else {
+            0..polyphony.clamp(1, NUM_VOICES)
+        }
+    }
+
+    /// The total number of voices that can currently play at the same time, as reported to the
+    /// host.
+    fn voice_capacity(&self) -> u32 {
+        if self.params.multitimbral.value() {
+            (0..NUM_PARTS)
+                .map(|part| self.part_voices(part).len() as u32)
+                .sum()
+        } else {
+            self.part_voices(0).len() as u32
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn start_voice(
         &mut self,
-        context: &mut impl ProcessContext<Self>,
+        context: &mut impl HostContext,
+        part: usize,
         sample_offset: u32,
         voice_id: Option<i32>,
         channel: u8,
@@ -1082,11 +2544,12 @@ impl SubSynth {
         filter_res_envelope: ADSREnvelope,
         filter: FilterType,
     ) -> &mut Voice {
-        let (amp_envelope, filter_cut_envelope, filter_res_envelope) =
-            self.construct_envelopes(192000.0, velocity);
         let new_voice = Voice {
-            voice_id: voice_id.unwrap_or_else(|| compute_fallback_voice_id(note, channel)),
+            voice_id: voice_id
+                .unwrap_or_else(|| self.voice_allocator.next_fallback_voice_id(note, channel)),
             internal_voice_id: self.next_internal_voice_id,
+            part,
+            layer_gain: 1.0,
             channel,
             note,
             velocity,
@@ -1097,64 +2560,78 @@ impl SubSynth {
             expression,
             vibrato,
             tuning,
+            tuning_glide: Smoother::none(),
             phase: 0.0,
             phase_delta: 0.0,
-            releasing: false,
             amp_envelope,
             voice_gain: None,
+            voice_cutoff: None,
+            voice_resonance: None,
+            voice_pan: None,
             filter_cut_envelope,
             filter_res_envelope,
             filter: Some(filter),
             vib_mod,
             trem_mod,
+            glide: None,
+            supersaw: Supersaw::new(&mut self.prng),
+            organ: DrawbarOrgan::new(),
+            svf: MorphingSvf::new(),
+            tone_filter: OnePoleLowpass::new(),
+            dc_blocker: DCBlocker::new(),
+            pitch_envelope: self.construct_pitch_envelope(part),
+            mseg: Mseg::new(),
+            drift: Drift::new(&mut self.prng),
+            noise: Pcg32::new(self.prng.gen(), 1337),
+            onset_delay: 0,
+            drone: false,
+            placement: (
+                self.prng.gen_range(-1.0..1.0),
+                self.prng.gen_range(-1.0..1.0),
+            ),
+            previous_mod_offsets: ModOffsets::default(),
+            choke_fade: None,
         };
 
         self.next_internal_voice_id = self.next_internal_voice_id.wrapping_add(1);
 
-        if let Some(free_voice_idx) = self.voices.iter().position(|voice| voice.is_none()) {
-            let voice = &mut self.voices[free_voice_idx];
-            if voice.is_none() {
-                *voice = Some(new_voice);
-                let voice = voice.as_mut().unwrap();
-                voice.amp_envelope.set_envelope_stage(ADSREnvelopeState::Attack);
-                voice.filter_cut_envelope.set_envelope_stage(ADSREnvelopeState::Attack);
-                voice.filter_res_envelope.set_envelope_stage(ADSREnvelopeState::Attack);
-                voice.vib_mod.trigger();
-                voice.trem_mod.trigger();
-            }
-            voice.as_mut().unwrap()
+        // Drone voices are only stolen when there's nothing else left to steal. The voices playing
+        // the lowest held note come right before them when that note is protected.
+        let part_voices = self.part_voices(part);
+        let lowest_held_note = if self.params.protect_lowest_note.value() {
+            self.voices[part_voices.clone()]
+                .iter()
+                .flatten()
+                .filter(|voice| voice.is_held())
+                .map(|voice| voice.note)
+                .min()
         } else {
-            let oldest_voice = self
-                .voices
-                .iter_mut()
-                .min_by_key(|voice| voice.as_ref().unwrap().internal_voice_id)
-                .unwrap();
-            let oldest_voice = oldest_voice.as_mut().unwrap();
-    
-            if oldest_voice.amp_envelope.get_state() == ADSREnvelopeState::Idle ||
-                oldest_voice.amp_envelope.get_state() == ADSREnvelopeState::Release
-            {
-                // If the oldest voice's amp envelope is already idle or releasing, no need to send a voice terminated event
-                *oldest_voice = new_voice;
-                oldest_voice.amp_envelope.set_envelope_stage(ADSREnvelopeState::Attack);
-                oldest_voice.filter_cut_envelope.set_envelope_stage(ADSREnvelopeState::Attack);
-                oldest_voice.filter_res_envelope.set_envelope_stage(ADSREnvelopeState::Attack);
-                oldest_voice.releasing = false; // Reset the releasing flag
-                oldest_voice.vib_mod.trigger();
-                oldest_voice.trem_mod.trigger();
-            } else {
-                context.send_event(NoteEvent::VoiceTerminated {
-                    timing: sample_offset,
-                    voice_id: Some(oldest_voice.voice_id),
-                    channel: oldest_voice.channel,
-                    note: oldest_voice.note,
-                });
-    
-                *oldest_voice = new_voice;
-            }
-    
-            oldest_voice
+            None
+        };
+        let voice_reserve = self.params.voice_reserve.value() as usize;
+        let allocation = self.voice_allocator.allocate(
+            &self.voices,
+            part_voices,
+            voice_reserve,
+            |voice| !voice.drone && !voice.is_held(),
+            |voice| {
+                let protected = voice.is_held() && lowest_held_note == Some(voice.note);
+                (voice.drone, protected, voice.internal_voice_id)
+            },
+        );
+        let slot = &mut self.voices[allocation.slot()];
+        // The host needs to know a stolen voice is gone, even if it was already releasing.
+        // Otherwise hosts that track voice IDs keep the stolen voice around.
+        if let Some(stolen_voice) = slot {
+            context.send_event(NoteEvent::VoiceTerminated {
+                timing: sample_offset,
+                voice_id: Some(stolen_voice.voice_id),
+                channel: stolen_voice.channel,
+                note: stolen_voice.note,
+            });
         }
+
+        slot.insert(new_voice)
     }
 
     fn start_release_for_voices(
@@ -1164,185 +2641,254 @@ impl SubSynth {
         channel: u8,
         note: u8,
     ) {
-        for voice in &mut self.voices {
-            if let Some(voice) = voice {
-                if voice_id == Some(voice.voice_id) || (channel == voice.channel && note == voice.note) {
-                    voice.amp_envelope.set_envelope_stage(ADSREnvelopeState::Release);
-                    voice.filter_cut_envelope.set_envelope_stage(ADSREnvelopeState::Release);
-                    voice.filter_res_envelope.set_envelope_stage(ADSREnvelopeState::Release);
-                    //voice.amp_envelope.advance();
-                    //voice.filter_cut_envelope.advance();
-                    //voice.filter_res_envelope.advance();
+        let freeze_cutoff: [bool; NUM_PARTS] =
+            std::array::from_fn(|part| self.params.part(part).filter.release_freeze.value());
+        for voice in self.voices.iter_mut().flatten() {
+            if voice.drone {
+                continue;
+            }
+
+            if event_targets_voice(
+                voice_id,
+                channel,
+                note,
+                voice.voice_id,
+                voice.channel,
+                voice.note,
+            ) {
+                // The cutoff envelope's value needs to be captured before it enters the release
+                // stage
+                if freeze_cutoff[voice.part] {
+                    voice.filter_cut_envelope.freeze();
                 }
+                voice
+                    .amp_envelope
+                    .set_envelope_stage(ADSREnvelopeState::Release);
+                voice
+                    .filter_cut_envelope
+                    .set_envelope_stage(ADSREnvelopeState::Release);
+                voice
+                    .filter_res_envelope
+                    .set_envelope_stage(ADSREnvelopeState::Release);
+                voice.mseg.release();
             }
         }
     }
 
-    fn _find_voice(&mut self, voice_id: Option<i32>, channel: u8, note: u8) -> Option<&mut Voice> {
-        self.voices
-            .iter_mut()
-            .find(|voice| {
-                let voice_id = voice_id.clone(); // Clone the voice_id for comparison inside the closure
-                if let Some(voice) = voice {
-                    voice.voice_id == voice_id.unwrap_or(voice.voice_id)
-                        && voice.channel == channel
-                        && voice.note == note
-                } else {
-                    false
+    /// Turn all voices that are currently sounding into drone voices by locking their envelopes.
+    fn freeze_voices(&mut self) {
+        for voice in self.voices.iter_mut().flatten() {
+            if voice.amp_envelope.get_state() == ADSREnvelopeState::Idle {
+                continue;
+            }
+
+            voice.drone = true;
+            voice.amp_envelope.lock();
+            voice.filter_cut_envelope.lock();
+            voice.filter_res_envelope.lock();
+        }
+    }
+
+    /// Release all voices captured by freeze. Voices that were already releasing when they were
+    /// captured simply continue their release.
+    fn release_drone_voices(&mut self) {
+        for voice in self.voices.iter_mut().flatten() {
+            if !voice.drone {
+                continue;
+            }
+
+            voice.drone = false;
+            for envelope in [
+                &mut voice.amp_envelope,
+                &mut voice.filter_cut_envelope,
+                &mut voice.filter_res_envelope,
+            ] {
+                envelope.unlock();
+                if envelope.get_state() != ADSREnvelopeState::Release
+                    && envelope.get_state() != ADSREnvelopeState::Idle
+                {
+                    envelope.release();
                 }
-            })
-            .map(|voice| voice.as_mut().unwrap())
+            }
+            voice.mseg.release();
+        }
     }
 
-    fn compute_fallback_voice_id(note: u8, channel: u8, next_voice_id: i32) -> i32 {
-        // Fallback voice ID computation...
-        // Modify this function to generate a unique voice ID based on note, channel, and next_voice_id.
-        // Example implementation:
-        (note as i32) + (channel as i32) + next_voice_id
+    /// Fade out all voices in a choke group. Drone voices are left alone, and voices that are still
+    /// waiting for their strum onset are stopped right away since they haven't made a sound yet.
+    fn choke_group(&mut self, group: u8) {
+        for voice in self.voices.iter_mut().flatten() {
+            if voice.drone
+                || voice.choke_fade.is_some()
+                || self.params.part_choke_group(voice.part) != Some(group)
+            {
+                continue;
+            }
+
+            voice.choke_fade = Some(if voice.onset_delay > 0 { 0.0 } else { 1.0 });
+        }
     }
 
-    fn find_or_create_voice(
+    /// Whether latch mode is holding a note. Hosts can send channels past 16, so these wrap around
+    /// the same way as in [`Pedal`].
+    fn latched_note(&mut self, channel: u8, note: u8) -> &mut bool {
+        &mut self.latched_notes[channel as usize % 16][note as usize % 128]
+    }
+
+    /// Release all notes held by latch mode.
+    fn release_latched_notes(&mut self, sample_rate: f32) {
+        for channel in 0..self.latched_notes.len() {
+            for note in 0..self.latched_notes[channel].len() {
+                if std::mem::take(&mut self.latched_notes[channel][note]) {
+                    self.start_release_for_voices(sample_rate, None, channel as u8, note as u8);
+                }
+            }
+        }
+    }
+
+    /// The host's next event after the input transform. Events the transform drops, like notes
+    /// transposed out of range, are skipped.
+    fn next_host_event(
         &mut self,
-        voice_id: Option<i32>,
-        channel: u8,
-        note: u8,
-        pan: f32,
-        pressure:f32,
-        brightness: f32,
-        expression: f32,
-        tuning: f32,
-        vibrato: f32,
-        amp_envelope: ADSREnvelope,
-        filter_cut_envelope: ADSREnvelope,
-        filter_res_envelope: ADSREnvelope,
-        vib_mod: Modulator,
-        trem_mod: Modulator,
-    ) -> &mut Voice {
-        // Search for an existing voice with the given voice_id
-        if let Some(existing_index) = self.voices.iter().position(|voice| {
-            voice
-                .as_ref()
-                .map(|voice_ref| {
-                    voice_ref.voice_id == voice_id.unwrap_or(voice_ref.voice_id)
-                        && voice_ref.channel == channel
-                        && voice_ref.note == note
-                })
-                .unwrap_or(false)
-        }) {
-            return self.voices[existing_index].as_mut().unwrap();
+        context: &mut impl HostContext,
+        settings: &TransformSettings,
+    ) -> Option<PluginNoteEvent<Self>> {
+        while let Some(event) = context.next_event() {
+            if let Some(event) = self.input_transform.transform(settings, event) {
+                return Some(event);
+            }
         }
 
-        // If no existing voice found, create a new voice
-        let new_voice_id = voice_id.unwrap_or_else(|| {
-            // Generate a fallback voice ID
-            self.next_voice_index += 1;
-            Self::compute_fallback_voice_id(
-                note,
-                channel,
-                self.next_voice_index.try_into().unwrap(),
-            )
-        });
+        None
+    }
 
-        // If no existing voice found, create a new voice
-        let (amp_envelope, filter_cut_envelope, filter_res_envelope) =
-            self.construct_envelopes(192000.0, 1.0);
-        let mut new_voice = Voice {
-            voice_id: new_voice_id,
-            channel,
-            note,
-            internal_voice_id: self.next_internal_voice_id,
-            velocity: 0.0,
-            velocity_sqrt: 0.0,
-            phase: 0.0,
-            phase_delta: 0.0,
-            releasing: false,
-            amp_envelope,
-            voice_gain: None,
-            filter_cut_envelope,
-            filter_res_envelope,
-            filter: Some(self.params.filter_type.value()),
-            pan,
-            pressure,
-            brightness,
-            expression,
-            tuning,
-            vibrato,
-            vib_mod,
-            trem_mod,
-        };
-        new_voice.amp_envelope.trigger();
-        new_voice.filter_cut_envelope.trigger();
-        new_voice.filter_res_envelope.trigger();
-        new_voice.vib_mod.trigger();
-        new_voice.trem_mod.trigger();
-        // Find the next available slot for a new voice
-        let mut next_voice_index = self.next_voice_index;
-        while self.voices[next_voice_index].is_some() {
-            next_voice_index = (next_voice_index + 1) % NUM_VOICES;
-            if next_voice_index == self.next_voice_index {
-                panic!("No available slots for new voices");
+    /// Turn a threshold crossing from the input trigger into a note event for the note the trigger
+    /// plays.
+    fn input_trigger_event(&mut self, event: TriggerEvent, timing: u32) -> PluginNoteEvent<Self> {
+        match event {
+            TriggerEvent::NoteOn => {
+                let trigger_note = self.params.trigger.note.value() as u8;
+                let note = match self.params.trigger.note_mode.value() {
+                    TriggerNote::Fixed => trigger_note,
+                    TriggerNote::LastPlayed => self.parts[0].last_note.unwrap_or(trigger_note),
+                };
+                self.input_trigger.note = Some(note);
+
+                NoteEvent::NoteOn {
+                    timing,
+                    voice_id: None,
+                    channel: 0,
+                    note,
+                    velocity: 1.0,
+                }
             }
+            TriggerEvent::NoteOff => NoteEvent::NoteOff {
+                timing,
+                voice_id: None,
+                channel: 0,
+                note: self.input_trigger.note.take().unwrap_or_default(),
+                velocity: 0.0,
+            },
         }
+    }
 
-        // Store the new voice in the found slot
-        self.voices[next_voice_index] = Some(new_voice);
+    /// Spread out the onsets of the voices that were started at the start of this block, if there
+    /// are several of them. Every part strums its own chord.
+    fn strum_new_voices(&mut self, first_internal_voice_id: u64, sample_rate: f32) {
+        for part in 0..NUM_PARTS {
+            self.strum_part(part, first_internal_voice_id, sample_rate);
+        }
+    }
 
-        // Update the next available slot index
-        self.next_voice_index = next_voice_index;
+    fn strum_part(&mut self, part: usize, first_internal_voice_id: u64, sample_rate: f32) {
+        let strum_params = &self.params.part(part).strum;
+        let spacing_samples =
+            (self.morph.value(&strum_params.time_ms) / 1000.0 * sample_rate) as u32;
+        if spacing_samples == 0 {
+            return;
+        }
 
-        // Return a mutable reference to the newly created voice
-        self.voices[next_voice_index].as_mut().unwrap()
+        let mut chord = [(0, 0); NUM_VOICES];
+        let mut chord_len = 0;
+        for (voice_idx, voice) in self.voices.iter().enumerate() {
+            if let Some(voice) = voice {
+                if voice.part == part && voice.internal_voice_id >= first_internal_voice_id {
+                    chord[chord_len] = (voice_idx, voice.note);
+                    chord_len += 1;
+                }
+            }
+        }
+        if chord_len < 2 {
+            return;
+        }
 
+        let chord = &mut chord[..chord_len];
+        order_strum(chord, strum_params.mode.value(), &mut self.prng);
+        for (position, &(voice_idx, _)) in chord.iter().enumerate() {
+            if let Some(voice) = &mut self.voices[voice_idx] {
+                voice.onset_delay = position as u32 * spacing_samples;
+            }
+        }
     }
 
-    fn handle_poly_event(
+    /// Apply a per-voice expression to the voice it's meant for. Expressions for voices that
+    /// don't exist (anymore) are ignored.
+    fn apply_voice_expression(
         &mut self,
-        timing: u32,
         voice_id: Option<i32>,
         channel: u8,
         note: u8,
-        gain: f32,
-        pan: f32,
-        brightness: f32,
-        expression: f32,
-        tuning: f32,
-        pressure: f32,
-        vibrato: f32,
-        amp_envelope: Option<&ADSREnvelope>,
-        filter_cut_envelope: Option<&ADSREnvelope>,
-        filter_res_envelope: Option<&ADSREnvelope>,
-        vibrato_modulator: Option<&Modulator>,
-        tremolo_modulator: Option<&Modulator>,
+        expression: NoteExpression,
+        sample_rate: f32,
     ) {
-        let voice = self.find_or_create_voice(
-            voice_id,
-            channel,
-            note,
-            pan,
-            pressure,
-            brightness,
-            expression,
-            tuning,
-            vibrato,
-            amp_envelope.cloned().unwrap(),
-            filter_cut_envelope.cloned().unwrap(),
-            filter_res_envelope.cloned().unwrap(),
-            vibrato_modulator.cloned().unwrap(),
-            tremolo_modulator.cloned().unwrap(),
-        );
-        voice.velocity = gain;
-        voice.velocity_sqrt = gain.sqrt();
-        if let Some(amp_envelope) = amp_envelope {
-            voice.amp_envelope = amp_envelope.clone();
-            voice.amp_envelope.set_velocity(gain);
+        let expression_glide_ms = self.params.expression_glide_ms.value();
+        let voice = match self.find_voice_idx(voice_id, channel, note) {
+            Some(voice_idx) => match &mut self.voices[voice_idx] {
+                Some(voice) => voice,
+                None => return,
+            },
+            None => return,
+        };
+
+        match expression {
+            NoteExpression::Pressure(pressure) => voice.pressure = pressure,
+            NoteExpression::Volume(gain) => {
+                voice.velocity = gain;
+                voice.velocity_sqrt = gain.sqrt();
+                let amp_env_params = &self.params.part(voice.part).amp_env;
+                voice.amp_envelope.set_velocity(envelope_velocity(
+                    gain,
+                    amp_env_params.velocity_sensitivity.value(),
+                    amp_env_params.velocity_curve.value(),
+                ));
+            }
+            // The voice's pan is stored between 0 and 1
+            NoteExpression::Pan(pan) => voice.pan = (pan + 1.0) * 0.5,
+            NoteExpression::Tuning(tuning) if expression_glide_ms > 0.0 => {
+                // The render loop moves the voice's tuning and pitch towards the new value
+                voice.tuning_glide = Smoother::new(SmoothingStyle::Linear(expression_glide_ms));
+                voice.tuning_glide.reset(voice.tuning);
+                voice.tuning_glide.set_target(sample_rate, tuning);
+            }
+            NoteExpression::Tuning(tuning) => {
+                voice.tuning = tuning;
+                voice.tuning_glide = Smoother::none();
+                // Gliding voices pick up the new tuning on the next sample
+                if voice.glide.is_none() {
+                    voice.phase_delta = util::midi_note_to_freq(voice.note)
+                        * (2.0_f32).powf(tuning / 12.0)
+                        / sample_rate;
+                }
+            }
+            NoteExpression::Vibrato(vibrato) => voice.vibrato = vibrato,
+            NoteExpression::Expression(expression) => voice.expression = expression,
+            NoteExpression::Brightness(brightness) => voice.brightness = brightness,
         }
     }
-    
-    
 
     fn choke_voices(
         &mut self,
-        context: &mut impl ProcessContext<Self>,
+        context: &mut impl HostContext,
         sample_offset: u32,
         voice_id: Option<i32>,
         channel: u8,
@@ -1350,19 +2896,21 @@ impl SubSynth {
     ) {
         for voice in self.voices.iter_mut() {
             match voice {
-                Some(Voice {
-                    voice_id: candidate_voice_id,
-                    channel: candidate_channel,
-                    note: candidate_note,
-                    ..
-                }) if voice_id == Some(*candidate_voice_id)
-                    || (channel == *candidate_channel && note == *candidate_note) =>
+                Some(candidate)
+                    if event_targets_voice(
+                        voice_id,
+                        channel,
+                        note,
+                        candidate.voice_id,
+                        candidate.channel,
+                        candidate.note,
+                    ) =>
                 {
                     context.send_event(NoteEvent::VoiceTerminated {
                         timing: sample_offset,
-                        voice_id: Some(*candidate_voice_id),
-                        channel,
-                        note,
+                        voice_id: Some(candidate.voice_id),
+                        channel: candidate.channel,
+                        note: candidate.note,
                     });
                     *voice = None;
 
@@ -1374,31 +2922,51 @@ impl SubSynth {
             }
         }
     }
-    pub fn clip(input: f32, limit: f32) -> f32 {
-        if input > limit {
-            limit
-        } else if input < -limit {
-            -limit
-        } else {
-            input
-        }
+}
+
+/// Where an event falls relative to the block that runs from `block_start` up to `block_end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EventPosition {
+    /// The event happens at the start of the block, or it's late, so it's handled right away.
+    Now,
+    /// The event happens during the block, so the block should end at this sample.
+    SplitAt(usize),
+    /// The event happens at or after the end of the block.
+    Later,
+}
+
+fn event_position(timing: u32, block_start: usize, block_end: usize) -> EventPosition {
+    let timing = timing as usize;
+    if timing <= block_start {
+        EventPosition::Now
+    } else if timing < block_end {
+        EventPosition::SplitAt(timing)
+    } else {
+        EventPosition::Later
     }
-    pub fn poly_blep(t: f32, dt: f32) -> f32 {
-        if t < dt {
-            let t = t / dt;
-            // 2 * (t - t^2/2 - 0.5)
-            return t + t - t * t - 1.0;
-        } else if t > 1.0 - dt {
-            let t = (t - 1.0) / dt;
-            // 2 * (t^2/2 + t + 0.5)
-            return t * t + t + t + 1.0;
-        }
-        0.0
+}
+
+/// Fill `values` with a voice's polyphonically modulated parameter values, or copy the parameter's
+/// global smoothed values if the voice isn't being modulated.
+fn poly_modulated_block(
+    modulation: &Option<(f32, Smoother<f32>)>,
+    global_values: &[f32],
+    values: &mut [f32],
+    block_len: usize,
+) {
+    match modulation {
+        Some((_, smoother)) => smoother.next_block(values, block_len),
+        None => values[..block_len].copy_from_slice(&global_values[..block_len]),
     }
 }
 
-const fn compute_fallback_voice_id(note: u8, channel: u8) -> i32 {
-    note as i32 | ((channel as i32) << 16)
+/// Whether a part listening to `part_channel` should respond to an event on `event_channel`. Either
+/// of them being `None` matches every channel.
+fn accepts_channel(part_channel: Option<u8>, event_channel: Option<u8>) -> bool {
+    match (part_channel, event_channel) {
+        (Some(part_channel), Some(event_channel)) => part_channel == event_channel,
+        _ => true,
+    }
 }
 
 impl ClapPlugin for SubSynth {
@@ -1429,3 +2997,576 @@ impl Vst3Plugin for SubSynth {
 
 nih_export_clap!(SubSynth);
 nih_export_vst3!(SubSynth);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use std::collections::{HashMap, HashSet};
+    use subsynth_dsp::modulator::SyncDivision;
+    use test_host::TestHost;
+
+    /// The sample rate the tests run `process()` at. This is kept low so the tests run quickly.
+    const TEST_SAMPLE_RATE: f32 = 22_050.0;
+    const TEST_MAX_BUFFER_SIZE: usize = 128;
+    /// The number of steps a parameter is swept in, at one step per buffer.
+    const SWEEP_STEPS: usize = 32;
+    /// The loudest the parameter sweeps are allowed to get, twice full scale. The sweeps stay below
+    /// full scale with a handful of voices, but unstable filters easily blow past this.
+    const MAX_SWEEP_LEVEL: f32 = 2.0;
+
+    /// Split a buffer into blocks the same way `process()` does, returning every block's start and
+    /// end along with the timings of the events handled at its start.
+    fn split_blocks(
+        num_samples: usize,
+        max_block_len: usize,
+        timings: &[u32],
+    ) -> Vec<(usize, usize, Vec<u32>)> {
+        let mut events = timings.iter().copied().peekable();
+        let mut blocks = Vec::new();
+        let mut block_start = 0;
+        let mut block_end = max_block_len.min(num_samples);
+        while block_start < num_samples {
+            let mut handled = Vec::new();
+            while let Some(&timing) = events.peek() {
+                match event_position(timing, block_start, block_end) {
+                    EventPosition::Now => {
+                        handled.push(timing);
+                        events.next();
+                    }
+                    EventPosition::SplitAt(timing) => {
+                        block_end = timing;
+                        break;
+                    }
+                    EventPosition::Later => break,
+                }
+            }
+
+            blocks.push((block_start, block_end, handled));
+            block_start = block_end;
+            block_end = (block_start + max_block_len).min(num_samples);
+        }
+
+        blocks
+    }
+
+    #[test]
+    fn test_event_position() {
+        assert_eq!(event_position(0, 0, 64), EventPosition::Now);
+        assert_eq!(event_position(10, 16, 64), EventPosition::Now);
+        assert_eq!(event_position(17, 16, 64), EventPosition::SplitAt(17));
+        assert_eq!(event_position(63, 16, 64), EventPosition::SplitAt(63));
+        assert_eq!(event_position(64, 16, 64), EventPosition::Later);
+    }
+
+    #[test]
+    fn test_mid_block_note_ons_split_blocks() {
+        // Two notes at the same sample start a single block, and every block still respects the
+        // maximum block length
+        assert_eq!(
+            split_blocks(200, 64, &[0, 10, 10, 40, 150]),
+            vec![
+                (0, 10, vec![0]),
+                (10, 40, vec![10, 10]),
+                (40, 104, vec![40]),
+                (104, 150, vec![]),
+                (150, 200, vec![150]),
+            ]
+        );
+
+        // Every note starts exactly at the start of its block
+        let timings = [3, 17, 18, 63, 64, 65, 127];
+        let blocks = split_blocks(128, 64, &timings);
+        for (block_start, block_end, handled) in &blocks {
+            assert!(block_start < block_end && block_end - block_start <= 64);
+            assert!(handled.iter().all(|&timing| timing as usize == *block_start));
+        }
+        let num_handled: usize = blocks.iter().map(|(_, _, handled)| handled.len()).sum();
+        assert_eq!(num_handled, timings.len());
+    }
+
+    #[test]
+    fn test_latch_on_out_of_range_channels() {
+        // CLAP hosts can send notes on channels past the 16 MIDI channels
+        let mut host = TestHost::new(TEST_SAMPLE_RATE, TEST_MAX_BUFFER_SIZE as u32);
+        let params = host.plugin.params.clone();
+        host.set_parameter(&params.latch, true);
+        let note_on = NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel: 255,
+            note: 60,
+            velocity: 1.0,
+        };
+        let note_off = NoteEvent::NoteOff {
+            timing: 0,
+            voice_id: None,
+            channel: 255,
+            note: 60,
+            velocity: 0.0,
+        };
+
+        host.process(1, [note_on, note_off]);
+        render(&mut host, TEST_SAMPLE_RATE as usize * 3);
+        assert!(!host.active_voice_ids().is_empty());
+
+        // Pressing the note again releases it
+        host.process(1, [note_on]);
+        render(&mut host, TEST_SAMPLE_RATE as usize * 3);
+        assert_eq!(host.active_voice_ids(), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn test_voice_id_events_target_one_voice() {
+        // Hosts like Bitwig can play the same note twice with different voice IDs. A note off or a
+        // choke for one of those voices must leave the other voice playing.
+        let mut host = TestHost::new(TEST_SAMPLE_RATE, TEST_MAX_BUFFER_SIZE as u32);
+        let note_on = |voice_id| NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: Some(voice_id),
+            channel: 0,
+            note: 60,
+            velocity: 1.0,
+        };
+
+        host.process(1, [note_on(1), note_on(2)]);
+        let mut voice_ids = host.active_voice_ids();
+        voice_ids.sort_unstable();
+        assert_eq!(voice_ids, vec![1, 2]);
+
+        host.process(
+            1,
+            [NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: Some(1),
+                channel: 0,
+                note: 60,
+                velocity: 0.0,
+            }],
+        );
+        render(&mut host, TEST_SAMPLE_RATE as usize * 3);
+        assert_eq!(host.active_voice_ids(), vec![2]);
+
+        // The choke's channel and note don't matter when it has a voice ID, and the terminated
+        // voice is reported with its own note
+        host.process(1, [note_on(3)]);
+        host.output_events.clear();
+        host.process(
+            1,
+            [NoteEvent::Choke {
+                timing: 0,
+                voice_id: Some(3),
+                channel: 1,
+                note: 61,
+            }],
+        );
+        assert_eq!(host.active_voice_ids(), vec![2]);
+        assert!(matches!(
+            host.output_events[..],
+            [NoteEvent::VoiceTerminated {
+                voice_id: Some(3),
+                channel: 0,
+                note: 60,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_tail_covers_slow_releases() {
+        // Soft notes release a lot slower than the release parameter is set to, and the host needs
+        // to keep processing until they're done
+        let mut host = TestHost::new(TEST_SAMPLE_RATE, TEST_MAX_BUFFER_SIZE as u32);
+        host.process(
+            1,
+            [NoteEvent::NoteOn {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 60,
+                velocity: 0.25,
+            }],
+        );
+        render(&mut host, TEST_SAMPLE_RATE as usize / 10);
+        host.process(
+            1,
+            [NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 60,
+                velocity: 0.0,
+            }],
+        );
+
+        let tail_samples = host.plugin.tail_samples(TEST_SAMPLE_RATE) as usize;
+        let release_ms = host.plugin.params.part_a.amp_env.release_ms.value();
+        let release_samples = (release_ms / 1000.0 * TEST_SAMPLE_RATE) as usize;
+        assert!(
+            tail_samples > release_samples * 2,
+            "{tail_samples} sample tail for a {release_samples} sample release"
+        );
+        render(&mut host, tail_samples / 2);
+        assert_eq!(host.active_voice_ids().len(), 1);
+        render(&mut host, tail_samples - tail_samples / 2);
+        assert!(host.active_voice_ids().is_empty());
+    }
+
+    /// Render `num_samples` samples without any events. The buffer sizes vary, like they do with
+    /// some hosts.
+    fn render(host: &mut TestHost, num_samples: usize) {
+        let mut buffer_sizes = [TEST_MAX_BUFFER_SIZE, 1, 37, 64, 100].into_iter().cycle();
+        let mut remaining = num_samples;
+        while remaining > 0 {
+            let num_samples = buffer_sizes.next().unwrap().min(remaining);
+            host.process(num_samples, []);
+            remaining -= num_samples;
+        }
+    }
+
+    /// A playing host with part A's vibrato synced to quarter notes, with or without syncing the
+    /// LFOs to the transport.
+    fn tempo_synced_host(tempo: Option<f64>, lfo_transport_sync: bool) -> TestHost {
+        let mut host = TestHost::new(TEST_SAMPLE_RATE, TEST_MAX_BUFFER_SIZE as u32);
+        let params = host.plugin.params.clone();
+        host.set_parameter(&params.lfo_transport_sync, lfo_transport_sync);
+        host.set_parameter(&params.part_a.vibrato.sync, SyncDivision::Quarter);
+        host.transport.playing = true;
+        host.transport.tempo = tempo;
+
+        host
+    }
+
+    /// Check that part A's vibrato LFO is where a quarter note synced LFO would be at the
+    /// transport's current position, if it had been running since the start of the song.
+    fn assert_vibrato_phase(host: &TestHost, tempo: f64) {
+        let pos_seconds = host.transport.pos_samples as f64 / TEST_SAMPLE_RATE as f64;
+        let expected = (tempo / 60.0 * pos_seconds).fract() as f32;
+        let phase = host.plugin.parts[0].global_vibrato_lfo.phase();
+        // The phase wraps around, so 0.999 and 0.001 are close together
+        let difference = (phase - expected).abs();
+        assert!(
+            difference.min(1.0 - difference) < 1e-3,
+            "expected a phase of {expected} at sample {}, got {phase}",
+            host.transport.pos_samples
+        );
+    }
+
+    #[test]
+    fn test_tempo_synced_lfo() {
+        for tempo in [Some(90.0), Some(174.0), None] {
+            let mut host = tempo_synced_host(tempo, true);
+            render(&mut host, TEST_SAMPLE_RATE as usize * 2);
+
+            // Without a tempo from the host, the internal tempo is used
+            let tempo = tempo.unwrap_or(host.plugin.params.internal_tempo.value() as f64);
+            assert_vibrato_phase(&host, tempo);
+        }
+    }
+
+    #[test]
+    fn test_lfo_transport_sync_on_jumps() {
+        let mut host = tempo_synced_host(Some(100.0), true);
+        render(&mut host, 10_000);
+        assert_vibrato_phase(&host, 100.0);
+
+        // Jumping forwards and backwards picks up the phase at the new position
+        host.transport.pos_samples = 123_456;
+        render(&mut host, 500);
+        assert_vibrato_phase(&host, 100.0);
+        host.transport.pos_samples = 777;
+        render(&mut host, 500);
+        assert_vibrato_phase(&host, 100.0);
+
+        // And so does looping, at an end that doesn't line up with the buffers
+        host.transport.pos_samples = 0;
+        host.transport.loop_range = Some((5_000, 12_345));
+        render(&mut host, 20_000);
+        assert!(host.transport.pos_samples < 12_345);
+        assert_vibrato_phase(&host, 100.0);
+
+        // Starting the transport somewhere in the song also counts as a jump
+        host.transport.playing = false;
+        render(&mut host, 1_000);
+        host.transport.playing = true;
+        host.transport.loop_range = None;
+        host.transport.pos_samples = 54_321;
+        render(&mut host, 1_000);
+        assert_vibrato_phase(&host, 100.0);
+    }
+
+    #[test]
+    fn test_lfo_ignores_jumps_without_transport_sync() {
+        let mut host = tempo_synced_host(Some(100.0), false);
+        render(&mut host, 10_000);
+        let phase = host.plugin.parts[0].global_vibrato_lfo.phase();
+
+        // The LFO keeps running like nothing happened
+        host.transport.pos_samples = 0;
+        render(&mut host, 1);
+        let expected = (phase + 100.0 / 60.0 / TEST_SAMPLE_RATE).fract();
+        let new_phase = host.plugin.parts[0].global_vibrato_lfo.phase();
+        assert!((new_phase - expected).abs() < 1e-6);
+    }
+
+    /// Sweep every parameter from its minimum to its maximum while a few notes are playing, and
+    /// check that the output stays finite and within [`MAX_SWEEP_LEVEL`]. The filter's parameters
+    /// mean something different for every filter type, so those are swept with each of them, and
+    /// with the resonance all the way up where the filters are the least stable. The others are
+    /// swept with the default patch's filter.
+    #[test]
+    fn test_parameter_sweeps() {
+        let mut host = TestHost::new(TEST_SAMPLE_RATE, TEST_MAX_BUFFER_SIZE as u32);
+        let params = host.plugin.params.clone();
+        let filter_types: Vec<FilterType> = (0..FilterType::variants().len())
+            .map(FilterType::from_index)
+            .collect();
+        for (id, param_ptr, _) in params.param_map() {
+            let (filter_types, resonance) = if id.contains("filter") {
+                (&filter_types[..], 1.0)
+            } else {
+                (&[FilterType::None][..], params.part_a.filter.resonance.default_plain_value())
+            };
+            for &filter_type in filter_types {
+                host.set_parameter(&params.part_a.filter.filter_type, filter_type);
+                host.set_parameter(&params.part_b.filter.filter_type, filter_type);
+                host.set_parameter(&params.part_a.filter.resonance, resonance);
+                host.set_parameter(&params.part_b.filter.resonance, resonance);
+                host.reset();
+                let notes = [36, 60, 67, 96].map(|note| NoteEvent::NoteOn {
+                    timing: 0,
+                    voice_id: None,
+                    channel: 0,
+                    note,
+                    velocity: 1.0,
+                });
+                host.process(TEST_MAX_BUFFER_SIZE, notes);
+
+                // The last value is held for a while so the smoothers can catch up
+                let steps = (0..=SWEEP_STEPS).chain([SWEEP_STEPS; SWEEP_STEPS]);
+                for step in steps {
+                    let normalized = step as f32 / SWEEP_STEPS as f32;
+                    host.set_normalized_parameter(param_ptr, normalized);
+                    let output = host.process(TEST_MAX_BUFFER_SIZE / 2, []);
+                    let peak = output.iter().flatten().fold(0.0f32, |peak, sample| {
+                        assert!(sample.is_finite(), "{id} at {normalized} ({filter_type:?})");
+                        peak.max(sample.abs())
+                    });
+                    assert!(
+                        peak <= MAX_SWEEP_LEVEL,
+                        "{id} at {normalized} ({filter_type:?}) peaked at {peak}"
+                    );
+                }
+
+                host.set_normalized_parameter(param_ptr, unsafe {
+                    param_ptr.default_normalized_value()
+                });
+            }
+        }
+    }
+
+    /// A random note event. Only a handful of notes, channels and voice IDs are used so the events
+    /// often collide: duplicate notes, note offs for other voices, and voice IDs that don't match
+    /// the note.
+    fn note_event() -> impl Strategy<Value = PluginNoteEvent<SubSynth>> {
+        let voice_id = proptest::option::of(0..4);
+        let channel = 0u8..2;
+        let note = 58u8..62;
+        prop_oneof![
+            (voice_id.clone(), channel.clone(), note.clone(), 0.0f32..=1.0).prop_map(
+                |(voice_id, channel, note, velocity)| NoteEvent::NoteOn {
+                    timing: 0,
+                    voice_id,
+                    channel,
+                    note,
+                    velocity,
+                }
+            ),
+            (voice_id.clone(), channel.clone(), note.clone()).prop_map(
+                |(voice_id, channel, note)| NoteEvent::NoteOff {
+                    timing: 0,
+                    voice_id,
+                    channel,
+                    note,
+                    velocity: 0.0,
+                }
+            ),
+            (voice_id.clone(), channel.clone(), note.clone()).prop_map(
+                |(voice_id, channel, note)| NoteEvent::Choke {
+                    timing: 0,
+                    voice_id,
+                    channel,
+                    note,
+                }
+            ),
+            (0..4, 0u32..(POLY_MOD_IDS_PER_PART * NUM_PARTS as u32), -1.0f32..=1.0).prop_map(
+                |(voice_id, poly_modulation_id, normalized_offset)| NoteEvent::PolyModulation {
+                    timing: 0,
+                    voice_id,
+                    poly_modulation_id,
+                    normalized_offset,
+                }
+            ),
+            (voice_id, channel, note, 0.0f32..=1.0).prop_map(
+                |(voice_id, channel, note, pressure)| NoteEvent::PolyPressure {
+                    timing: 0,
+                    voice_id,
+                    channel,
+                    note,
+                    pressure,
+                }
+            ),
+        ]
+    }
+
+    /// A buffer's length along with the events that happen during it, sorted by their timing.
+    fn buffer_with_events() -> impl Strategy<Value = (usize, Vec<PluginNoteEvent<SubSynth>>)> {
+        (1..=TEST_MAX_BUFFER_SIZE, vec((0..TEST_MAX_BUFFER_SIZE, note_event()), 0..6)).prop_map(
+            |(num_samples, events)| {
+                let mut events: Vec<_> = events
+                    .into_iter()
+                    .map(|(timing, mut event)| {
+                        set_timing(&mut event, (timing % num_samples) as u32);
+                        event
+                    })
+                    .collect();
+                events.sort_by_key(|event| event.timing());
+
+                (num_samples, events)
+            },
+        )
+    }
+
+    fn set_timing(event: &mut PluginNoteEvent<SubSynth>, new_timing: u32) {
+        match event {
+            NoteEvent::NoteOn { timing, .. }
+            | NoteEvent::NoteOff { timing, .. }
+            | NoteEvent::Choke { timing, .. }
+            | NoteEvent::PolyModulation { timing, .. }
+            | NoteEvent::PolyPressure { timing, .. } => *timing = new_timing,
+            _ => unreachable!(),
+        }
+    }
+
+    /// A buffer that starts more held notes than there are voices, so the following events run
+    /// into voice stealing. The voice IDs repeat, like the ones from [`note_event()`].
+    fn voice_pool_overflow() -> (usize, Vec<PluginNoteEvent<SubSynth>>) {
+        let events = (0..NUM_VOICES + 4)
+            .map(|idx| NoteEvent::NoteOn {
+                timing: 0,
+                voice_id: Some(idx as i32 % 4),
+                channel: 0,
+                note: 40 + idx as u8,
+                velocity: 1.0,
+            })
+            .collect();
+
+        (1, events)
+    }
+
+    /// Run the events through a new plugin instance and check that they never cause a panic or a
+    /// non-finite output, that every voice eventually stops once all notes have been released,
+    /// and that every voice that stopped was reported to the host exactly once.
+    fn check_random_events(
+        buffers: Vec<(usize, Vec<PluginNoteEvent<SubSynth>>)>,
+    ) -> Result<(), TestCaseError> {
+        let mut host = TestHost::new(TEST_SAMPLE_RATE, TEST_MAX_BUFFER_SIZE as u32);
+        // The softest notes would otherwise take 127 times as long to release
+        let params = host.plugin.params.clone();
+        for part in 0..NUM_PARTS {
+            host.set_parameter(&params.part(part).amp_env.velocity_sensitivity, 0.0);
+        }
+
+        // The host voice IDs repeat, so the voices are told apart by their internal IDs. Voices
+        // that are started and stolen within the same buffer are only counted in total.
+        let mut played_notes = HashSet::new();
+        let mut played_voices = HashMap::new();
+        let mut record_voices = |host: &TestHost| {
+            for voice in host.plugin.voices.iter().flatten() {
+                played_voices.insert(
+                    voice.internal_voice_id,
+                    (voice.voice_id, voice.channel, voice.note),
+                );
+            }
+        };
+        for (num_samples, events) in buffers {
+            for event in &events {
+                if let NoteEvent::NoteOn { channel, note, .. } = *event {
+                    played_notes.insert((channel, note));
+                }
+            }
+
+            let output = host.process(num_samples, events);
+            prop_assert!(output.iter().flatten().all(|sample| sample.is_finite()));
+            record_voices(&host);
+        }
+
+        // Note offs without a voice ID release every voice playing the note, after which even the
+        // slowest release has to finish within a couple of seconds
+        let note_offs = played_notes
+            .iter()
+            .map(|&(channel, note)| NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel,
+                note,
+                velocity: 0.0,
+            });
+        host.process(1, note_offs);
+        record_voices(&host);
+        for _ in 0..(TEST_SAMPLE_RATE as usize * 3 / TEST_MAX_BUFFER_SIZE) {
+            host.process(TEST_MAX_BUFFER_SIZE, []);
+        }
+        prop_assert_eq!(host.active_voice_ids(), Vec::<i32>::new());
+
+        let mut num_terminated = HashMap::new();
+        for event in &host.output_events {
+            if let NoteEvent::VoiceTerminated {
+                voice_id: Some(voice_id),
+                channel,
+                note,
+                ..
+            } = *event
+            {
+                *num_terminated.entry((voice_id, channel, note)).or_insert(0) += 1;
+            }
+        }
+        let mut num_played = HashMap::new();
+        for &voice in played_voices.values() {
+            *num_played.entry(voice).or_insert(0) += 1;
+        }
+        for (voice, &num_played) in &num_played {
+            let num_terminated = num_terminated.get(voice).copied().unwrap_or(0);
+            prop_assert!(
+                num_terminated >= num_played,
+                "{num_played} voices with ID, channel, and note {voice:?} were played, but only \
+                 {num_terminated} were terminated"
+            );
+        }
+        prop_assert_eq!(
+            num_terminated.values().sum::<u64>(),
+            host.plugin.next_internal_voice_id,
+            "every started voice needs to be terminated exactly once"
+        );
+
+        Ok(())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn test_random_events(buffers in vec(buffer_with_events(), 1..16)) {
+            check_random_events(buffers)?;
+        }
+
+        #[test]
+        fn test_random_events_with_voice_stealing(
+            mut buffers in vec(buffer_with_events(), 1..16)
+        ) {
+            buffers.insert(0, voice_pool_overflow());
+            check_random_events(buffers)?;
+        }
+    }
+}
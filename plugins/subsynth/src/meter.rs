@@ -0,0 +1,178 @@
+//! The output meter. The audio thread runs the meter's ballistics and stores its readings in a
+//! [`MeterDisplay`], which the editor reads from and resets the peak hold of.
+
+use atomic_float::AtomicF32;
+use enum_iterator::Sequence;
+use nih_plug::prelude::{util, Enum};
+use std::sync::atomic::Ordering;
+
+/// What the output meter shows.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum MeterMode {
+    /// Jumps to every peak immediately and then falls back.
+    Peak,
+    /// The average power, which follows perceived loudness more closely than the peaks.
+    #[name = "RMS"]
+    Rms,
+}
+
+/// How quickly the output meter falls back after the signal gets quieter.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum MeterSpeed {
+    Fast,
+    Slow,
+}
+
+impl MeterSpeed {
+    /// The time it takes for the meter to drop by 12 dB after switching to complete silence.
+    fn decay_ms(&self) -> f32 {
+        match self {
+            MeterSpeed::Fast => 150.0,
+            MeterSpeed::Slow => 600.0,
+        }
+    }
+}
+
+/// The meter's ballistics, run on the audio thread.
+#[derive(Debug, Clone, Copy)]
+pub struct OutputMeter {
+    sample_rate: f32,
+    mode: MeterMode,
+    speed: MeterSpeed,
+    /// The weight of the previous value for every new sample.
+    weight: f32,
+    /// The peak level as voltage gain, or the mean square for the RMS meter.
+    value: f32,
+}
+
+impl OutputMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut meter = Self {
+            sample_rate,
+            mode: MeterMode::Peak,
+            speed: MeterSpeed::Fast,
+            weight: 0.0,
+            value: 0.0,
+        };
+        meter.update_weight();
+
+        meter
+    }
+
+    pub fn reset(&mut self) {
+        self.value = 0.0;
+    }
+
+    /// Process the next sample's amplitude and return the meter's level as voltage gain.
+    pub fn process(&mut self, amplitude: f32, mode: MeterMode, speed: MeterSpeed) -> f32 {
+        if mode != self.mode || speed != self.speed {
+            // The peak and RMS meters store different things, so switching modes starts over
+            if mode != self.mode {
+                self.value = 0.0;
+            }
+            self.mode = mode;
+            self.speed = speed;
+            self.update_weight();
+        }
+
+        match self.mode {
+            MeterMode::Peak => {
+                if amplitude > self.value {
+                    self.value = amplitude;
+                } else {
+                    self.value = self.value * self.weight + amplitude * (1.0 - self.weight);
+                }
+
+                self.value
+            }
+            MeterMode::Rms => {
+                self.value = self.value * self.weight + amplitude * amplitude * (1.0 - self.weight);
+
+                self.value.sqrt()
+            }
+        }
+    }
+
+    fn update_weight(&mut self) {
+        // 12 dB is a quarter of the peak level, and a sixteenth of the mean square
+        let decay = match self.mode {
+            MeterMode::Peak => 0.25f32,
+            MeterMode::Rms => 0.0625f32,
+        };
+        self.weight = decay.powf((self.sample_rate * self.speed.decay_ms() / 1000.0).recip());
+    }
+}
+
+/// The meter's readings, as voltage gain. The audio thread writes these and the editor reads them.
+#[derive(Debug, Default)]
+pub struct MeterDisplay {
+    level: AtomicF32,
+    /// The loudest sample since the peak hold was last reset.
+    peak_hold: AtomicF32,
+}
+
+impl MeterDisplay {
+    /// Store the meter's level along with the current sample's peak.
+    pub fn update(&self, level: f32, peak: f32) {
+        self.level.store(level, Ordering::Relaxed);
+        self.peak_hold.fetch_max(peak, Ordering::Relaxed);
+    }
+
+    pub fn level_db(&self) -> f32 {
+        util::gain_to_db(self.level.load(Ordering::Relaxed))
+    }
+
+    pub fn reset_peak_hold(&self) {
+        self.peak_hold.store(0.0, Ordering::Relaxed);
+    }
+
+    /// The peak hold in decibels, for the editor's readout.
+    pub fn peak_hold_readout(&self) -> String {
+        let peak_hold = self.peak_hold.load(Ordering::Relaxed);
+        if peak_hold > util::db_to_gain(util::MINUS_INFINITY_DB) {
+            format!("Peak {:+.1} dB", util::gain_to_db(peak_hold))
+        } else {
+            String::from("Peak -inf dB")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ballistics() {
+        let sample_rate = 1000.0;
+        for mode in [MeterMode::Peak, MeterMode::Rms] {
+            for speed in [MeterSpeed::Fast, MeterSpeed::Slow] {
+                let mut meter = OutputMeter::new(sample_rate);
+                let mut level = 0.0;
+                for _ in 0..(sample_rate * 4.0) as usize {
+                    level = meter.process(0.5, mode, speed);
+                }
+                assert!((level - 0.5).abs() < 1e-3, "{mode:?} {speed:?}: {level}");
+
+                // After the decay time of silence the meter should be 12 dB lower
+                for _ in 0..(speed.decay_ms() * sample_rate / 1000.0) as usize {
+                    level = meter.process(0.0, mode, speed);
+                }
+                assert!(
+                    (util::gain_to_db(level) - util::gain_to_db(0.5 * 0.25)).abs() < 0.1,
+                    "{mode:?} {speed:?}: {level}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_peak_hold() {
+        let display = MeterDisplay::default();
+        assert_eq!(display.peak_hold_readout(), "Peak -inf dB");
+        display.update(0.1, 0.5);
+        display.update(0.1, 0.25);
+        assert_eq!(display.peak_hold_readout(), "Peak -6.0 dB");
+        display.reset_peak_hold();
+        assert_eq!(display.peak_hold_readout(), "Peak -inf dB");
+    }
+}
@@ -0,0 +1,563 @@
+//! MIDI and note expression input. [`MidiInput`] keeps track of the controller state for every MIDI
+//! channel, and turns the host's note events into the [`VoiceEvent`]s the synth acts on. Events
+//! that only change the controller state are handled here completely.
+
+use nih_plug::prelude::*;
+
+use crate::cc_map::{CcValues, SUSTAIN_PEDAL_CC};
+
+/// How far the pitch wheel bends the pitch at either end, in semitones.
+pub const PITCH_BEND_RANGE_SEMITONES: f32 = 2.0;
+/// The sustain pedal counts as pressed from this value on, the same as with a MIDI value of 64.
+const PEDAL_DOWN_THRESHOLD: f32 = 0.5;
+
+/// A per-voice expression, with the value from the host's expression event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteExpression {
+    /// Polyphonic key pressure, between 0 and 1.
+    Pressure(f32),
+    /// A gain ratio, where 1 is unity gain.
+    Volume(f32),
+    /// Between -1 for hard left and 1 for hard right.
+    Pan(f32),
+    /// In semitones.
+    Tuning(f32),
+    /// Between 0 and 1.
+    Vibrato(f32),
+    /// Between 0 and 1.
+    Expression(f32),
+    /// Between 0 and 1.
+    Brightness(f32),
+}
+
+/// The events that need to be applied to the voices.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoiceEvent {
+    NoteOn {
+        timing: u32,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+        velocity: f32,
+    },
+    NoteOff {
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+    },
+    Choke {
+        timing: u32,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+    },
+    PolyModulation {
+        voice_id: i32,
+        poly_modulation_id: u32,
+        normalized_offset: f32,
+    },
+    MonoAutomation {
+        poly_modulation_id: u32,
+        normalized_value: f32,
+    },
+    /// An expression for a single voice. Like the other events, this targets the voice with the
+    /// voice ID, or every voice playing the note on the channel if there is no voice ID.
+    Expression {
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+        expression: NoteExpression,
+    },
+    /// The sustain pedal was pressed or lifted on a channel.
+    Pedal { channel: u8, down: bool },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MidiInput {
+    cc_values: CcValues,
+    /// The pitch wheel's position for every channel, between -1 and 1.
+    pitch_bend: [f32; 16],
+    /// The channel pressure for every channel, between 0 and 1.
+    channel_pressure: [f32; 16],
+}
+
+impl Default for MidiInput {
+    fn default() -> Self {
+        Self {
+            cc_values: CcValues::default(),
+            pitch_bend: [0.0; 16],
+            channel_pressure: [0.0; 16],
+        }
+    }
+}
+
+impl MidiInput {
+    /// Update the controller state from an event. Returns the event the voices need to respond to,
+    /// if any.
+    pub fn handle<S>(&mut self, event: &NoteEvent<S>) -> Option<VoiceEvent> {
+        let expression = |voice_id, channel, note, expression| {
+            Some(VoiceEvent::Expression {
+                voice_id,
+                channel,
+                note,
+                expression,
+            })
+        };
+
+        match *event {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity,
+            } => Some(VoiceEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity,
+            }),
+            NoteEvent::NoteOff {
+                voice_id,
+                channel,
+                note,
+                ..
+            } => Some(VoiceEvent::NoteOff {
+                voice_id,
+                channel,
+                note,
+            }),
+            NoteEvent::Choke {
+                timing,
+                voice_id,
+                channel,
+                note,
+            } => Some(VoiceEvent::Choke {
+                timing,
+                voice_id,
+                channel,
+                note,
+            }),
+            NoteEvent::PolyModulation {
+                voice_id,
+                poly_modulation_id,
+                normalized_offset,
+                ..
+            } => Some(VoiceEvent::PolyModulation {
+                voice_id,
+                poly_modulation_id,
+                normalized_offset,
+            }),
+            NoteEvent::MonoAutomation {
+                poly_modulation_id,
+                normalized_value,
+                ..
+            } => Some(VoiceEvent::MonoAutomation {
+                poly_modulation_id,
+                normalized_value,
+            }),
+            NoteEvent::PolyPressure {
+                voice_id,
+                channel,
+                note,
+                pressure,
+                ..
+            } => expression(voice_id, channel, note, NoteExpression::Pressure(pressure)),
+            NoteEvent::PolyVolume {
+                voice_id,
+                channel,
+                note,
+                gain,
+                ..
+            } => expression(voice_id, channel, note, NoteExpression::Volume(gain)),
+            NoteEvent::PolyPan {
+                voice_id,
+                channel,
+                note,
+                pan,
+                ..
+            } => expression(voice_id, channel, note, NoteExpression::Pan(pan)),
+            NoteEvent::PolyTuning {
+                voice_id,
+                channel,
+                note,
+                tuning,
+                ..
+            } => expression(voice_id, channel, note, NoteExpression::Tuning(tuning)),
+            NoteEvent::PolyVibrato {
+                voice_id,
+                channel,
+                note,
+                vibrato,
+                ..
+            } => expression(voice_id, channel, note, NoteExpression::Vibrato(vibrato)),
+            NoteEvent::PolyExpression {
+                voice_id,
+                channel,
+                note,
+                expression: value,
+                ..
+            } => expression(voice_id, channel, note, NoteExpression::Expression(value)),
+            NoteEvent::PolyBrightness {
+                voice_id,
+                channel,
+                note,
+                brightness,
+                ..
+            } => expression(
+                voice_id,
+                channel,
+                note,
+                NoteExpression::Brightness(brightness),
+            ),
+            NoteEvent::MidiChannelPressure {
+                channel, pressure, ..
+            } => {
+                self.channel_pressure[channel as usize % 16] = pressure.clamp(0.0, 1.0);
+                None
+            }
+            NoteEvent::MidiPitchBend { channel, value, .. } => {
+                // The wheel's center position is at 0.5
+                self.pitch_bend[channel as usize % 16] = (value * 2.0 - 1.0).clamp(-1.0, 1.0);
+                None
+            }
+            NoteEvent::MidiCC {
+                channel, cc, value, ..
+            } => {
+                let was_down = self.cc_values.get(Some(channel), cc) >= PEDAL_DOWN_THRESHOLD;
+                self.cc_values.set(channel, cc, value);
+
+                let down = value >= PEDAL_DOWN_THRESHOLD;
+                if cc == SUSTAIN_PEDAL_CC && down != was_down {
+                    Some(VoiceEvent::Pedal { channel, down })
+                } else {
+                    None
+                }
+            }
+            // There are no programs to switch between, voice terminations only go from the plugin
+            // to the host, and there's nothing to do with SysEx messages
+            NoteEvent::MidiProgramChange { .. } => None,
+            _ => None,
+        }
+    }
+
+    pub fn cc_values(&self) -> &CcValues {
+        &self.cc_values
+    }
+
+    pub fn pitch_bend(&self, channel: u8) -> f32 {
+        self.pitch_bend[channel as usize % 16]
+    }
+
+    pub fn channel_pressure(&self, channel: u8) -> f32 {
+        self.channel_pressure[channel as usize % 16]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handle(input: &mut MidiInput, event: NoteEvent<()>) -> Option<VoiceEvent> {
+        input.handle(&event)
+    }
+
+    #[test]
+    fn test_note_on() {
+        let mut input = MidiInput::default();
+        let event = NoteEvent::NoteOn {
+            timing: 3,
+            voice_id: Some(5),
+            channel: 1,
+            note: 60,
+            velocity: 0.5,
+        };
+
+        assert_eq!(
+            handle(&mut input, event),
+            Some(VoiceEvent::NoteOn {
+                timing: 3,
+                voice_id: Some(5),
+                channel: 1,
+                note: 60,
+                velocity: 0.5,
+            })
+        );
+    }
+
+    #[test]
+    fn test_note_off() {
+        let mut input = MidiInput::default();
+        let event = NoteEvent::NoteOff {
+            timing: 3,
+            voice_id: None,
+            channel: 1,
+            note: 60,
+            velocity: 0.0,
+        };
+
+        assert_eq!(
+            handle(&mut input, event),
+            Some(VoiceEvent::NoteOff {
+                voice_id: None,
+                channel: 1,
+                note: 60,
+            })
+        );
+    }
+
+    #[test]
+    fn test_choke() {
+        let mut input = MidiInput::default();
+        let event = NoteEvent::Choke {
+            timing: 3,
+            voice_id: Some(5),
+            channel: 1,
+            note: 60,
+        };
+
+        assert_eq!(
+            handle(&mut input, event),
+            Some(VoiceEvent::Choke {
+                timing: 3,
+                voice_id: Some(5),
+                channel: 1,
+                note: 60,
+            })
+        );
+    }
+
+    #[test]
+    fn test_poly_modulation() {
+        let mut input = MidiInput::default();
+        let modulation = NoteEvent::PolyModulation {
+            timing: 0,
+            voice_id: 5,
+            poly_modulation_id: 2,
+            normalized_offset: 0.25,
+        };
+        let automation = NoteEvent::MonoAutomation {
+            timing: 0,
+            poly_modulation_id: 2,
+            normalized_value: 0.75,
+        };
+
+        assert_eq!(
+            handle(&mut input, modulation),
+            Some(VoiceEvent::PolyModulation {
+                voice_id: 5,
+                poly_modulation_id: 2,
+                normalized_offset: 0.25,
+            })
+        );
+        assert_eq!(
+            handle(&mut input, automation),
+            Some(VoiceEvent::MonoAutomation {
+                poly_modulation_id: 2,
+                normalized_value: 0.75,
+            })
+        );
+    }
+
+    #[test]
+    fn test_poly_expressions() {
+        let mut input = MidiInput::default();
+        let (timing, voice_id, channel, note) = (0, Some(5), 1, 60);
+        let events = [
+            (
+                NoteEvent::PolyPressure {
+                    timing,
+                    voice_id,
+                    channel,
+                    note,
+                    pressure: 0.1,
+                },
+                NoteExpression::Pressure(0.1),
+            ),
+            (
+                NoteEvent::PolyVolume {
+                    timing,
+                    voice_id,
+                    channel,
+                    note,
+                    gain: 0.2,
+                },
+                NoteExpression::Volume(0.2),
+            ),
+            (
+                NoteEvent::PolyPan {
+                    timing,
+                    voice_id,
+                    channel,
+                    note,
+                    pan: -0.3,
+                },
+                NoteExpression::Pan(-0.3),
+            ),
+            (
+                NoteEvent::PolyTuning {
+                    timing,
+                    voice_id,
+                    channel,
+                    note,
+                    tuning: 0.4,
+                },
+                NoteExpression::Tuning(0.4),
+            ),
+            (
+                NoteEvent::PolyVibrato {
+                    timing,
+                    voice_id,
+                    channel,
+                    note,
+                    vibrato: 0.5,
+                },
+                NoteExpression::Vibrato(0.5),
+            ),
+            (
+                NoteEvent::PolyExpression {
+                    timing,
+                    voice_id,
+                    channel,
+                    note,
+                    expression: 0.6,
+                },
+                NoteExpression::Expression(0.6),
+            ),
+            (
+                NoteEvent::PolyBrightness {
+                    timing,
+                    voice_id,
+                    channel,
+                    note,
+                    brightness: 0.7,
+                },
+                NoteExpression::Brightness(0.7),
+            ),
+        ];
+
+        for (event, expression) in events {
+            assert_eq!(
+                handle(&mut input, event),
+                Some(VoiceEvent::Expression {
+                    voice_id,
+                    channel,
+                    note,
+                    expression,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_midi_cc() {
+        let mut input = MidiInput::default();
+        let event = NoteEvent::MidiCC {
+            timing: 0,
+            channel: 2,
+            cc: 1,
+            value: 0.5,
+        };
+
+        assert_eq!(handle(&mut input, event), None);
+        assert_eq!(input.cc_values().get(Some(2), 1), 0.5);
+        assert_eq!(input.cc_values().get(None, 1), 0.5);
+        assert_eq!(input.cc_values().get(Some(3), 1), 0.0);
+    }
+
+    #[test]
+    fn test_pedal() {
+        let mut input = MidiInput::default();
+        let pedal = |value| NoteEvent::MidiCC {
+            timing: 0,
+            channel: 2,
+            cc: SUSTAIN_PEDAL_CC,
+            value,
+        };
+
+        assert_eq!(
+            handle(&mut input, pedal(1.0)),
+            Some(VoiceEvent::Pedal {
+                channel: 2,
+                down: true
+            })
+        );
+        // Only changes between pressed and lifted are events
+        assert_eq!(handle(&mut input, pedal(0.8)), None);
+        assert_eq!(
+            handle(&mut input, pedal(0.2)),
+            Some(VoiceEvent::Pedal {
+                channel: 2,
+                down: false
+            })
+        );
+        assert_eq!(handle(&mut input, pedal(0.0)), None);
+    }
+
+    #[test]
+    fn test_pitch_bend() {
+        let mut input = MidiInput::default();
+        let bend = |channel, value| NoteEvent::MidiPitchBend {
+            timing: 0,
+            channel,
+            value,
+        };
+
+        assert_eq!(handle(&mut input, bend(0, 0.5)), None);
+        assert_eq!(input.pitch_bend(0), 0.0);
+        handle(&mut input, bend(0, 0.0));
+        handle(&mut input, bend(1, 1.0));
+        assert_eq!(input.pitch_bend(0), -1.0);
+        assert_eq!(input.pitch_bend(1), 1.0);
+        assert_eq!(input.pitch_bend(2), 0.0);
+    }
+
+    #[test]
+    fn test_channel_pressure() {
+        let mut input = MidiInput::default();
+        let event = NoteEvent::MidiChannelPressure {
+            timing: 0,
+            channel: 4,
+            pressure: 0.8,
+        };
+
+        assert_eq!(handle(&mut input, event), None);
+        assert_eq!(input.channel_pressure(4), 0.8);
+        assert_eq!(input.channel_pressure(5), 0.0);
+    }
+
+    #[test]
+    fn test_program_change() {
+        let mut input = MidiInput::default();
+        let event = NoteEvent::MidiProgramChange {
+            timing: 0,
+            channel: 9,
+            program: 42,
+        };
+
+        // There are no programs to switch to, so these don't do anything
+        assert_eq!(handle(&mut input, event), None);
+        assert_eq!(input.pitch_bend(9), 0.0);
+        assert_eq!(input.channel_pressure(9), 0.0);
+        assert_eq!(input.cc_values().get(Some(9), 0), 0.0);
+    }
+
+    #[test]
+    fn test_ignored_events() {
+        let mut input = MidiInput::default();
+        let terminated = NoteEvent::VoiceTerminated {
+            timing: 0,
+            voice_id: Some(5),
+            channel: 0,
+            note: 60,
+        };
+        let sysex = NoteEvent::MidiSysEx {
+            timing: 0,
+            message: (),
+        };
+
+        assert_eq!(handle(&mut input, terminated), None);
+        assert_eq!(handle(&mut input, sysex), None);
+    }
+}
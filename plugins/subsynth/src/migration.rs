@@ -0,0 +1,80 @@
+//! Upgrades state saved by older versions of SubSynth. Parameter values are stored unnormalized, so
+//! whenever a parameter's range or meaning changes, old patches need to be converted to keep
+//! sounding the same. The state's schema version is stored as a persistent field on the parameters
+//! struct.
+
+use nih_plug::prelude::*;
+use nih_plug::wrapper::state::ParamValue;
+
+use crate::{MAX_FILTER_CUTOFF_HZ, MIN_FILTER_CUTOFF_HZ};
+
+/// The key of the persistent field the schema version is stored under.
+pub const STATE_VERSION_KEY: &str = "state-version";
+/// The current schema version. Whenever this is increased, a migration from the previous version
+/// needs to be added to [`MIGRATIONS`].
+pub const STATE_VERSION: u32 = 2;
+
+/// `MIGRATIONS[n]` upgrades state from version `n` to version `n + 1`. State saved before the
+/// schema version was introduced is treated as version 0.
+const MIGRATIONS: [fn(&mut PluginState); STATE_VERSION as usize] =
+    [migrate_v0_to_v1, migrate_v1_to_v2];
+
+/// Upgrade `state` to [`STATE_VERSION`] by running all migrations between the state's version and
+/// the current version.
+pub fn migrate(state: &mut PluginState) {
+    // Persistent fields are stored as JSON, and a number serializes to just its digits
+    let version = state
+        .fields
+        .get(STATE_VERSION_KEY)
+        .and_then(|version| version.parse::<u32>().ok())
+        .unwrap_or(0);
+    if version > STATE_VERSION {
+        nih_log!(
+            "Loading state with schema version {version}, but this version of SubSynth only \
+             supports up to version {STATE_VERSION}"
+        );
+        return;
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(state);
+    }
+    state
+        .fields
+        .insert(STATE_VERSION_KEY.to_string(), STATE_VERSION.to_string());
+}
+
+/// The cutoff range changed from a linear 20 Hz to 10 kHz range to a skewed 20 Hz to 20 kHz range,
+/// and the resonance changed from a raw 0-10 value to a normalized 0-1 amount.
+fn migrate_v0_to_v1(state: &mut PluginState) {
+    if let Some(ParamValue::F32(cutoff)) = state.params.get_mut("filter_cut") {
+        *cutoff = cutoff.clamp(MIN_FILTER_CUTOFF_HZ, MAX_FILTER_CUTOFF_HZ);
+    }
+    if let Some(ParamValue::F32(resonance)) = state.params.get_mut("filter_res") {
+        *resonance = (*resonance / 10.0).clamp(0.0, 1.0);
+    }
+}
+
+/// The envelope and LFO attack times were labeled as milliseconds, but they were actually used as
+/// seconds. They're now stored in actual milliseconds.
+fn migrate_v1_to_v2(state: &mut PluginState) {
+    const TIME_PARAM_IDS: [&str; 11] = [
+        "amp_atk",
+        "amp_dec",
+        "amp_rel",
+        "filter_cut_atk",
+        "filter_cut_dec",
+        "filter_cut_rel",
+        "filter_res_atk",
+        "filter_res_dec",
+        "filter_res_rel",
+        "vibrato_atk",
+        "tremolo_atk",
+    ];
+
+    for param_id in TIME_PARAM_IDS {
+        if let Some(ParamValue::F32(time)) = state.params.get_mut(param_id) {
+            *time *= 1000.0;
+        }
+    }
+}
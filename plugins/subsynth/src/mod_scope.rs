@@ -0,0 +1,121 @@
+//! A scope for the modulation signals, to help figure out why a patch moves the way it does. The
+//! audio thread tapes the selected signal of the newest voice into a ring buffer at a fixed control
+//! rate, and the editor draws the last few seconds of it.
+
+use atomic_float::AtomicF32;
+use enum_iterator::Sequence;
+use nih_plug::prelude::Enum;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How far back the scope goes.
+pub const SCOPE_LENGTH_SECONDS: f32 = 4.0;
+/// The number of values the scope holds, which makes for a control rate of 100 Hz.
+pub const SCOPE_SIZE: usize = 400;
+
+/// The signal shown on the scope. The LFOs and the matrix outputs are bipolar, the rest lies
+/// between 0 and 1.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum ScopeSignal {
+    #[name = "Vibrato LFO"]
+    VibratoLfo,
+    #[name = "Tremolo LFO"]
+    TremoloLfo,
+    #[name = "MSEG"]
+    Mseg,
+    #[name = "Amp Envelope"]
+    AmpEnvelope,
+    #[name = "Filter Envelope"]
+    FilterEnvelope,
+    Combiner,
+    /// The mod matrix's summed offsets for some of its destinations, including the MIDI CC
+    /// mappings.
+    #[name = "Matrix Pitch"]
+    MatrixPitch,
+    #[name = "Matrix Cutoff"]
+    MatrixCutoff,
+    #[name = "Matrix Amplitude"]
+    MatrixAmplitude,
+}
+
+/// The scope's ring buffer. The audio thread writes to this and the editor reads from it.
+#[derive(Debug)]
+pub struct ModScope {
+    values: Vec<AtomicF32>,
+    /// The index the next value gets written to, which is also where the oldest value is.
+    write_pos: AtomicUsize,
+}
+
+impl Default for ModScope {
+    fn default() -> Self {
+        Self {
+            values: (0..SCOPE_SIZE).map(|_| AtomicF32::new(0.0)).collect(),
+            write_pos: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ModScope {
+    pub fn push(&self, value: f32) {
+        let write_pos = self.write_pos.load(Ordering::Relaxed);
+        self.values[write_pos].store(value, Ordering::Relaxed);
+        self.write_pos
+            .store((write_pos + 1) % SCOPE_SIZE, Ordering::Relaxed);
+    }
+
+    /// All values from the oldest to the newest.
+    pub fn values(&self) -> impl Iterator<Item = f32> + '_ {
+        let write_pos = self.write_pos.load(Ordering::Relaxed);
+        self.values[write_pos..]
+            .iter()
+            .chain(&self.values[..write_pos])
+            .map(|value| value.load(Ordering::Relaxed))
+    }
+}
+
+/// Decides when the audio thread writes the next value to the scope.
+#[derive(Debug, Clone, Copy)]
+pub struct ScopeTap {
+    samples_per_value: f32,
+    samples_until_next: f32,
+}
+
+impl ScopeTap {
+    pub fn new(sample_rate: f32) -> Self {
+        let samples_per_value = sample_rate * SCOPE_LENGTH_SECONDS / SCOPE_SIZE as f32;
+        Self {
+            samples_per_value,
+            samples_until_next: samples_per_value,
+        }
+    }
+
+    /// Account for a block of `block_len` samples, writing `value` to the scope once for every
+    /// scope position the block covers.
+    pub fn process(&mut self, scope: &ModScope, value: f32, block_len: usize) {
+        self.samples_until_next -= block_len as f32;
+        while self.samples_until_next <= 0.0 {
+            scope.push(value);
+            self.samples_until_next += self.samples_per_value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tap_rate() {
+        let scope = ModScope::default();
+        let mut tap = ScopeTap::new(48_000.0);
+        // One second in 64 sample blocks covers a quarter of the scope
+        for block in 0..750 {
+            tap.process(&scope, block as f32, 64);
+        }
+
+        let values: Vec<f32> = scope.values().collect();
+        assert_eq!(values.len(), SCOPE_SIZE);
+        assert!(values[..SCOPE_SIZE * 3 / 4].iter().all(|&value| value == 0.0));
+        assert_eq!(values[SCOPE_SIZE - 1], 749.0);
+        assert!(values[SCOPE_SIZE * 3 / 4] > 0.0);
+    }
+}
@@ -0,0 +1,309 @@
+//! The modulation matrix. Every slot routes a modulation source to a destination with its own
+//! amount and response curve. The four macro knobs are the main sources, so a host or controller
+//! only needs to drive those to change many parameters at once.
+
+use enum_iterator::Sequence;
+use nih_plug::params::enums::Enum;
+use serde::{Deserialize, Serialize};
+
+use crate::params::{CombinerParams, ModSlotParams};
+
+/// The number of routing slots in the mod matrix.
+pub const NUM_MOD_SLOTS: usize = 8;
+/// The number of macro knobs.
+pub const NUM_MACROS: usize = 4;
+/// How far a slot bends the pitch at full amount, in semitones.
+pub const MOD_PITCH_RANGE_SEMITONES: f32 = 24.0;
+/// How far a slot pushes the filter cutoff at full amount, in octaves.
+pub const MOD_CUTOFF_RANGE_OCTAVES: f32 = 4.0;
+/// How far a slot speeds up or slows down an LFO at full amount, in octaves.
+pub const MOD_LFO_RATE_RANGE_OCTAVES: f32 = 4.0;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum ModSource {
+    Off,
+    #[name = "Macro 1"]
+    Macro1,
+    #[name = "Macro 2"]
+    Macro2,
+    #[name = "Macro 3"]
+    Macro3,
+    #[name = "Macro 4"]
+    Macro4,
+    #[name = "MSEG"]
+    Mseg,
+    Velocity,
+    #[name = "Vibrato LFO"]
+    VibratoLfo,
+    #[name = "Tremolo LFO"]
+    TremoloLfo,
+    /// The mod wheel's last value on any channel.
+    #[name = "Mod Wheel"]
+    ModWheel,
+    /// Channel aftertouch on the voice's MIDI channel.
+    #[name = "Channel Pressure"]
+    ChannelPressure,
+    /// The voice's MIDI note, with note 0 at 0 and note 127 at 1.
+    #[name = "Key Position"]
+    KeyPosition,
+    /// 1 while the voice's key is held, and 0 once the voice is releasing.
+    #[name = "Key Gate"]
+    KeyGate,
+    /// Always 1, for fixed offsets.
+    Constant,
+    /// The combiner's output, which mixes two other sources.
+    Combiner,
+    /// The sustain pedal's last value on any channel. With the pedal mode set to Filter Switch,
+    /// routing this to the cutoff opens the filter while the pedal is down.
+    #[name = "Sustain Pedal"]
+    SustainPedal,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence, Serialize, Deserialize)]
+pub enum ModDestination {
+    Off,
+    Pitch,
+    Cutoff,
+    Resonance,
+    #[name = "Filter Morph"]
+    FilterMorph,
+    Amplitude,
+    #[name = "Supersaw Detune"]
+    SupersawDetune,
+    #[name = "Supersaw Mix"]
+    SupersawMix,
+    /// The LFO destinations only affect the LFOs a sample later, so the LFOs can modulate each
+    /// other and themselves. Rate modulation only applies to per-voice LFOs.
+    #[name = "Vibrato Rate"]
+    VibratoRate,
+    #[name = "Vibrato Amount"]
+    VibratoAmount,
+    #[name = "Tremolo Rate"]
+    TremoloRate,
+    #[name = "Tremolo Amount"]
+    TremoloAmount,
+    /// Scales how far the filter cutoff envelope moves the cutoff. A full negative offset turns the
+    /// envelope off and a full positive offset doubles its depth.
+    #[name = "Filter Env Depth"]
+    FilterEnvDepth,
+}
+
+/// How the combiner mixes its two sources.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum CombineOperation {
+    /// Source B scales source A, for things like an LFO's depth following the mod wheel.
+    Multiply,
+    /// The sum of both sources, clamped to 1.
+    Add,
+}
+
+/// How a slot responds to its source, which always lies between 0 and 1.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum ModCurve {
+    Linear,
+    Exponential,
+    Logarithmic,
+    #[name = "S-Curve"]
+    SCurve,
+}
+
+impl ModCurve {
+    pub fn apply(&self, value: f32) -> f32 {
+        let value = value.clamp(0.0, 1.0);
+        match self {
+            ModCurve::Linear => value,
+            ModCurve::Exponential => value * value,
+            ModCurve::Logarithmic => value.sqrt(),
+            ModCurve::SCurve => value * value * (3.0 - 2.0 * value),
+        }
+    }
+}
+
+/// The current values of all modulation sources for a single voice, between 0 and 1.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ModSourceValues {
+    pub macros: [f32; NUM_MACROS],
+    pub mseg: f32,
+    pub velocity: f32,
+    pub vibrato_lfo: f32,
+    pub tremolo_lfo: f32,
+    pub mod_wheel: f32,
+    pub sustain_pedal: f32,
+    pub channel_pressure: f32,
+    pub key_position: f32,
+    pub key_gate: f32,
+}
+
+impl ModSourceValues {
+    fn get(&self, source: ModSource) -> f32 {
+        match source {
+            ModSource::Off => 0.0,
+            ModSource::Macro1 => self.macros[0],
+            ModSource::Macro2 => self.macros[1],
+            ModSource::Macro3 => self.macros[2],
+            ModSource::Macro4 => self.macros[3],
+            ModSource::Mseg => self.mseg,
+            ModSource::Velocity => self.velocity,
+            ModSource::VibratoLfo => self.vibrato_lfo,
+            ModSource::TremoloLfo => self.tremolo_lfo,
+            ModSource::ModWheel => self.mod_wheel,
+            ModSource::ChannelPressure => self.channel_pressure,
+            ModSource::KeyPosition => self.key_position,
+            ModSource::KeyGate => self.key_gate,
+            ModSource::Constant => 1.0,
+            // The combiner can't feed itself, see `Combiner::apply()`
+            ModSource::Combiner => 0.0,
+            ModSource::SustainPedal => self.sustain_pedal,
+        }
+    }
+}
+
+/// A snapshot of a mod matrix slot's settings.
+#[derive(Debug, Clone, Copy)]
+struct ModSlot {
+    source: ModSource,
+    destination: ModDestination,
+    amount: f32,
+    curve: ModCurve,
+}
+
+/// A snapshot of the combiner's settings.
+#[derive(Debug, Clone, Copy)]
+struct Combiner {
+    source_a: ModSource,
+    source_b: ModSource,
+    operation: CombineOperation,
+    /// How much source B affects the result. At zero the combiner simply outputs source A.
+    amount_b: f32,
+}
+
+impl Combiner {
+    fn apply(&self, sources: &ModSourceValues) -> f32 {
+        let a = sources.get(self.source_a);
+        let b = sources.get(self.source_b);
+        match self.operation {
+            CombineOperation::Multiply => a * (1.0 - self.amount_b + self.amount_b * b),
+            CombineOperation::Add => (a + self.amount_b * b).min(1.0),
+        }
+    }
+}
+
+/// The mod matrix's routing, read from the parameters once per block.
+#[derive(Debug, Clone, Copy)]
+pub struct ModMatrix {
+    slots: [ModSlot; NUM_MOD_SLOTS],
+    combiner: Combiner,
+}
+
+/// The summed modulation for every destination. These are bipolar offsets where 1.0 corresponds to
+/// a single slot at full amount.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ModOffsets {
+    pub pitch: f32,
+    pub cutoff: f32,
+    pub resonance: f32,
+    pub filter_morph: f32,
+    pub amplitude: f32,
+    pub supersaw_detune: f32,
+    pub supersaw_mix: f32,
+    pub vibrato_rate: f32,
+    pub vibrato_amount: f32,
+    pub tremolo_rate: f32,
+    pub tremolo_amount: f32,
+    pub filter_env_depth: f32,
+}
+
+impl ModMatrix {
+    pub fn from_params(
+        slot_params: &[ModSlotParams; NUM_MOD_SLOTS],
+        combiner_params: &CombinerParams,
+    ) -> Self {
+        Self {
+            slots: std::array::from_fn(|idx| ModSlot {
+                source: slot_params[idx].source.value(),
+                destination: slot_params[idx].destination.value(),
+                amount: slot_params[idx].amount.value(),
+                curve: slot_params[idx].curve.value(),
+            }),
+            combiner: Combiner {
+                source_a: combiner_params.source_a.value(),
+                source_b: combiner_params.source_b.value(),
+                operation: combiner_params.operation.value(),
+                amount_b: combiner_params.amount_b.value(),
+            },
+        }
+    }
+
+    /// Whether any slot is routed somewhere. The matrix can be skipped entirely if it isn't.
+    pub fn is_active(&self) -> bool {
+        self.slots.iter().any(|slot| {
+            slot.source != ModSource::Off
+                && slot.destination != ModDestination::Off
+                && slot.amount != 0.0
+        })
+    }
+
+    pub fn evaluate(&self, sources: &ModSourceValues) -> ModOffsets {
+        let mut offsets = ModOffsets::default();
+        for slot in &self.slots {
+            if slot.source == ModSource::Off {
+                continue;
+            }
+
+            let source_value = match slot.source {
+                ModSource::Combiner => self.combiner.apply(sources),
+                source => sources.get(source),
+            };
+            let value = slot.amount * slot.curve.apply(source_value);
+            offsets.add(slot.destination, value);
+        }
+
+        offsets
+    }
+
+    /// The combiner's output, for showing it on the modulation scope.
+    pub fn combiner_output(&self, sources: &ModSourceValues) -> f32 {
+        self.combiner.apply(sources)
+    }
+}
+
+impl ModOffsets {
+    pub fn add(&mut self, destination: ModDestination, value: f32) {
+        match destination {
+            ModDestination::Off => (),
+            ModDestination::Pitch => self.pitch += value,
+            ModDestination::Cutoff => self.cutoff += value,
+            ModDestination::Resonance => self.resonance += value,
+            ModDestination::FilterMorph => self.filter_morph += value,
+            ModDestination::Amplitude => self.amplitude += value,
+            ModDestination::SupersawDetune => self.supersaw_detune += value,
+            ModDestination::SupersawMix => self.supersaw_mix += value,
+            ModDestination::VibratoRate => self.vibrato_rate += value,
+            ModDestination::VibratoAmount => self.vibrato_amount += value,
+            ModDestination::TremoloRate => self.tremolo_rate += value,
+            ModDestination::TremoloAmount => self.tremolo_amount += value,
+            ModDestination::FilterEnvDepth => self.filter_env_depth += value,
+        }
+    }
+}
+
+impl std::ops::Add for ModOffsets {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            pitch: self.pitch + other.pitch,
+            cutoff: self.cutoff + other.cutoff,
+            resonance: self.resonance + other.resonance,
+            filter_morph: self.filter_morph + other.filter_morph,
+            amplitude: self.amplitude + other.amplitude,
+            supersaw_detune: self.supersaw_detune + other.supersaw_detune,
+            supersaw_mix: self.supersaw_mix + other.supersaw_mix,
+            vibrato_rate: self.vibrato_rate + other.vibrato_rate,
+            vibrato_amount: self.vibrato_amount + other.vibrato_amount,
+            tremolo_rate: self.tremolo_rate + other.tremolo_rate,
+            tremolo_amount: self.tremolo_amount + other.tremolo_amount,
+            filter_env_depth: self.filter_env_depth + other.filter_env_depth,
+        }
+    }
+}
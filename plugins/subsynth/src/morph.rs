@@ -0,0 +1,176 @@
+//! Morphing between two stored snapshots of the patch. A snapshot contains the normalized values of
+//! all continuous parameters of both parts and the master gain. Once both snapshots have been
+//! stored, the morph knob interpolates between them in the audio thread, overriding the parameters'
+//! own values. Stepped parameters like enums, integers, and toggles are not morphed.
+
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::SubSynthParams;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MorphSlot {
+    A,
+    B,
+}
+
+/// The stored snapshots, saved with the patch. The values are keyed by parameter ID.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MorphSnapshots {
+    pub a: Option<HashMap<String, f32>>,
+    pub b: Option<HashMap<String, f32>>,
+    /// Incremented whenever a snapshot changes so the audio thread knows when to update its copy.
+    #[serde(skip)]
+    revision: u32,
+}
+
+/// The audio thread's copy of the snapshots, laid out so parameters can be looked up without
+/// allocating.
+pub struct Morph {
+    /// The IDs of the morphable parameters. The snapshot values are stored in the same order.
+    param_ids: Vec<String>,
+    indices: HashMap<ParamPtr, usize>,
+    a: Vec<f32>,
+    b: Vec<f32>,
+    /// Only set when both snapshots have been stored.
+    active: bool,
+    /// The morph knob's position, where 0 is snapshot A and 1 is snapshot B.
+    amount: f32,
+    /// The revision of the snapshots that was last copied, if any.
+    revision: Option<u32>,
+}
+
+/// The parameters that can be morphed, as pairs of parameter IDs and pointers.
+pub fn morphable_params(params: &SubSynthParams) -> Vec<(String, ParamPtr)> {
+    let morphable: HashSet<ParamPtr> = params
+        .part_a
+        .param_map()
+        .into_iter()
+        .chain(params.part_b.param_map())
+        .map(|(_, ptr, _)| ptr)
+        .chain([params.master_gain.as_ptr()])
+        .collect();
+
+    params
+        .param_map()
+        .into_iter()
+        .filter(|(_, ptr, _)| matches!(ptr, ParamPtr::FloatParam(_)) && morphable.contains(ptr))
+        .map(|(id, ptr, _)| (id, ptr))
+        .collect()
+}
+
+impl MorphSnapshots {
+    /// Store the parameters' current values in one of the snapshots.
+    pub fn store(&mut self, slot: MorphSlot, params: &SubSynthParams) {
+        let values = morphable_params(params)
+            .into_iter()
+            // SAFETY: The pointers point to the parameters in `params`, which outlives this call
+            .map(|(id, ptr)| (id, unsafe { ptr.unmodulated_normalized_value() }))
+            .collect();
+        match slot {
+            MorphSlot::A => self.a = Some(values),
+            MorphSlot::B => self.b = Some(values),
+        }
+        self.revision = self.revision.wrapping_add(1);
+    }
+
+    pub fn clear(&mut self) {
+        self.a = None;
+        self.b = None;
+        self.revision = self.revision.wrapping_add(1);
+    }
+
+    pub fn is_stored(&self, slot: MorphSlot) -> bool {
+        match slot {
+            MorphSlot::A => self.a.is_some(),
+            MorphSlot::B => self.b.is_some(),
+        }
+    }
+}
+
+impl Morph {
+    pub fn new(params: &SubSynthParams) -> Self {
+        let morphable = morphable_params(params);
+
+        Self {
+            indices: morphable
+                .iter()
+                .enumerate()
+                .map(|(idx, (_, ptr))| (*ptr, idx))
+                .collect(),
+            a: vec![0.0; morphable.len()],
+            b: vec![0.0; morphable.len()],
+            param_ids: morphable.into_iter().map(|(id, _)| id).collect(),
+            active: false,
+            amount: 0.0,
+            revision: None,
+        }
+    }
+
+    /// Copy the snapshots if they changed since the last time this was called.
+    pub fn update(&mut self, snapshots: &MorphSnapshots) {
+        if self.revision == Some(snapshots.revision) {
+            return;
+        }
+
+        self.revision = Some(snapshots.revision);
+        self.active = false;
+        if let (Some(a), Some(b)) = (&snapshots.a, &snapshots.b) {
+            for (idx, id) in self.param_ids.iter().enumerate() {
+                // Parameters that are missing from a snapshot, for instance because it was stored
+                // by an older version, are not morphed
+                self.a[idx] = a.get(id).copied().unwrap_or(f32::NAN);
+                self.b[idx] = b.get(id).copied().unwrap_or(f32::NAN);
+            }
+            self.active = true;
+        }
+    }
+
+    /// Copy the snapshots again on the next update. The revision isn't saved, so this is needed
+    /// after loading a patch.
+    pub fn invalidate(&mut self) {
+        self.revision = None;
+    }
+
+    pub fn set_amount(&mut self, amount: f32) {
+        self.amount = amount;
+    }
+
+    /// The parameter's morphed normalized value, or `None` if the parameter isn't being morphed.
+    fn morphed_normalized(&self, param: &FloatParam) -> Option<f32> {
+        if !self.active {
+            return None;
+        }
+
+        let idx = *self.indices.get(&param.as_ptr())?;
+        let (a, b) = (self.a[idx], self.b[idx]);
+        if a.is_nan() || b.is_nan() {
+            return None;
+        }
+
+        Some(a + (b - a) * self.amount)
+    }
+
+    /// The parameter's value, or its morphed value if it's being morphed.
+    pub fn value(&self, param: &FloatParam) -> f32 {
+        match self.morphed_normalized(param) {
+            Some(normalized) => param.preview_plain(normalized),
+            None => param.value(),
+        }
+    }
+
+    /// Fill `values` with the parameter's smoothed values, or with its morphed value if it's being
+    /// morphed. The parameter's smoother is advanced either way.
+    pub fn next_block(
+        &self,
+        param: &FloatParam,
+        values: &mut [f32],
+        block_len: usize,
+    ) {
+        param.smoothed.next_block(values, block_len);
+        if let Some(normalized) = self.morphed_normalized(param) {
+            values[..block_len].fill(param.preview_plain(normalized));
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! A multi-segment envelope generator. The envelope's shape is a list of breakpoints that's drawn
+//! in the editor and saved with the patch. Every voice runs its own [`Mseg`] through that shared
+//! shape.
+
+use enum_iterator::Sequence;
+use nih_plug::params::enums::Enum;
+use serde::{Deserialize, Serialize};
+
+/// The maximum number of breakpoints in an envelope, including the first and the last point. The
+/// points are stored in a fixed size array so the shape can be copied to the audio thread without
+/// allocating.
+pub const MAX_MSEG_POINTS: usize = 16;
+/// How far the MSEG bends the pitch at full amount, in semitones.
+pub const MSEG_PITCH_RANGE_SEMITONES: f32 = 24.0;
+/// How far the MSEG pushes the filter cutoff at full amount, in octaves.
+pub const MSEG_CUTOFF_RANGE_OCTAVES: f32 = 4.0;
+/// How strongly a segment with a curvature of 1 or -1 is bent.
+const CURVE_STEEPNESS: f32 = 6.0;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum MsegDestination {
+    Off,
+    Pitch,
+    Cutoff,
+    Amplitude,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MsegPoint {
+    /// Where this point lies within the envelope, between 0 and 1.
+    pub time: f32,
+    /// The envelope's value at this point, between 0 and 1.
+    pub value: f32,
+    /// The curvature of the segment leading up to this point, between -1 and 1. Positive values
+    /// start slow and end fast, negative values do the opposite, and 0 is a straight line.
+    pub curve: f32,
+}
+
+/// The breakpoints making up an envelope. The first point always lies at time 0 and the last point
+/// at time 1. While a note is held, the envelope loops between the points of the loop region if
+/// one is set.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MsegShape {
+    points: [MsegPoint; MAX_MSEG_POINTS],
+    num_points: usize,
+    /// The indices of the points the loop starts and ends at.
+    loop_region: Option<(usize, usize)>,
+}
+
+/// The per-voice state for running through an [`MsegShape`].
+#[derive(Debug, Clone, Copy)]
+pub struct Mseg {
+    /// The current position within the envelope, between 0 and 1.
+    position: f32,
+    released: bool,
+}
+
+impl MsegPoint {
+    fn new(time: f32, value: f32) -> Self {
+        Self {
+            time,
+            value,
+            curve: 0.0,
+        }
+    }
+}
+
+impl Default for MsegShape {
+    /// A simple attack-decay shape.
+    fn default() -> Self {
+        let mut points = [MsegPoint::new(1.0, 0.0); MAX_MSEG_POINTS];
+        points[0] = MsegPoint::new(0.0, 0.0);
+        points[1] = MsegPoint::new(0.1, 1.0);
+        points[2] = MsegPoint {
+            curve: -0.5,
+            ..MsegPoint::new(1.0, 0.0)
+        };
+
+        Self {
+            points,
+            num_points: 3,
+            loop_region: None,
+        }
+    }
+}
+
+impl MsegShape {
+    pub fn points(&self) -> &[MsegPoint] {
+        // Saved state could contain anything, so this is clamped to stay in bounds
+        &self.points[..self.num_points.clamp(2, MAX_MSEG_POINTS)]
+    }
+
+    pub fn loop_region(&self) -> Option<(usize, usize)> {
+        self.loop_region
+            .filter(|&(start, end)| start < end && end < self.points().len())
+    }
+
+    /// Loop between the points at these two indices while the note is held. Invalid regions
+    /// disable looping.
+    pub fn set_loop_region(&mut self, loop_region: Option<(usize, usize)>) {
+        self.loop_region =
+            loop_region.filter(|&(start, end)| start < end && end < self.points().len());
+    }
+
+    /// Add a point at the given time. Returns the new point's index, or `None` if the envelope
+    /// already has the maximum number of points.
+    pub fn insert_point(&mut self, time: f32, value: f32) -> Option<usize> {
+        let num_points = self.points().len();
+        if num_points >= MAX_MSEG_POINTS {
+            return None;
+        }
+
+        // New points always end up between the first and the last point
+        let idx = self.points()[1..]
+            .iter()
+            .position(|point| point.time > time)
+            .map(|idx| idx + 1)
+            .unwrap_or(num_points - 1);
+        self.points.copy_within(idx..num_points, idx + 1);
+        self.points[idx] = MsegPoint::new(time.clamp(0.0, 1.0), value.clamp(0.0, 1.0));
+        self.num_points = num_points + 1;
+
+        if let Some((start, end)) = self.loop_region {
+            let shift = |i: usize| if i >= idx { i + 1 } else { i };
+            self.loop_region = Some((shift(start), shift(end)));
+        }
+
+        Some(idx)
+    }
+
+    /// Remove the point at `idx`. The first and the last point cannot be removed.
+    pub fn remove_point(&mut self, idx: usize) -> bool {
+        let num_points = self.points().len();
+        if idx == 0 || idx >= num_points - 1 {
+            return false;
+        }
+
+        self.points.copy_within(idx + 1..num_points, idx);
+        self.num_points = num_points - 1;
+
+        self.loop_region = match self.loop_region {
+            Some((start, end)) if start == idx || end == idx => None,
+            Some((start, end)) => {
+                let shift = |i: usize| if i > idx { i - 1 } else { i };
+                Some((shift(start), shift(end)))
+            }
+            None => None,
+        };
+
+        true
+    }
+
+    /// Move the point at `idx`. Points cannot be moved past their neighbours, and the first and
+    /// the last point stay at the start and the end of the envelope.
+    pub fn move_point(&mut self, idx: usize, time: f32, value: f32) {
+        let num_points = self.points().len();
+        if idx >= num_points {
+            return;
+        }
+
+        let time = if idx == 0 {
+            0.0
+        } else if idx == num_points - 1 {
+            1.0
+        } else {
+            time.clamp(self.points[idx - 1].time, self.points[idx + 1].time)
+        };
+        self.points[idx].time = time;
+        self.points[idx].value = value.clamp(0.0, 1.0);
+    }
+
+    /// Set the curvature of the segment leading up to the point at `idx`.
+    pub fn set_curve(&mut self, idx: usize, curve: f32) {
+        if idx > 0 && idx < self.points().len() {
+            self.points[idx].curve = curve.clamp(-1.0, 1.0);
+        }
+    }
+
+    /// The index of the point at the end of the segment containing `position`.
+    pub fn segment_at(&self, position: f32) -> usize {
+        let points = self.points();
+        points[1..]
+            .iter()
+            .position(|point| point.time >= position)
+            .map(|idx| idx + 1)
+            .unwrap_or(points.len() - 1)
+    }
+
+    /// The envelope's value at `position`, which lies between 0 and 1.
+    pub fn value_at(&self, position: f32) -> f32 {
+        let points = self.points();
+        let idx = self.segment_at(position);
+        let (start, end) = (points[idx - 1], points[idx]);
+
+        let width = end.time - start.time;
+        if width <= f32::EPSILON {
+            return end.value;
+        }
+
+        let t = ((position - start.time) / width).clamp(0.0, 1.0);
+        start.value + (end.value - start.value) * apply_curve(t, end.curve)
+    }
+}
+
+/// Bend a linear `t` between 0 and 1 using an exponential curve.
+fn apply_curve(t: f32, curve: f32) -> f32 {
+    if curve.abs() < 1e-3 {
+        return t;
+    }
+
+    let steepness = curve * CURVE_STEEPNESS;
+    ((steepness * t).exp() - 1.0) / (steepness.exp() - 1.0)
+}
+
+impl Mseg {
+    pub fn new() -> Self {
+        Self {
+            position: 0.0,
+            released: false,
+        }
+    }
+
+    /// Stop looping and continue to the end of the envelope.
+    pub fn release(&mut self) {
+        self.released = true;
+    }
+
+    /// Get the envelope's current value and advance it by `delta`, which is the fraction of the
+    /// envelope's length that one sample takes up.
+    pub fn next_value(&mut self, shape: &MsegShape, delta: f32) -> f32 {
+        let value = shape.value_at(self.position);
+
+        self.position += delta;
+        match shape.loop_region() {
+            Some((start, end)) if !self.released => {
+                let loop_start = shape.points()[start].time;
+                let loop_end = shape.points()[end].time;
+                if self.position >= loop_end && loop_end > loop_start {
+                    self.position =
+                        loop_start + (self.position - loop_end) % (loop_end - loop_start);
+                }
+            }
+            _ => (),
+        }
+        self.position = self.position.min(1.0);
+
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A shape with straight segments through `points`, given as `(time, value)` pairs.
+    fn shape(points: &[(f32, f32)]) -> MsegShape {
+        let mut shape = MsegShape {
+            points: [MsegPoint::new(1.0, 0.0); MAX_MSEG_POINTS],
+            num_points: points.len(),
+            loop_region: None,
+        };
+        for (point, &(time, value)) in shape.points.iter_mut().zip(points) {
+            *point = MsegPoint::new(time, value);
+        }
+
+        shape
+    }
+
+    /// Run `mseg` through `shape` for `num_samples` samples, returning every value.
+    fn run(mseg: &mut Mseg, shape: &MsegShape, delta: f32, num_samples: usize) -> Vec<f32> {
+        (0..num_samples)
+            .map(|_| mseg.next_value(shape, delta))
+            .collect()
+    }
+
+    #[test]
+    fn test_segment_interpolation() {
+        let mut shape = shape(&[(0.0, 0.0), (0.5, 1.0), (1.0, 0.0)]);
+        assert_eq!(shape.value_at(0.0), 0.0);
+        assert_eq!(shape.value_at(0.5), 1.0);
+        assert!((shape.value_at(0.25) - 0.5).abs() < 1e-6);
+        assert!((shape.value_at(0.875) - 0.25).abs() < 1e-6);
+
+        // Curved segments still pass through both of their points, but bend in between
+        shape.set_curve(1, 1.0);
+        assert_eq!(shape.value_at(0.0), 0.0);
+        assert_eq!(shape.value_at(0.5), 1.0);
+        assert!(shape.value_at(0.25) < 0.5);
+        shape.set_curve(1, -1.0);
+        assert!(shape.value_at(0.25) > 0.5);
+
+        // A vertical segment jumps straight to its end point
+        let shape = self::shape(&[(0.0, 0.0), (0.5, 0.0), (0.5, 1.0), (1.0, 1.0)]);
+        assert_eq!(shape.value_at(0.5), 0.0);
+        assert_eq!(shape.value_at(0.75), 1.0);
+    }
+
+    #[test]
+    fn test_loops_until_released() {
+        let mut shape = shape(&[(0.0, 0.0), (0.25, 1.0), (0.75, 0.0), (1.0, 0.5)]);
+        shape.set_loop_region(Some((1, 2)));
+
+        // Without a release the envelope keeps cycling through the loop, and it never reaches the
+        // segment after the loop's end
+        let mut mseg = Mseg::new();
+        let values = run(&mut mseg, &shape, 0.01, 1000);
+        assert!(mseg.position >= 0.25 && mseg.position < 0.75);
+        let num_peaks = values.iter().filter(|&&value| value > 0.95).count();
+        assert!(num_peaks > 10, "the loop only peaked {num_peaks} times");
+        assert!(values[100..]
+            .iter()
+            .all(|&value| (0.0..=1.0).contains(&value)));
+
+        // After the release it continues to the end of the envelope
+        mseg.release();
+        let values = run(&mut mseg, &shape, 0.01, 200);
+        assert!((values.last().unwrap() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_holds_last_point() {
+        let shape = shape(&[(0.0, 1.0), (0.5, 0.0), (1.0, 0.3)]);
+        assert_eq!(shape.segment_at(1.0), 2);
+        assert!((shape.value_at(1.0) - 0.3).abs() < 1e-6);
+
+        // Running past the end of the envelope stays on the last point's value
+        let mut mseg = Mseg::new();
+        let values = run(&mut mseg, &shape, 0.01, 500);
+        assert_eq!(mseg.position, 1.0);
+        assert!(values[200..]
+            .iter()
+            .all(|&value| (value - 0.3).abs() < 1e-6));
+
+        // Invalid loop regions are ignored
+        let mut looping = shape;
+        looping.set_loop_region(Some((2, 1)));
+        assert_eq!(looping.loop_region(), None);
+        looping.set_loop_region(Some((1, 3)));
+        assert_eq!(looping.loop_region(), None);
+    }
+}
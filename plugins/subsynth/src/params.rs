@@ -0,0 +1,1136 @@
+//! Parameter groups for SubSynth. These are nested into `SubSynthParams` so hosts can show them in a
+//! sensible hierarchy. The ID prefixes used when nesting these groups result in the exact same
+//! parameter IDs the flat parameter struct used, so existing projects and presets still load.
+
+use nih_plug::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use subsynth_dsp::drawbar::{Percussion, DRAWBAR_NAMES, MAX_DRAWBAR_LEVEL, NUM_DRAWBARS};
+use subsynth_dsp::envelope::PitchEnvelopeSource;
+use subsynth_dsp::filter::FilterType;
+use subsynth_dsp::glide::GlideMode;
+use subsynth_dsp::modulator::{LfoMode, LfoScope, OscillatorShape, SyncDivision};
+use subsynth_dsp::velocity::VelocityCurve;
+use subsynth_dsp::waveform::{OscSource, PdCurve, Waveform};
+
+use crate::display;
+use crate::layer::CrossfadeCurve;
+use crate::modmatrix::{CombineOperation, ModCurve, ModDestination, ModSource};
+use crate::mseg::{MsegDestination, MsegShape};
+use crate::strum::StrumMode;
+use crate::trigger::TriggerNote;
+use crate::{
+    CUTOFF_POLY_MOD_ID, GAIN_POLY_MOD_ID, MAX_FILTER_CUTOFF_HZ, MIN_FILTER_CUTOFF_HZ,
+    PAN_POLY_MOD_ID, RESONANCE_POLY_MOD_ID,
+};
+
+/// Everything that makes up a single sound. SubSynth has two of these parts for its multitimbral
+/// mode. Part A is nested without an ID prefix so its parameters keep the IDs they had before there
+/// were multiple parts, and part B is nested with the `part_b` ID prefix.
+#[derive(Params)]
+pub struct PatchParams {
+    /// The per-voice gain, applied to the oscillator before the filter.
+    #[id = "gain"]
+    pub gain: FloatParam,
+    /// Offsets every voice's stereo position. Mostly useful as a polyphonic modulation target.
+    #[id = "pan"]
+    pub pan: FloatParam,
+    /// Only respond to MIDI events on this channel, so several instances or parts can share a
+    /// controller. 0 means omni, which responds to every channel.
+    #[id = "midi_channel"]
+    pub midi_channel: IntParam,
+    /// Playing a note in a choke group quickly fades out the other notes in the same group, like an
+    /// open hi-hat being cut off by a closed one. 0 means the part isn't in a choke group.
+    #[id = "choke_group"]
+    pub choke_group: IntParam,
+
+    #[nested(group = "Oscillator")]
+    pub osc: OscParams,
+    #[nested(array, group = "Drawbars")]
+    pub drawbars: [DrawbarParams; NUM_DRAWBARS],
+    #[nested(id_prefix = "perc", group = "Percussion")]
+    pub percussion: PercussionParams,
+    #[nested(id_prefix = "pitch_env", group = "Pitch Envelope")]
+    pub pitch_env: PitchEnvParams,
+    #[nested(id_prefix = "glide", group = "Glide")]
+    pub glide: GlideParams,
+    #[nested(id_prefix = "strum", group = "Strum")]
+    pub strum: StrumParams,
+    #[nested(id_prefix = "amp", group = "Amp Envelope")]
+    pub amp_env: AmpEnvParams,
+    #[nested(id_prefix = "filter", group = "Filter")]
+    pub filter: FilterParams,
+    #[nested(id_prefix = "filter_cut", group = "Filter Cutoff Envelope")]
+    pub filter_cut_env: FilterEnvParams,
+    #[nested(id_prefix = "filter_res", group = "Filter Resonance Envelope")]
+    pub filter_res_env: FilterEnvParams,
+    #[nested(id_prefix = "vibrato", group = "Vibrato")]
+    pub vibrato: LfoParams,
+    #[nested(id_prefix = "tremolo", group = "Tremolo")]
+    pub tremolo: LfoParams,
+    #[nested(id_prefix = "mseg", group = "MSEG")]
+    pub mseg: MsegParams,
+    #[nested(id_prefix = "drift", group = "Analog Drift")]
+    pub drift: DriftParams,
+    #[nested(id_prefix = "surround", group = "Surround")]
+    pub surround: SurroundParams,
+}
+
+#[derive(Params)]
+pub struct OscParams {
+    #[id = "osc_source"]
+    pub source: EnumParam<OscSource>,
+    #[id = "waveform"]
+    pub waveform: EnumParam<Waveform>,
+    #[id = "supersaw_detune"]
+    pub supersaw_detune: FloatParam,
+    #[id = "supersaw_mix"]
+    pub supersaw_mix: FloatParam,
+    #[id = "pd_curve"]
+    pub pd_curve: EnumParam<PdCurve>,
+    /// How strongly the phase distortion oscillator warps its phase.
+    #[id = "pd_amount"]
+    pub pd_amount: FloatParam,
+    /// How much the filter cutoff envelope adds to the phase distortion amount, like the DCW
+    /// envelope on a CZ.
+    #[id = "pd_env"]
+    pub pd_env_amount: FloatParam,
+}
+
+/// A single drawbar of the drawbar organ waveform. These are nested as an array, so the IDs become
+/// `drawbar_1` through `drawbar_9`.
+#[derive(Params)]
+pub struct DrawbarParams {
+    #[id = "drawbar"]
+    pub level: IntParam,
+}
+
+/// The drawbar organ's percussion, nested with the `perc` ID prefix.
+#[derive(Params)]
+pub struct PercussionParams {
+    #[id = "harmonic"]
+    pub harmonic: EnumParam<Percussion>,
+    #[id = "level"]
+    pub level: FloatParam,
+    /// How long it takes for the percussion to decay by 60 dB.
+    #[id = "decay"]
+    pub decay_ms: FloatParam,
+}
+
+/// Nested with the `amp` ID prefix.
+#[derive(Params)]
+pub struct AmpEnvParams {
+    #[id = "atk"]
+    pub attack_ms: FloatParam,
+    #[id = "dec"]
+    pub decay_ms: FloatParam,
+    /// The sustain stage's level as a gain ratio, displayed in decibels.
+    #[id = "sus"]
+    pub sustain_level: FloatParam,
+    #[id = "rel"]
+    pub release_ms: FloatParam,
+    #[id = "env_level"]
+    pub envelope_level: FloatParam,
+    /// How much faster the envelope runs for higher velocities. At zero it ignores the velocity.
+    #[id = "vel_sens"]
+    pub velocity_sensitivity: FloatParam,
+    #[id = "vel_curve"]
+    pub velocity_curve: EnumParam<VelocityCurve>,
+}
+
+/// Nested with the `filter` ID prefix.
+#[derive(Params)]
+pub struct FilterParams {
+    #[id = "type"]
+    pub filter_type: EnumParam<FilterType>,
+    #[id = "cut"]
+    pub cutoff: FloatParam,
+    #[id = "res"]
+    pub resonance: FloatParam,
+    /// Blends between the lowpass, bandpass, highpass, and notch responses when the filter type is
+    /// set to morph.
+    #[id = "morph"]
+    pub morph: FloatParam,
+    /// How many octaves the oscillator's own output pushes the filter cutoff up and down at audio
+    /// rate.
+    #[id = "fm"]
+    pub fm_amount: FloatParam,
+    /// The cutoff of a gentle lowpass after the main filter, for middle C. The tone control is
+    /// bypassed when this is fully open.
+    #[id = "tone"]
+    pub tone: FloatParam,
+    /// How closely the tone control's cutoff follows the note. At 100% it moves up an octave for
+    /// every octave the note goes up.
+    #[id = "tone_key"]
+    pub tone_key_tracking: FloatParam,
+    /// Scales how far both the cutoff and the resonance envelopes move the filter, so they can be
+    /// turned up and down together.
+    #[id = "env_amount"]
+    pub envelope_amount: FloatParam,
+    /// How much of the envelope amount goes to the resonance envelope. The cutoff envelope always
+    /// gets all of it.
+    #[id = "env_res_ratio"]
+    pub envelope_resonance_ratio: FloatParam,
+    /// Hold the cutoff envelope at its value from when the note was released instead of letting it
+    /// run through its release stage.
+    #[id = "release_freeze"]
+    pub release_freeze: BoolParam,
+    /// Show the cutoff frequency as a note name instead of in Hertz. This only affects how the
+    /// value is displayed.
+    #[id = "cut_note_display"]
+    pub cutoff_note_display: BoolParam,
+}
+
+/// Used for both the filter cutoff and the filter resonance envelopes, nested with the `filter_cut`
+/// and `filter_res` ID prefixes.
+#[derive(Params)]
+pub struct FilterEnvParams {
+    #[id = "atk"]
+    pub attack_ms: FloatParam,
+    #[id = "dec"]
+    pub decay_ms: FloatParam,
+    /// The level the decay stage falls to, displayed as a percentage of the envelope's peak.
+    #[id = "sus"]
+    pub sustain_level: FloatParam,
+    #[id = "rel"]
+    pub release_ms: FloatParam,
+    #[id = "env_level"]
+    pub envelope_level: FloatParam,
+    /// How much faster the envelope runs for higher velocities. At zero it ignores the velocity.
+    #[id = "vel_sens"]
+    pub velocity_sensitivity: FloatParam,
+    #[id = "vel_curve"]
+    pub velocity_curve: EnumParam<VelocityCurve>,
+}
+
+/// Used for both the vibrato and the tremolo, nested with the `vibrato` and `tremolo` ID prefixes.
+#[derive(Params)]
+pub struct LfoParams {
+    /// How long the LFO takes to fade in after a note-on.
+    #[id = "atk"]
+    pub attack_ms: FloatParam,
+    #[id = "int"]
+    pub intensity: FloatParam,
+    #[id = "rate"]
+    pub rate: FloatParam,
+    #[id = "shape"]
+    pub shape: EnumParam<OscillatorShape>,
+    #[id = "mode"]
+    pub mode: EnumParam<LfoMode>,
+    #[id = "scope"]
+    pub scope: EnumParam<LfoScope>,
+    #[id = "sync"]
+    pub sync: EnumParam<SyncDivision>,
+}
+
+/// Nested with the `glide` ID prefix.
+#[derive(Params)]
+pub struct GlideParams {
+    #[id = "mode"]
+    pub mode: EnumParam<GlideMode>,
+    #[id = "time"]
+    pub time_ms: FloatParam,
+    /// How much the glide time scales with the interval between the two notes.
+    #[id = "key_track"]
+    pub key_tracking: FloatParam,
+    /// How much faster notes glide when they're played harder.
+    #[id = "vel_track"]
+    pub velocity_tracking: FloatParam,
+    /// Glide every new note from the most recently played note, even when nothing is held anymore.
+    #[id = "poly"]
+    pub poly: BoolParam,
+}
+
+/// Nested with the `strum` ID prefix.
+#[derive(Params)]
+pub struct StrumParams {
+    /// The delay between the onsets of notes that start at the same time. Single notes are never
+    /// delayed.
+    #[id = "time"]
+    pub time_ms: FloatParam,
+    #[id = "mode"]
+    pub mode: EnumParam<StrumMode>,
+}
+
+/// Nested with the `pitch_env` ID prefix.
+#[derive(Params)]
+pub struct PitchEnvParams {
+    /// How far the pitch envelope bends the pitch at its peak, in semitones.
+    #[id = "amount"]
+    pub amount: FloatParam,
+    #[id = "source"]
+    pub source: EnumParam<PitchEnvelopeSource>,
+    #[id = "atk"]
+    pub attack_ms: FloatParam,
+    #[id = "dec"]
+    pub decay_ms: FloatParam,
+}
+
+/// Nested with the `mseg` ID prefix.
+#[derive(Params)]
+pub struct MsegParams {
+    /// The envelope's breakpoints, edited in the GUI.
+    #[persist = "shape"]
+    pub shape: Arc<RwLock<MsegShape>>,
+    #[id = "dest"]
+    pub destination: EnumParam<MsegDestination>,
+    /// How strongly the envelope modulates its destination. Negative amounts invert the envelope.
+    #[id = "amount"]
+    pub amount: FloatParam,
+    /// The length of the entire envelope when it's not synced to the tempo.
+    #[id = "length"]
+    pub length_ms: FloatParam,
+    #[id = "sync"]
+    pub sync: EnumParam<SyncDivision>,
+}
+
+/// Nested with the `drift` ID prefix.
+#[derive(Params)]
+pub struct DriftParams {
+    /// How far each voice's pitch wanders off at most, in cents.
+    #[id = "depth"]
+    pub depth: FloatParam,
+    /// How quickly the pitch wanders around.
+    #[id = "rate"]
+    pub rate: FloatParam,
+}
+
+/// Nested with the `surround` ID prefix. The Y axis only has an effect with the quad output layout.
+#[derive(Params)]
+pub struct SurroundParams {
+    /// Moves every voice between the front and the rear speakers.
+    #[id = "y"]
+    pub position_y: FloatParam,
+    /// How far apart the voices are spread from left to right.
+    #[id = "spread_x"]
+    pub spread_x: FloatParam,
+    /// How far apart the voices are spread from front to back.
+    #[id = "spread_y"]
+    pub spread_y: FloatParam,
+}
+
+/// Nested with the `trigger` ID prefix.
+#[derive(Params)]
+pub struct InputTriggerParams {
+    /// Play notes when the audio input gets loud enough, for instance to trigger stabs from a drum
+    /// track.
+    #[id = "enabled"]
+    pub enabled: BoolParam,
+    #[id = "threshold"]
+    pub threshold_db: FloatParam,
+    #[id = "note_mode"]
+    pub note_mode: EnumParam<TriggerNote>,
+    #[id = "note"]
+    pub note: IntParam,
+}
+
+/// Adapts the host's MIDI input to the controller. Nested with the `input` ID prefix. The
+/// performance page and the input trigger aren't affected.
+#[derive(Params)]
+pub struct InputTransformParams {
+    #[id = "vel_scale"]
+    pub velocity_scale: FloatParam,
+    #[id = "vel_offset"]
+    pub velocity_offset: FloatParam,
+    /// Applied before the scale and offset.
+    #[id = "vel_curve"]
+    pub velocity_curve: EnumParam<VelocityCurve>,
+    /// Move every event to this MIDI channel. 0 keeps the events on their own channels.
+    #[id = "channel"]
+    pub channel: IntParam,
+    #[id = "transpose"]
+    pub transpose: IntParam,
+}
+
+/// Nested with the `xfade` ID prefix. Only used in multitimbral mode.
+#[derive(Params)]
+pub struct VelocityCrossfadeParams {
+    /// Let the note's velocity decide how loud each part plays it. Without this both parts play
+    /// every note at full volume.
+    #[id = "enabled"]
+    pub enabled: BoolParam,
+    /// The velocity both parts are equally loud at.
+    #[id = "center"]
+    pub center: FloatParam,
+    /// The range of velocities the crossfade is spread over. Zero splits the parts at the center.
+    #[id = "width"]
+    pub width: FloatParam,
+    #[id = "curve"]
+    pub curve: EnumParam<CrossfadeCurve>,
+}
+
+/// A macro knob. These are nested as an array, so the IDs become `macro_1` through `macro_4`.
+#[derive(Params)]
+pub struct MacroParams {
+    #[id = "macro"]
+    pub value: FloatParam,
+}
+
+/// A single routing in the mod matrix. These are nested as an array, so the IDs get the slot's
+/// number as a suffix.
+#[derive(Params)]
+pub struct ModSlotParams {
+    #[id = "mod_src"]
+    pub source: EnumParam<ModSource>,
+    #[id = "mod_dest"]
+    pub destination: EnumParam<ModDestination>,
+    /// How strongly the source modulates the destination. Negative amounts modulate in the other
+    /// direction.
+    #[id = "mod_amount"]
+    pub amount: FloatParam,
+    #[id = "mod_curve"]
+    pub curve: EnumParam<ModCurve>,
+}
+
+/// Mixes two mod matrix sources into the matrix's combiner source. Nested with the `combiner` ID
+/// prefix.
+#[derive(Params)]
+pub struct CombinerParams {
+    #[id = "src_a"]
+    pub source_a: EnumParam<ModSource>,
+    #[id = "src_b"]
+    pub source_b: EnumParam<ModSource>,
+    #[id = "op"]
+    pub operation: EnumParam<CombineOperation>,
+    #[id = "amount_b"]
+    pub amount_b: FloatParam,
+}
+
+/// An envelope stage's duration in milliseconds. The range is skewed so short times get most of the
+/// knob's travel, and the value is shown in either milliseconds or seconds.
+fn envelope_time_param(name: &str, default_ms: f32, max_ms: f32) -> FloatParam {
+    FloatParam::new(
+        name,
+        default_ms,
+        FloatRange::Skewed {
+            min: 0.0,
+            max: max_ms,
+            factor: FloatRange::skew_factor(-2.0),
+        },
+    )
+    .with_step_size(0.1)
+    .with_value_to_string(display::v2s_f32_ms_then_s(1))
+    .with_string_to_value(display::s2v_f32_ms_then_s())
+}
+
+/// How strongly an envelope's speed follows the velocity. Defaults to the full response the
+/// envelopes had before this was adjustable.
+fn velocity_sensitivity_param(name: &str) -> FloatParam {
+    FloatParam::new(name, 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+        .with_unit("%")
+        .with_value_to_string(formatters::v2s_f32_percentage(0))
+        .with_string_to_value(formatters::s2v_f32_percentage())
+}
+
+impl Default for OscParams {
+    fn default() -> Self {
+        Self {
+            source: EnumParam::new("Source", OscSource::Oscillator),
+            waveform: EnumParam::new("Waveform", Waveform::Sine),
+            supersaw_detune: FloatParam::new(
+                "Supersaw Detune",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+            supersaw_mix: FloatParam::new(
+                "Supersaw Mix",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+            pd_curve: EnumParam::new("PD Curve", PdCurve::Sawtooth),
+            pd_amount: FloatParam::new(
+                "PD Amount",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+            pd_env_amount: FloatParam::new(
+                "PD Envelope",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+        }
+    }
+}
+
+impl Default for AmpEnvParams {
+    fn default() -> Self {
+        Self {
+            attack_ms: envelope_time_param("Attack", 1000.0, 10_000.0),
+            decay_ms: envelope_time_param("Decay", 10_000.0, 100_000.0),
+            sustain_level: FloatParam::new(
+                "Sustain",
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 1.0,
+                    factor: FloatRange::gain_skew_factor(-60.0, 0.0),
+                },
+            )
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(1))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            release_ms: envelope_time_param("Release", 1000.0, 10_000.0),
+            envelope_level: FloatParam::new(
+                "Amplitude Envelope Level",
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+            velocity_sensitivity: velocity_sensitivity_param("Amplitude Velocity Sensitivity"),
+            velocity_curve: EnumParam::new("Amplitude Velocity Curve", VelocityCurve::Linear),
+        }
+    }
+}
+
+impl FilterParams {
+    /// `poly_mod_id_offset` is added to the cutoff's and the resonance's polyphonic modulation IDs.
+    pub fn new(poly_mod_id_offset: u32) -> Self {
+        let show_cutoff_note_names = Arc::new(AtomicBool::new(false));
+
+        Self {
+            filter_type: EnumParam::new("Filter Type", FilterType::None),
+            cutoff: FloatParam::new(
+                "Filter Cutoff",
+                200.0,
+                FloatRange::Skewed {
+                    min: MIN_FILTER_CUTOFF_HZ,
+                    max: MAX_FILTER_CUTOFF_HZ,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_poly_modulation_id(CUTOFF_POLY_MOD_ID + poly_mod_id_offset)
+            .with_smoother(SmoothingStyle::Logarithmic(10.0))
+            .with_value_to_string(display::v2s_f32_hz_or_note_name(
+                0,
+                show_cutoff_note_names.clone(),
+            ))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            resonance: FloatParam::new(
+                "Filter Resonance",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_poly_modulation_id(RESONANCE_POLY_MOD_ID + poly_mod_id_offset)
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            morph: FloatParam::new(
+                "Filter Morph",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            fm_amount: FloatParam::new(
+                "Filter FM",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 4.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01)
+            .with_unit(" oct"),
+            tone: FloatParam::new(
+                "Tone",
+                MAX_FILTER_CUTOFF_HZ,
+                FloatRange::Skewed {
+                    min: 200.0,
+                    max: MAX_FILTER_CUTOFF_HZ,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(10.0))
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            tone_key_tracking: FloatParam::new(
+                "Tone Key Tracking",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            envelope_amount: FloatParam::new(
+                "Filter Env Amount",
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            envelope_resonance_ratio: FloatParam::new(
+                "Filter Env Res Ratio",
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            release_freeze: BoolParam::new("Freeze Cutoff On Release", false),
+            cutoff_note_display: BoolParam::new("Cutoff As Note", false)
+                .with_callback(Arc::new(move |value| {
+                    show_cutoff_note_names.store(value, Ordering::Relaxed)
+                }))
+                .non_automatable(),
+        }
+    }
+}
+
+impl PatchParams {
+    /// `poly_mod_id_offset` is added to all of the part's polyphonic modulation IDs, so every part
+    /// can be modulated separately.
+    pub fn new(poly_mod_id_offset: u32) -> Self {
+        Self {
+            gain: FloatParam::new(
+                "Voice Gain",
+                util::db_to_gain(-36.0),
+                FloatRange::Linear {
+                    min: util::db_to_gain(-36.0),
+                    max: util::db_to_gain(0.0),
+                },
+            )
+            .with_poly_modulation_id(GAIN_POLY_MOD_ID + poly_mod_id_offset)
+            .with_smoother(SmoothingStyle::Logarithmic(5.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            pan: FloatParam::new(
+                "Pan",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_poly_modulation_id(PAN_POLY_MOD_ID + poly_mod_id_offset)
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_value_to_string(formatters::v2s_f32_panning())
+            .with_string_to_value(formatters::s2v_f32_panning()),
+            midi_channel: IntParam::new("MIDI Channel", 0, IntRange::Linear { min: 0, max: 16 })
+                .with_value_to_string(Arc::new(|value| match value {
+                    0 => String::from("Omni"),
+                    channel => channel.to_string(),
+                }))
+                .with_string_to_value(Arc::new(|string| {
+                    let string = string.trim();
+                    if string.eq_ignore_ascii_case("omni") {
+                        Some(0)
+                    } else {
+                        string.parse().ok()
+                    }
+                })),
+            choke_group: IntParam::new("Choke Group", 0, IntRange::Linear { min: 0, max: 4 })
+                .with_value_to_string(Arc::new(|value| match value {
+                    0 => String::from("Off"),
+                    group => group.to_string(),
+                }))
+                .with_string_to_value(Arc::new(|string| {
+                    let string = string.trim();
+                    if string.eq_ignore_ascii_case("off") {
+                        Some(0)
+                    } else {
+                        string.parse().ok()
+                    }
+                })),
+
+            osc: OscParams::default(),
+            drawbars: std::array::from_fn(DrawbarParams::new),
+            percussion: PercussionParams::default(),
+            pitch_env: PitchEnvParams::default(),
+            glide: GlideParams::default(),
+            strum: StrumParams::default(),
+            amp_env: AmpEnvParams::default(),
+            filter: FilterParams::new(poly_mod_id_offset),
+            filter_cut_env: FilterEnvParams::new("Filter Cutoff", 1000.0, 10_000.0),
+            filter_res_env: FilterEnvParams::new("Filter Resonance", 10_000.0, 100_000.0),
+            vibrato: LfoParams::new(
+                "Vibrato",
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 32.0,
+                },
+                1.0,
+            ),
+            tremolo: LfoParams::new(
+                "Tremolo",
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 10.0,
+                },
+                0.01,
+            ),
+            mseg: MsegParams::default(),
+            drift: DriftParams::default(),
+            surround: SurroundParams::default(),
+        }
+    }
+}
+
+impl FilterEnvParams {
+    /// The cutoff and resonance envelopes only differ in their names and attack ranges.
+    pub fn new(name: &str, default_attack_ms: f32, max_attack_ms: f32) -> Self {
+        Self {
+            attack_ms: envelope_time_param(
+                &format!("{name} Attack"),
+                default_attack_ms,
+                max_attack_ms,
+            ),
+            decay_ms: envelope_time_param(&format!("{name} Decay"), 10_000.0, 100_000.0),
+            sustain_level: FloatParam::new(
+                format!("{name} Sustain"),
+                1.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_step_size(0.01)
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            release_ms: envelope_time_param(&format!("{name} Release"), 1000.0, 10_000.0),
+            envelope_level: FloatParam::new(
+                format!("{name} Envelope Level"),
+                1.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+            velocity_sensitivity: velocity_sensitivity_param(&format!(
+                "{name} Velocity Sensitivity"
+            )),
+            velocity_curve: EnumParam::new(
+                format!("{name} Velocity Curve"),
+                VelocityCurve::Linear,
+            ),
+        }
+    }
+}
+
+impl LfoParams {
+    /// The vibrato and tremolo use different intensity and rate ranges.
+    pub fn new(
+        name: &str,
+        intensity_range: FloatRange,
+        rate_range: FloatRange,
+        rate_step_size: f32,
+    ) -> Self {
+        Self {
+            attack_ms: envelope_time_param(&format!("{name} Attack"), 1000.0, 10_000.0),
+            intensity: FloatParam::new(format!("{name} Intensity"), 0.0, intensity_range)
+                .with_smoother(SmoothingStyle::Linear(10.0))
+                .with_step_size(0.01)
+                .with_unit(""),
+            rate: FloatParam::new(format!("{name} Rate"), 1.0, rate_range)
+                .with_step_size(rate_step_size)
+                .with_unit(" Hz"),
+            shape: EnumParam::new(format!("{name} Shape"), OscillatorShape::Sine),
+            mode: EnumParam::new(format!("{name} Mode"), LfoMode::KeySync),
+            scope: EnumParam::new(format!("{name} Scope"), LfoScope::PerVoice),
+            sync: EnumParam::new(format!("{name} Sync"), SyncDivision::Off),
+        }
+    }
+}
+
+impl Default for GlideParams {
+    fn default() -> Self {
+        Self {
+            mode: EnumParam::new("Glide Mode", GlideMode::Off),
+            time_ms: FloatParam::new(
+                "Glide Time",
+                100.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_step_size(0.1)
+            .with_unit(" ms"),
+            key_tracking: FloatParam::new(
+                "Glide Key Tracking",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            velocity_tracking: FloatParam::new(
+                "Glide Velocity Tracking",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            poly: BoolParam::new("Poly Glide", false),
+        }
+    }
+}
+
+impl Default for StrumParams {
+    fn default() -> Self {
+        Self {
+            time_ms: FloatParam::new(
+                "Strum Time",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_step_size(0.1)
+            .with_unit(" ms"),
+            mode: EnumParam::new("Strum Mode", StrumMode::Up),
+        }
+    }
+}
+
+impl Default for PitchEnvParams {
+    fn default() -> Self {
+        Self {
+            amount: FloatParam::new(
+                "Pitch Env Amount",
+                0.0,
+                FloatRange::Linear {
+                    min: -48.0,
+                    max: 48.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01)
+            .with_value_to_string(display::v2s_f32_semitones_with_hz(2))
+            .with_string_to_value(display::s2v_f32_leading_number()),
+            source: EnumParam::new("Pitch Env Source", PitchEnvelopeSource::Dedicated),
+            attack_ms: envelope_time_param("Pitch Env Attack", 0.0, 2000.0),
+            decay_ms: envelope_time_param("Pitch Env Decay", 100.0, 5000.0),
+        }
+    }
+}
+
+impl Default for MsegParams {
+    fn default() -> Self {
+        Self {
+            shape: Arc::new(RwLock::new(MsegShape::default())),
+            destination: EnumParam::new("MSEG Destination", MsegDestination::Off),
+            amount: FloatParam::new(
+                "MSEG Amount",
+                0.5,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            length_ms: FloatParam::new(
+                "MSEG Length",
+                1000.0,
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 10_000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_step_size(1.0)
+            .with_unit(" ms"),
+            sync: EnumParam::new("MSEG Sync", SyncDivision::Off),
+        }
+    }
+}
+
+impl DrawbarParams {
+    /// `idx` starts at 0. The 16' and 8' drawbars start out fully pulled, like the classic
+    /// 888000000 registration.
+    pub fn new(idx: usize) -> Self {
+        let default = if idx < 3 { MAX_DRAWBAR_LEVEL } else { 0 };
+        Self {
+            level: IntParam::new(
+                format!("Drawbar {}", DRAWBAR_NAMES[idx]),
+                default,
+                IntRange::Linear {
+                    min: 0,
+                    max: MAX_DRAWBAR_LEVEL,
+                },
+            ),
+        }
+    }
+}
+
+impl Default for PercussionParams {
+    fn default() -> Self {
+        Self {
+            harmonic: EnumParam::new("Percussion", Percussion::Off),
+            level: FloatParam::new(
+                "Percussion Level",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            decay_ms: FloatParam::new(
+                "Percussion Decay",
+                500.0,
+                FloatRange::Skewed {
+                    min: 50.0,
+                    max: 5000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_step_size(0.1)
+            .with_value_to_string(display::v2s_f32_ms_then_s(1))
+            .with_string_to_value(display::s2v_f32_ms_then_s()),
+        }
+    }
+}
+
+impl MacroParams {
+    /// `number` starts at 1.
+    pub fn new(number: usize) -> Self {
+        Self {
+            value: FloatParam::new(
+                format!("Macro {number}"),
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+}
+
+impl ModSlotParams {
+    /// `number` starts at 1.
+    pub fn new(number: usize) -> Self {
+        Self {
+            source: EnumParam::new(format!("Mod {number} Source"), ModSource::Off),
+            destination: EnumParam::new(format!("Mod {number} Destination"), ModDestination::Off),
+            amount: FloatParam::new(
+                format!("Mod {number} Amount"),
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            curve: EnumParam::new(format!("Mod {number} Curve"), ModCurve::Linear),
+        }
+    }
+}
+
+impl Default for CombinerParams {
+    fn default() -> Self {
+        Self {
+            source_a: EnumParam::new("Combiner Source A", ModSource::Off),
+            source_b: EnumParam::new("Combiner Source B", ModSource::Off),
+            operation: EnumParam::new("Combiner Operation", CombineOperation::Multiply),
+            amount_b: FloatParam::new(
+                "Combiner B Amount",
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+}
+
+impl Default for SurroundParams {
+    fn default() -> Self {
+        Self {
+            position_y: FloatParam::new(
+                "Front/Rear",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_value_to_string(Arc::new(|value| {
+                if value == 0.0 {
+                    String::from("C")
+                } else if value < 0.0 {
+                    format!("{:.0}F", value * -100.0)
+                } else {
+                    format!("{:.0}R", value * 100.0)
+                }
+            })),
+            spread_x: FloatParam::new(
+                "Spread X",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            spread_y: FloatParam::new(
+                "Spread Y",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+}
+
+impl Default for InputTriggerParams {
+    fn default() -> Self {
+        Self {
+            enabled: BoolParam::new("Input Trigger", false),
+            threshold_db: FloatParam::new(
+                "Trigger Threshold",
+                -24.0,
+                FloatRange::Linear {
+                    min: -60.0,
+                    max: 0.0,
+                },
+            )
+            .with_step_size(0.1)
+            .with_unit(" dB"),
+            note_mode: EnumParam::new("Trigger Note Mode", TriggerNote::Fixed),
+            note: IntParam::new("Trigger Note", 60, IntRange::Linear { min: 0, max: 127 })
+                .with_value_to_string(formatters::v2s_i32_note_formatter())
+                .with_string_to_value(formatters::s2v_i32_note_formatter()),
+        }
+    }
+}
+
+impl Default for InputTransformParams {
+    fn default() -> Self {
+        Self {
+            velocity_scale: FloatParam::new(
+                "Velocity Scale",
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 2.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            velocity_offset: FloatParam::new(
+                "Velocity Offset",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            velocity_curve: EnumParam::new("Velocity Curve", VelocityCurve::Linear),
+            channel: IntParam::new("Channel Remap", 0, IntRange::Linear { min: 0, max: 16 })
+                .with_value_to_string(Arc::new(|value| match value {
+                    0 => String::from("Off"),
+                    channel => channel.to_string(),
+                }))
+                .with_string_to_value(Arc::new(|string| {
+                    let string = string.trim();
+                    if string.eq_ignore_ascii_case("off") {
+                        Some(0)
+                    } else {
+                        string.parse().ok()
+                    }
+                })),
+            transpose: IntParam::new(
+                "Transpose",
+                0,
+                IntRange::Linear {
+                    min: -48,
+                    max: 48,
+                },
+            )
+            .with_unit(" st"),
+        }
+    }
+}
+
+impl Default for VelocityCrossfadeParams {
+    fn default() -> Self {
+        Self {
+            enabled: BoolParam::new("Velocity Crossfade", false),
+            center: FloatParam::new(
+                "Crossfade Center",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            width: FloatParam::new(
+                "Crossfade Width",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            curve: EnumParam::new("Crossfade Curve", CrossfadeCurve::EqualPower),
+        }
+    }
+}
+
+impl Default for DriftParams {
+    fn default() -> Self {
+        Self {
+            depth: FloatParam::new(
+                "Drift Depth",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 50.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.1)
+            .with_value_to_string(display::v2s_f32_cents_with_hz(1))
+            .with_string_to_value(display::s2v_f32_leading_number()),
+            rate: FloatParam::new(
+                "Drift Rate",
+                0.5,
+                FloatRange::Skewed {
+                    min: 0.01,
+                    max: 5.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_step_size(0.01)
+            .with_unit(" Hz"),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! The sustain pedal, CC 64. Depending on the pedal mode it either holds notes after they're
+//! released, or it only acts through the mod matrix's Sustain Pedal source.
+
+use enum_iterator::Sequence;
+use nih_plug::prelude::Enum;
+
+/// What the sustain pedal does.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum PedalMode {
+    /// Every note released while the pedal is down keeps playing until the pedal is lifted.
+    Sustain,
+    /// Only the notes that were already held when the pedal went down keep playing after they're
+    /// released.
+    Sostenuto,
+    /// The pedal doesn't hold any notes. Route the mod matrix's Sustain Pedal source to the cutoff
+    /// to open the filter with it, or anywhere else.
+    #[name = "Filter Switch"]
+    FilterSwitch,
+}
+
+/// The notes the pedal is holding on every MIDI channel.
+#[derive(Debug, Clone)]
+pub struct Pedal {
+    down: [bool; 16],
+    /// The notes that were released while the pedal held them. These are released when the pedal
+    /// is lifted.
+    sustained: [[bool; 128]; 16],
+    /// The notes that were held when the pedal went down in the sostenuto mode.
+    sostenuto: [[bool; 128]; 16],
+}
+
+impl Default for Pedal {
+    fn default() -> Self {
+        Self {
+            down: [false; 16],
+            sustained: [[false; 128]; 16],
+            sostenuto: [[false; 128]; 16],
+        }
+    }
+}
+
+impl Pedal {
+    /// Press the pedal on a channel. `held_notes` are the notes currently held on that channel,
+    /// which the sostenuto mode keeps playing.
+    pub fn press(&mut self, mode: PedalMode, channel: u8, held_notes: impl Iterator<Item = u8>) {
+        let channel = channel as usize % 16;
+        self.down[channel] = true;
+        if mode == PedalMode::Sostenuto {
+            for note in held_notes {
+                self.sostenuto[channel][note as usize % 128] = true;
+            }
+        }
+    }
+
+    /// Lift the pedal on a channel. Returns the notes that should be released now.
+    pub fn lift(&mut self, channel: u8) -> impl Iterator<Item = u8> {
+        let channel = channel as usize % 16;
+        self.down[channel] = false;
+        self.sostenuto[channel] = [false; 128];
+        let sustained = std::mem::replace(&mut self.sustained[channel], [false; 128]);
+
+        sustained
+            .into_iter()
+            .enumerate()
+            .filter(|(_, sustained)| *sustained)
+            .map(|(note, _)| note as u8)
+    }
+
+    /// Playing a note again means its key is held, so it shouldn't be released when the pedal is
+    /// lifted.
+    pub fn note_on(&mut self, channel: u8, note: u8) {
+        self.sustained[channel as usize % 16][note as usize % 128] = false;
+    }
+
+    /// Check whether the pedal holds a note that's being released. If it does, then the note is
+    /// released later when the pedal is lifted instead.
+    pub fn hold_note_off(&mut self, mode: PedalMode, channel: u8, note: u8) -> bool {
+        let (channel, note) = (channel as usize % 16, note as usize % 128);
+        let held = match mode {
+            PedalMode::Sustain => self.down[channel],
+            PedalMode::Sostenuto => self.down[channel] && self.sostenuto[channel][note],
+            PedalMode::FilterSwitch => false,
+        };
+        if held {
+            self.sustained[channel][note] = true;
+        }
+
+        held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pedal_modes() {
+        let mut pedal = Pedal::default();
+        pedal.press(PedalMode::Sustain, 0, [60].into_iter());
+        assert!(pedal.hold_note_off(PedalMode::Sustain, 0, 64));
+        assert!(!pedal.hold_note_off(PedalMode::Sustain, 1, 64));
+        assert_eq!(pedal.lift(0).collect::<Vec<_>>(), vec![64]);
+        assert!(!pedal.hold_note_off(PedalMode::Sustain, 0, 64));
+
+        // Sostenuto only holds the notes that were playing when the pedal went down
+        pedal.press(PedalMode::Sostenuto, 0, [60, 67].into_iter());
+        assert!(pedal.hold_note_off(PedalMode::Sostenuto, 0, 60));
+        assert!(!pedal.hold_note_off(PedalMode::Sostenuto, 0, 64));
+        pedal.note_on(0, 60);
+        assert!(pedal.hold_note_off(PedalMode::Sostenuto, 0, 67));
+        assert_eq!(pedal.lift(0).collect::<Vec<_>>(), vec![67]);
+
+        pedal.press(PedalMode::FilterSwitch, 0, [60].into_iter());
+        assert!(!pedal.hold_note_off(PedalMode::FilterSwitch, 0, 60));
+        assert_eq!(pedal.lift(0).count(), 0);
+    }
+}
@@ -0,0 +1,186 @@
+//! The editor's performance page plays the synth with an on-screen keyboard and virtual pitch and
+//! mod wheels. The editor writes their state to a [`PerformanceInput`], and the audio thread turns
+//! every change into the same note event a MIDI controller would send.
+
+use atomic_float::AtomicF32;
+use nih_plug::prelude::NoteEvent;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::cc_map::MOD_WHEEL_CC;
+
+/// The lowest note on the on-screen keyboard, C3.
+pub const KEYBOARD_LOWEST_NOTE: u8 = 48;
+/// Two octaves and the C above them.
+pub const KEYBOARD_NUM_KEYS: u8 = 25;
+/// The velocity the on-screen keyboard plays its notes with.
+const KEYBOARD_VELOCITY: f32 = 0.8;
+/// The performance page's notes and wheels all use the first MIDI channel.
+const PERFORMANCE_CHANNEL: u8 = 0;
+
+/// The state of the performance page's controls. The editor writes this and the audio thread reads
+/// it.
+#[derive(Debug)]
+pub struct PerformanceInput {
+    held_keys: [AtomicBool; 128],
+    /// Between -1 and 1, springs back to 0 when released.
+    pitch_wheel: AtomicF32,
+    /// Between 0 and 1.
+    mod_wheel: AtomicF32,
+}
+
+impl Default for PerformanceInput {
+    fn default() -> Self {
+        Self {
+            held_keys: std::array::from_fn(|_| AtomicBool::new(false)),
+            pitch_wheel: AtomicF32::new(0.0),
+            mod_wheel: AtomicF32::new(0.0),
+        }
+    }
+}
+
+impl PerformanceInput {
+    pub fn set_key_held(&self, note: u8, held: bool) {
+        self.held_keys[note as usize % 128].store(held, Ordering::Relaxed);
+    }
+
+    pub fn is_key_held(&self, note: u8) -> bool {
+        self.held_keys[note as usize % 128].load(Ordering::Relaxed)
+    }
+
+    pub fn pitch_wheel(&self) -> f32 {
+        self.pitch_wheel.load(Ordering::Relaxed)
+    }
+
+    pub fn set_pitch_wheel(&self, value: f32) {
+        self.pitch_wheel
+            .store(value.clamp(-1.0, 1.0), Ordering::Relaxed);
+    }
+
+    pub fn mod_wheel(&self) -> f32 {
+        self.mod_wheel.load(Ordering::Relaxed)
+    }
+
+    pub fn set_mod_wheel(&self, value: f32) {
+        self.mod_wheel.store(value.clamp(0.0, 1.0), Ordering::Relaxed);
+    }
+}
+
+/// Turns changes on the performance page into note events on the audio thread. Only changes are
+/// sent, so a hardware controller's wheels keep working alongside the virtual ones.
+#[derive(Debug, Clone)]
+pub struct PerformanceEvents {
+    /// The keys a note on has been sent for.
+    sent_keys: [bool; 128],
+    sent_pitch_wheel: f32,
+    sent_mod_wheel: f32,
+}
+
+impl Default for PerformanceEvents {
+    fn default() -> Self {
+        Self {
+            sent_keys: [false; 128],
+            sent_pitch_wheel: 0.0,
+            sent_mod_wheel: 0.0,
+        }
+    }
+}
+
+impl PerformanceEvents {
+    /// Whether anything changed since the last event was sent.
+    pub fn has_changes(&self, input: &PerformanceInput) -> bool {
+        input.pitch_wheel() != self.sent_pitch_wheel
+            || input.mod_wheel() != self.sent_mod_wheel
+            || (0..128).any(|note| input.is_key_held(note) != self.sent_keys[note as usize])
+    }
+
+    /// The event for the next change on the performance page, if there is one. Call this until it
+    /// returns `None` to get every change.
+    pub fn next_event<S>(&mut self, input: &PerformanceInput, timing: u32) -> Option<NoteEvent<S>> {
+        let pitch_wheel = input.pitch_wheel();
+        if pitch_wheel != self.sent_pitch_wheel {
+            self.sent_pitch_wheel = pitch_wheel;
+            return Some(NoteEvent::MidiPitchBend {
+                timing,
+                channel: PERFORMANCE_CHANNEL,
+                value: (pitch_wheel + 1.0) * 0.5,
+            });
+        }
+
+        let mod_wheel = input.mod_wheel();
+        if mod_wheel != self.sent_mod_wheel {
+            self.sent_mod_wheel = mod_wheel;
+            return Some(NoteEvent::MidiCC {
+                timing,
+                channel: PERFORMANCE_CHANNEL,
+                cc: MOD_WHEEL_CC,
+                value: mod_wheel,
+            });
+        }
+
+        let note =
+            (0..128u8).find(|&note| input.is_key_held(note) != self.sent_keys[note as usize])?;
+        let held = !self.sent_keys[note as usize];
+        self.sent_keys[note as usize] = held;
+        if held {
+            Some(NoteEvent::NoteOn {
+                timing,
+                voice_id: None,
+                channel: PERFORMANCE_CHANNEL,
+                note,
+                velocity: KEYBOARD_VELOCITY,
+            })
+        } else {
+            Some(NoteEvent::NoteOff {
+                timing,
+                voice_id: None,
+                channel: PERFORMANCE_CHANNEL,
+                note,
+                velocity: 0.0,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_changes_are_sent() {
+        let input = PerformanceInput::default();
+        let mut events = PerformanceEvents::default();
+        assert!(!events.has_changes(&input));
+        assert_eq!(events.next_event::<()>(&input, 0), None);
+
+        input.set_key_held(60, true);
+        input.set_mod_wheel(0.5);
+        assert!(events.has_changes(&input));
+        assert_eq!(
+            events.next_event::<()>(&input, 0),
+            Some(NoteEvent::MidiCC {
+                timing: 0,
+                channel: 0,
+                cc: MOD_WHEEL_CC,
+                value: 0.5,
+            })
+        );
+        assert_eq!(
+            events.next_event::<()>(&input, 0),
+            Some(NoteEvent::NoteOn {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 60,
+                velocity: KEYBOARD_VELOCITY,
+            })
+        );
+        assert_eq!(events.next_event::<()>(&input, 0), None);
+
+        input.set_key_held(60, false);
+        assert!(matches!(
+            events.next_event::<()>(&input, 0),
+            Some(NoteEvent::NoteOff { note: 60, .. })
+        ));
+        assert!(!events.has_changes(&input));
+    }
+}
@@ -0,0 +1,392 @@
+//! Renders short previews of the current patch to WAV files, so a patch can be shared without
+//! recording it in the DAW. The editor starts these as background tasks. They run on a separate
+//! copy of the synth that plays the plugin's parameters.
+
+use nih_plug::prelude::*;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::host::{HostContext, HostTransport};
+use crate::{SubSynth, SubSynthParams};
+
+/// The sample rate previews are rendered at.
+pub const PREVIEW_SAMPLE_RATE: u32 = 44100;
+/// Previews are rendered in blocks of this many samples.
+const PREVIEW_BLOCK_SIZE: usize = 512;
+/// The C major chord's notes, starting at middle C.
+const CHORD_NOTES: [u8; 3] = [60, 64, 67];
+/// How long the chord is held for, in seconds.
+const CHORD_LENGTH: f32 = 2.0;
+/// How long every note in a sequence is held for, in seconds.
+const SEQUENCE_NOTE_LENGTH: f32 = 0.5;
+const PREVIEW_VELOCITY: f32 = 0.8;
+/// How long the preview keeps going after the last note off while voices are still releasing, in
+/// seconds. This cuts off drones and very long releases.
+const MAX_RELEASE_LENGTH: f32 = 10.0;
+
+/// Render a preview of the current patch and write it to a WAV file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviewTask {
+    /// An existing file is overwritten.
+    pub path: PathBuf,
+    pub notes: PreviewNotes,
+}
+
+/// What a preview plays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreviewNotes {
+    /// A C major chord starting at middle C.
+    CMajorChord,
+    /// These MIDI notes, one after the other.
+    Sequence(Vec<u8>),
+}
+
+/// A note on or off in a preview.
+#[derive(Debug, Clone, Copy)]
+struct ScheduledNote {
+    /// The position in samples since the start of the preview.
+    timing: usize,
+    note: u8,
+    on: bool,
+}
+
+/// The preview's copy of the synth doesn't run inside of a host, so nothing ends up here.
+struct PreviewInitContext;
+
+impl InitContext<SubSynth> for PreviewInitContext {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Clap
+    }
+
+    fn execute(&self, _task: PreviewTask) {}
+
+    fn set_latency_samples(&self, _samples: u32) {}
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+/// Feeds one block's notes to the synth.
+struct PreviewContext<'a> {
+    notes: &'a [ScheduledNote],
+    block_start: usize,
+}
+
+impl HostContext for PreviewContext<'_> {
+    fn next_event(&mut self) -> Option<PluginNoteEvent<SubSynth>> {
+        let (note, notes) = self.notes.split_first()?;
+        self.notes = notes;
+
+        let timing = (note.timing - self.block_start) as u32;
+        Some(if note.on {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id: None,
+                channel: 0,
+                note: note.note,
+                velocity: PREVIEW_VELOCITY,
+            }
+        } else {
+            NoteEvent::NoteOff {
+                timing,
+                voice_id: None,
+                channel: 0,
+                note: note.note,
+                velocity: 0.0,
+            }
+        })
+    }
+
+    fn send_event(&mut self, _event: PluginNoteEvent<SubSynth>) {}
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+impl PreviewNotes {
+    /// Parse a list of notes separated by spaces or commas. Notes are either MIDI note numbers or
+    /// note names like `C4` or `F#3`, where `C4` is middle C. An empty list plays the chord.
+    pub fn parse(text: &str) -> Option<Self> {
+        let notes = text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|note| !note.is_empty())
+            .map(parse_note)
+            .collect::<Option<Vec<u8>>>()?;
+
+        if notes.is_empty() {
+            Some(Self::CMajorChord)
+        } else {
+            Some(Self::Sequence(notes))
+        }
+    }
+
+    /// The note ons and offs, sorted by their timing. Note offs come before note ons at the same
+    /// position, so a repeated note is played again.
+    fn schedule(&self) -> Vec<ScheduledNote> {
+        let note_on = |timing, note| ScheduledNote {
+            timing,
+            note,
+            on: true,
+        };
+        let note_off = |timing, note| ScheduledNote {
+            timing,
+            note,
+            on: false,
+        };
+
+        match self {
+            Self::CMajorChord => {
+                let length = seconds_to_samples(CHORD_LENGTH);
+                CHORD_NOTES
+                    .iter()
+                    .map(|&note| note_on(0, note))
+                    .chain(CHORD_NOTES.iter().map(|&note| note_off(length, note)))
+                    .collect()
+            }
+            Self::Sequence(notes) => {
+                let length = seconds_to_samples(SEQUENCE_NOTE_LENGTH);
+                notes
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(idx, &note)| {
+                        [
+                            note_on(idx * length, note),
+                            note_off((idx + 1) * length, note),
+                        ]
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Parse a MIDI note number or a note name like `C4` or `F#-1`.
+fn parse_note(text: &str) -> Option<u8> {
+    if let Ok(note) = text.parse::<u8>() {
+        return (note <= 127).then_some(note);
+    }
+
+    let octave_start = text.find(|c: char| c == '-' || c.is_ascii_digit())?;
+    let (name, octave) = text.split_at(octave_start);
+    let pitch_class = util::NOTES
+        .iter()
+        .position(|note_name| note_name.eq_ignore_ascii_case(name))? as i32;
+    let octave: i32 = octave.parse().ok()?;
+
+    u8::try_from((octave + 1) * 12 + pitch_class)
+        .ok()
+        .filter(|note| *note <= 127)
+}
+
+fn seconds_to_samples(seconds: f32) -> usize {
+    (seconds * PREVIEW_SAMPLE_RATE as f32).round() as usize
+}
+
+/// Render the preview, write it to the task's file, and log the result. There's nobody to report
+/// errors to from a background task.
+pub fn run(params: Arc<SubSynthParams>, task: PreviewTask) {
+    let channels = render(params, &task.notes);
+    let result = File::create(&task.path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        write_wav(&mut writer, PREVIEW_SAMPLE_RATE, &channels)?;
+        writer.flush()
+    });
+
+    match result {
+        Ok(()) => nih_log!("Wrote a patch preview to '{}'", task.path.display()),
+        Err(err) => nih_error!(
+            "Could not write a patch preview to '{}': {err}",
+            task.path.display()
+        ),
+    }
+}
+
+/// Play the notes on a new copy of the synth and return the left and right channels. The preview
+/// ends once the last voice has finished releasing.
+///
+/// The copy plays the plugin's parameters as they are while this runs. It shares their smoothers
+/// with the plugin, so a parameter that's changed during the render glides to its new value faster
+/// than usual.
+fn render(params: Arc<SubSynthParams>, notes: &PreviewNotes) -> [Vec<f32>; 2] {
+    let mut synth = SubSynth::new(params);
+    let initialized = synth.initialize(
+        &SubSynth::AUDIO_IO_LAYOUTS[0],
+        &BufferConfig {
+            sample_rate: PREVIEW_SAMPLE_RATE as f32,
+            min_buffer_size: None,
+            max_buffer_size: PREVIEW_BLOCK_SIZE as u32,
+            process_mode: ProcessMode::Offline,
+        },
+        &mut PreviewInitContext,
+    );
+    nih_debug_assert!(initialized);
+    synth.reset();
+
+    // Without a tempo from the host, the synth falls back to its internal tempo
+    let transport = HostTransport {
+        playing: false,
+        sample_rate: PREVIEW_SAMPLE_RATE as f32,
+        tempo: None,
+        time_sig_denominator: None,
+        pos_samples: None,
+        pos_beats: None,
+        bar_start_pos_beats: None,
+        bar_number: None,
+    };
+    let schedule = notes.schedule();
+    let notes_end = schedule.last().map_or(0, |note| note.timing);
+    let max_length = notes_end + seconds_to_samples(MAX_RELEASE_LENGTH);
+
+    let mut output = [Vec::new(), Vec::new()];
+    let mut block_start = 0;
+    let mut next_note_idx = 0;
+    while block_start < max_length
+        && (next_note_idx < schedule.len() || synth.voices.iter().any(Option::is_some))
+    {
+        let block_end = block_start + PREVIEW_BLOCK_SIZE;
+        let num_notes = schedule[next_note_idx..]
+            .iter()
+            .take_while(|note| note.timing < block_end)
+            .count();
+        let mut context = PreviewContext {
+            notes: &schedule[next_note_idx..next_note_idx + num_notes],
+            block_start,
+        };
+        next_note_idx += num_notes;
+
+        let mut channels = [vec![0.0; PREVIEW_BLOCK_SIZE], vec![0.0; PREVIEW_BLOCK_SIZE]];
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(PREVIEW_BLOCK_SIZE, |slices| {
+                *slices = channels
+                    .iter_mut()
+                    .map(|channel| channel.as_mut_slice())
+                    .collect();
+            });
+        }
+        synth.process_buffer(&mut buffer, &transport, &mut context);
+        drop(buffer);
+
+        for (output, channel) in output.iter_mut().zip(channels) {
+            output.extend(channel);
+        }
+        block_start = block_end;
+    }
+
+    output
+}
+
+/// Write the channels to a 16-bit stereo WAV file. Samples outside of `[-1, 1]` are clipped.
+fn write_wav(mut writer: impl Write, sample_rate: u32, channels: &[Vec<f32>; 2]) -> io::Result<()> {
+    const NUM_CHANNELS: u16 = 2;
+    const BITS_PER_SAMPLE: u16 = 16;
+    const BLOCK_ALIGN: u16 = NUM_CHANNELS * BITS_PER_SAMPLE / 8;
+
+    let [left, right] = channels;
+    let data_len = u32::try_from(left.len() * BLOCK_ALIGN as usize)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "The preview is too long"))?;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_len).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    // 1 is integer PCM
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&NUM_CHANNELS.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * BLOCK_ALIGN as u32).to_le_bytes())?;
+    writer.write_all(&BLOCK_ALIGN.to_le_bytes())?;
+    writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())?;
+    for (&left, &right) in left.iter().zip(right) {
+        for sample in [left, right] {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+            writer.write_all(&sample.to_le_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_host::TestHost;
+
+    #[test]
+    fn test_parse_notes() {
+        assert_eq!(PreviewNotes::parse(""), Some(PreviewNotes::CMajorChord));
+        assert_eq!(PreviewNotes::parse(" , "), Some(PreviewNotes::CMajorChord));
+        assert_eq!(
+            PreviewNotes::parse("C4 e4, F#3 c-1 G9 72"),
+            Some(PreviewNotes::Sequence(vec![60, 64, 54, 0, 127, 72]))
+        );
+        assert_eq!(PreviewNotes::parse("C4 H4"), None);
+        assert_eq!(PreviewNotes::parse("G#9"), None);
+        assert_eq!(PreviewNotes::parse("128"), None);
+        assert_eq!(PreviewNotes::parse("C"), None);
+    }
+
+    #[test]
+    fn test_sequence_schedule() {
+        let schedule = PreviewNotes::Sequence(vec![60, 60]).schedule();
+        let length = seconds_to_samples(SEQUENCE_NOTE_LENGTH);
+        let schedule: Vec<_> = schedule
+            .iter()
+            .map(|note| (note.timing, note.note, note.on))
+            .collect();
+        assert_eq!(
+            schedule,
+            [
+                (0, 60, true),
+                (length, 60, false),
+                (length, 60, true),
+                (length * 2, 60, false)
+            ]
+        );
+    }
+
+    /// The chord should be audible, and the preview should stop once its release has finished.
+    #[test]
+    fn test_render_chord() {
+        // The test host snaps the parameters' smoothers to their values, like the plugin's wrapper
+        let host = TestHost::new(PREVIEW_SAMPLE_RATE as f32, PREVIEW_BLOCK_SIZE as u32);
+        let [left, right] = render(host.plugin.params.clone(), &PreviewNotes::CMajorChord);
+
+        assert_eq!(left.len(), right.len());
+        let chord_length = seconds_to_samples(CHORD_LENGTH);
+        assert!(left.len() > chord_length);
+        assert!(left.len() < chord_length + seconds_to_samples(MAX_RELEASE_LENGTH));
+        assert!(left.iter().chain(&right).all(|sample| sample.is_finite()));
+        let peak = left[..chord_length]
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!(peak > 0.01, "The chord is silent");
+        let tail = &left[left.len() - PREVIEW_BLOCK_SIZE..];
+        assert!(tail.iter().all(|sample| sample.abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_write_wav() {
+        let channels = [vec![0.0, 1.0, -2.0], vec![0.5, -1.0, 0.0]];
+        let mut wav = Vec::new();
+        write_wav(&mut wav, 48000, &channels).unwrap();
+
+        assert_eq!(wav.len(), 44 + 3 * 4);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(
+            u32::from_le_bytes(wav[4..8].try_into().unwrap()),
+            36 + 3 * 4
+        );
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 48000);
+        assert_eq!(&wav[36..40], b"data");
+        let samples: Vec<i16> = wav[44..]
+            .chunks(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+            .collect();
+        assert_eq!(samples, [0, 16384, i16::MAX, -i16::MAX, -i16::MAX, 0]);
+    }
+}
@@ -0,0 +1,31 @@
+//! Chord strumming. When several notes start at the same time, their onsets can be spread out so
+//! the chord rolls in like a strummed guitar instead of starting all at once.
+
+use enum_iterator::Sequence;
+use nih_plug::params::enums::Enum;
+use rand::Rng;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum StrumMode {
+    /// The lowest note starts first.
+    Up,
+    /// The highest note starts first.
+    Down,
+    /// The notes start in a random order.
+    Random,
+}
+
+/// Put the notes of a chord in the order they should start in. Every entry is a voice index paired
+/// with that voice's note.
+pub fn order_strum(chord: &mut [(usize, u8)], mode: StrumMode, rng: &mut impl Rng) {
+    match mode {
+        StrumMode::Up => chord.sort_unstable_by_key(|&(_, note)| note),
+        StrumMode::Down => chord.sort_unstable_by_key(|&(_, note)| std::cmp::Reverse(note)),
+        StrumMode::Random => {
+            // A Fisher-Yates shuffle, so the order only depends on the synth's PRNG
+            for idx in (1..chord.len()).rev() {
+                chord.swap(idx, rng.gen_range(0..=idx));
+            }
+        }
+    }
+}
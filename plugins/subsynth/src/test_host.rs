@@ -0,0 +1,177 @@
+//! A minimal stand-in for a host, so the tests can drive `process()` directly. It initializes the
+//! plugin like a host would, feeds it note events, changes its parameters, runs a transport, and
+//! collects the events the plugin sends back.
+
+use nih_plug::prelude::*;
+use std::collections::VecDeque;
+
+use crate::host::{HostContext, HostTransport};
+use crate::preview::PreviewTask;
+use crate::SubSynth;
+
+/// The plugin's stereo layout.
+const NUM_CHANNELS: usize = 2;
+
+pub struct TestHost {
+    pub plugin: SubSynth,
+    sample_rate: f32,
+    /// The transport the next buffer is processed with.
+    pub transport: TestTransport,
+    /// Every event the plugin sent back, in order.
+    pub output_events: Vec<PluginNoteEvent<SubSynth>>,
+}
+
+/// The host's transport. While it's playing, the position advances by every processed buffer's
+/// length.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TestTransport {
+    pub playing: bool,
+    /// `None` for hosts that don't send a tempo.
+    pub tempo: Option<f64>,
+    pub pos_samples: i64,
+    /// The loop's start and exclusive end in samples, if looping is enabled. Like most hosts, this
+    /// only jumps back to the loop's start between buffers.
+    pub loop_range: Option<(i64, i64)>,
+}
+
+struct TestInitContext;
+
+impl InitContext<SubSynth> for TestInitContext {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Clap
+    }
+
+    fn execute(&self, _task: PreviewTask) {}
+
+    fn set_latency_samples(&self, _samples: u32) {}
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+struct TestProcessContext<'a> {
+    input_events: VecDeque<PluginNoteEvent<SubSynth>>,
+    output_events: &'a mut Vec<PluginNoteEvent<SubSynth>>,
+}
+
+impl HostContext for TestProcessContext<'_> {
+    fn next_event(&mut self) -> Option<PluginNoteEvent<SubSynth>> {
+        self.input_events.pop_front()
+    }
+
+    fn send_event(&mut self, event: PluginNoteEvent<SubSynth>) {
+        self.output_events.push(event);
+    }
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+impl TestHost {
+    /// Create and initialize the plugin with the default parameters.
+    pub fn new(sample_rate: f32, max_buffer_size: u32) -> Self {
+        let mut plugin = SubSynth::default();
+        let initialized = plugin.initialize(
+            &SubSynth::AUDIO_IO_LAYOUTS[0],
+            &BufferConfig {
+                sample_rate,
+                min_buffer_size: None,
+                max_buffer_size,
+                process_mode: ProcessMode::Offline,
+            },
+            &mut TestInitContext,
+        );
+        assert!(initialized);
+
+        let mut host = Self {
+            plugin,
+            sample_rate,
+            transport: TestTransport::default(),
+            output_events: Vec::new(),
+        };
+        host.reset();
+
+        host
+    }
+
+    /// Reset the plugin and snap the parameters' smoothers to their current values, like a host
+    /// would when playback restarts.
+    pub fn reset(&mut self) {
+        for (_, param_ptr, _) in self.plugin.params().param_map() {
+            unsafe {
+                let normalized = param_ptr.unmodulated_normalized_value();
+                param_ptr.set_normalized_value_for_tests(normalized, self.sample_rate, true);
+            }
+        }
+        self.plugin.reset();
+    }
+
+    /// Change a parameter like the host would when automating it. The new value is smoothed.
+    pub fn set_parameter<P: Param>(&mut self, param: &P, value: P::Plain) {
+        self.set_normalized_parameter(param.as_ptr(), param.preview_normalized(value));
+    }
+
+    /// Change a parameter from [`Params::param_map()`] to a normalized value, like
+    /// [`set_parameter()`][Self::set_parameter()].
+    pub fn set_normalized_parameter(&mut self, param_ptr: ParamPtr, normalized: f32) {
+        // Only NIH-plug's wrappers can change a parameter's value. A `GuiContext` behind a
+        // `ParamSetter` doesn't get around that, since it just hands the change to the wrapper this
+        // host stands in for. This is the one hidden helper NIH-plug has for it.
+        unsafe { param_ptr.set_normalized_value_for_tests(normalized, self.sample_rate, false) };
+    }
+
+    /// Process a buffer of `num_samples` samples with the given events, which need to be sorted by
+    /// their timing. Returns the output channels.
+    pub fn process(
+        &mut self,
+        num_samples: usize,
+        events: impl IntoIterator<Item = PluginNoteEvent<SubSynth>>,
+    ) -> Vec<Vec<f32>> {
+        let mut channels = vec![vec![0.0; num_samples]; NUM_CHANNELS];
+        let transport = HostTransport {
+            playing: self.transport.playing,
+            sample_rate: self.sample_rate,
+            tempo: self.transport.tempo,
+            time_sig_denominator: None,
+            pos_samples: Some(self.transport.pos_samples),
+            pos_beats: None,
+            bar_start_pos_beats: None,
+            bar_number: None,
+        };
+        let mut context = TestProcessContext {
+            input_events: events.into_iter().collect(),
+            output_events: &mut self.output_events,
+        };
+
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(num_samples, |slices| {
+                *slices = channels.iter_mut().map(|channel| channel.as_mut_slice()).collect();
+            });
+        }
+        let status = self
+            .plugin
+            .process_buffer(&mut buffer, &transport, &mut context);
+        assert!(!matches!(status, ProcessStatus::Error(_)));
+        drop(buffer);
+
+        if self.transport.playing {
+            self.transport.pos_samples += num_samples as i64;
+            if let Some((loop_start, loop_end)) = self.transport.loop_range {
+                if self.transport.pos_samples >= loop_end {
+                    self.transport.pos_samples = loop_start;
+                }
+            }
+        }
+
+        channels
+    }
+
+    /// The voice IDs of the voices that are currently playing.
+    pub fn active_voice_ids(&self) -> Vec<i32> {
+        self.plugin
+            .voices
+            .iter()
+            .flatten()
+            .map(|voice| voice.voice_id)
+            .collect()
+    }
+}
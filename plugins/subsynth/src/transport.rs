@@ -0,0 +1,100 @@
+//! The host's musical time, shared with the editor so users can check that the tempo synced
+//! features follow the host.
+
+use atomic_float::AtomicF32;
+use nih_plug::prelude::Enum;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use subsynth_dsp::modulator::SyncDivision;
+
+use crate::host::HostTransport;
+
+/// The transport state during the last processed buffer. The audio thread writes this and the
+/// editor reads it.
+#[derive(Debug)]
+pub struct TransportDisplay {
+    playing: AtomicBool,
+    /// The tempo used by the tempo synced features, in beats per minute.
+    tempo: AtomicF32,
+    /// Whether `tempo` came from the host, or from the internal tempo parameter.
+    host_tempo: AtomicBool,
+    /// The zero-based bar number, or -1 if the host doesn't report its position.
+    bar: AtomicI32,
+    /// The position within the bar in the time signature's beats, starting at zero.
+    beat: AtomicF32,
+}
+
+impl Default for TransportDisplay {
+    fn default() -> Self {
+        Self {
+            playing: AtomicBool::new(false),
+            tempo: AtomicF32::new(120.0),
+            host_tempo: AtomicBool::new(false),
+            bar: AtomicI32::new(-1),
+            beat: AtomicF32::new(0.0),
+        }
+    }
+}
+
+impl TransportDisplay {
+    /// Store the transport's state. `tempo` is the tempo the synth actually uses, which is the
+    /// internal tempo when the host doesn't provide one.
+    pub fn update(&self, transport: &HostTransport, tempo: f64) {
+        self.playing.store(transport.playing, Ordering::Relaxed);
+        self.tempo.store(tempo as f32, Ordering::Relaxed);
+        self.host_tempo
+            .store(transport.tempo.is_some(), Ordering::Relaxed);
+
+        match (
+            transport.bar_number,
+            transport.pos_beats,
+            transport.bar_start_pos_beats,
+        ) {
+            (Some(bar), Some(pos_beats), Some(bar_start_pos_beats)) => {
+                // The positions are in quarter notes, the displayed beats follow the time signature
+                let beat_length = 4.0 / transport.time_sig_denominator.unwrap_or(4) as f64;
+                self.bar.store(bar, Ordering::Relaxed);
+                self.beat.store(
+                    ((pos_beats - bar_start_pos_beats) / beat_length).max(0.0) as f32,
+                    Ordering::Relaxed,
+                );
+            }
+            _ => self.bar.store(-1, Ordering::Relaxed),
+        }
+    }
+
+    pub fn tempo(&self) -> f64 {
+        self.tempo.load(Ordering::Relaxed) as f64
+    }
+}
+
+/// Shows the position as one-based bars and beats, followed by the tempo and the play state.
+impl fmt::Display for TransportDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.bar.load(Ordering::Relaxed) {
+            bar if bar >= 0 => {
+                let beat = self.beat.load(Ordering::Relaxed) as i32;
+                write!(f, "{}.{}", bar + 1, beat + 1)?
+            }
+            _ => write!(f, "-.-")?,
+        }
+        write!(f, "  {:.1} BPM", self.tempo.load(Ordering::Relaxed))?;
+        if !self.host_tempo.load(Ordering::Relaxed) {
+            write!(f, " (internal)")?;
+        }
+        if self.playing.load(Ordering::Relaxed) {
+            write!(f, "  Playing")
+        } else {
+            write!(f, "  Stopped")
+        }
+    }
+}
+
+/// A tempo synced feature's note division along with the rate it results in at `tempo`.
+pub fn sync_readout(name: &str, division: SyncDivision, tempo: f64) -> String {
+    let division_name = SyncDivision::variants()[division.to_index()];
+    match division.rate_hz(tempo) {
+        Some(rate) => format!("{name} {division_name} ({rate:.2} Hz)"),
+        None => format!("{name} {division_name}"),
+    }
+}
@@ -0,0 +1,74 @@
+//! Triggers notes from the audio input. An envelope follower tracks the input's level, and a note
+//! starts whenever that level rises above a threshold. The note is released again once the level
+//! drops back down.
+
+use enum_iterator::Sequence;
+use nih_plug::params::enums::Enum;
+use nih_plug::util;
+
+/// How far below the threshold the input needs to drop before the note is released, in decibels.
+/// Without this a signal hovering around the threshold would trigger a burst of notes.
+const HYSTERESIS_DB: f32 = 6.0;
+/// The follower reacts to transients almost immediately.
+const ATTACK_MS: f32 = 0.1;
+const RELEASE_MS: f32 = 50.0;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum TriggerNote {
+    /// Always play the trigger note.
+    Fixed,
+    /// Play the last note that was played, or the trigger note if there hasn't been one yet.
+    #[name = "Last Played"]
+    LastPlayed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerEvent {
+    NoteOn,
+    NoteOff,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct InputTrigger {
+    /// The input's current level as a linear gain value.
+    envelope: f32,
+    attack_coefficient: f32,
+    release_coefficient: f32,
+    /// The note that's currently playing because of the input, if any.
+    pub note: Option<u8>,
+}
+
+impl InputTrigger {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            envelope: 0.0,
+            attack_coefficient: (-1000.0 / (ATTACK_MS * sample_rate)).exp(),
+            release_coefficient: (-1000.0 / (RELEASE_MS * sample_rate)).exp(),
+            note: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.envelope = 0.0;
+        self.note = None;
+    }
+
+    /// Feed the next input sample to the envelope follower. Returns an event if the level crossed
+    /// the threshold in either direction. `threshold_db` is the level a note is triggered at.
+    pub fn next_sample(&mut self, sample: f32, threshold_db: f32) -> Option<TriggerEvent> {
+        let level = sample.abs();
+        let coefficient = if level > self.envelope {
+            self.attack_coefficient
+        } else {
+            self.release_coefficient
+        };
+        self.envelope = level + (self.envelope - level) * coefficient;
+
+        let envelope_db = util::gain_to_db(self.envelope);
+        match self.note {
+            None if envelope_db >= threshold_db => Some(TriggerEvent::NoteOn),
+            Some(_) if envelope_db < threshold_db - HYSTERESIS_DB => Some(TriggerEvent::NoteOff),
+            _ => None,
+        }
+    }
+}
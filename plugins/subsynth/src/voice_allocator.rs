@@ -0,0 +1,348 @@
+//! Voice slot allocation. New voices are handed out in round-robin order so every slot gets used
+//! equally, instead of the lowest slots always being reused first. When there's no free slot left,
+//! the oldest voice gets stolen. A number of free slots can be kept in reserve for new notes by
+//! stealing releasing voices before the pool fills up. Voices the host didn't assign an ID to get a
+//! fallback ID from here as well.
+
+use std::ops::Range;
+
+/// Where a new voice should go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Allocation {
+    /// The slot at this index is free.
+    Free(usize),
+    /// All slots are in use, and the voice at this index should make room for the new voice.
+    Stolen(usize),
+}
+
+impl Allocation {
+    pub fn slot(self) -> usize {
+        match self {
+            Allocation::Free(idx) | Allocation::Stolen(idx) => idx,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VoiceAllocator {
+    /// The slot after the one that was handed out last. The next search starts here.
+    next_slot: usize,
+    /// Counts the fallback voice IDs that have been handed out, so they stay unique even when the
+    /// same note gets played again.
+    fallback_voice_counter: u16,
+}
+
+/// Whether an event meant for `event_voice_id` should be applied to a voice. Events with a voice ID
+/// only target the voice with that ID, while events without one target every voice playing the
+/// same note on the same channel.
+pub fn event_targets_voice(
+    event_voice_id: Option<i32>,
+    event_channel: u8,
+    event_note: u8,
+    voice_id: i32,
+    voice_channel: u8,
+    voice_note: u8,
+) -> bool {
+    match event_voice_id {
+        Some(event_voice_id) => event_voice_id == voice_id,
+        None => event_channel == voice_channel && event_note == voice_note,
+    }
+}
+
+impl VoiceAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&mut self) {
+        self.next_slot = 0;
+        self.fallback_voice_counter = 0;
+    }
+
+    /// A voice ID for a voice the host didn't assign one to. The note goes in the lowest byte, the
+    /// channel in the second byte, and a counter in the upper half. The counter is limited to 15
+    /// bits so the IDs are never negative, which means an ID is only reused after 32768 other
+    /// fallback IDs have been handed out.
+    pub fn next_fallback_voice_id(&mut self, note: u8, channel: u8) -> i32 {
+        let counter = self.fallback_voice_counter & 0x7fff;
+        self.fallback_voice_counter = (counter + 1) & 0x7fff;
+
+        (note as i32 & 0x7f) | ((channel as i32 & 0x0f) << 8) | ((counter as i32) << 16)
+    }
+
+    /// Pick a slot within `range` for a new voice. Free slots are searched starting after the last
+    /// allocated slot. If every slot in the range is taken, the voice with the lowest `age` key is
+    /// stolen. While fewer than `reserve` slots are free, the releasing voice with the lowest `age`
+    /// key is stolen instead of using up a free slot. An empty or out of bounds range falls back to
+    /// all of `slots`, so this always returns a valid index as long as `slots` isn't empty.
+    pub fn allocate<T, K: Ord>(
+        &mut self,
+        slots: &[Option<T>],
+        range: Range<usize>,
+        reserve: usize,
+        is_releasing: impl Fn(&T) -> bool,
+        age: impl Fn(&T) -> K,
+    ) -> Allocation {
+        let range = if range.is_empty() || range.end > slots.len() {
+            0..slots.len()
+        } else {
+            range
+        };
+        let len = range.len();
+        let offset = self.next_slot.saturating_sub(range.start) % len.max(1);
+
+        let free_slot = (0..len)
+            .map(|idx| range.start + (offset + idx) % len)
+            .find(|&idx| slots[idx].is_none());
+        let num_free_slots = slots[range.clone()].iter().filter(|slot| slot.is_none()).count();
+        let releasing_slot = if num_free_slots < reserve {
+            range
+                .clone()
+                .filter_map(|idx| match &slots[idx] {
+                    Some(voice) if is_releasing(voice) => Some((age(voice), idx)),
+                    _ => None,
+                })
+                .min_by(|(a, _), (b, _)| a.cmp(b))
+                .map(|(_, idx)| idx)
+        } else {
+            None
+        };
+        let allocation = match (releasing_slot, free_slot) {
+            (Some(idx), _) => Allocation::Stolen(idx),
+            (None, Some(idx)) => Allocation::Free(idx),
+            (None, None) => Allocation::Stolen(
+                range
+                    .clone()
+                    .filter_map(|idx| slots[idx].as_ref().map(|voice| (age(voice), idx)))
+                    .min_by(|(a, _), (b, _)| a.cmp(b))
+                    .map(|(_, idx)| idx)
+                    .unwrap_or(range.start),
+            ),
+        };
+        self.next_slot = allocation.slot() + 1;
+
+        allocation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct TestVoice {
+        voice_id: i32,
+        age: u64,
+        releasing: bool,
+    }
+
+    fn voice(voice_id: i32, age: u64) -> TestVoice {
+        TestVoice {
+            voice_id,
+            age,
+            releasing: false,
+        }
+    }
+
+    fn releasing_voice(voice_id: i32, age: u64) -> TestVoice {
+        TestVoice {
+            releasing: true,
+            ..voice(voice_id, age)
+        }
+    }
+
+    /// Allocate a slot for a voice and put it there, like the synth does.
+    fn play(
+        allocator: &mut VoiceAllocator,
+        slots: &mut [Option<TestVoice>],
+        range: Range<usize>,
+        voice: TestVoice,
+    ) -> Allocation {
+        play_with_reserve(allocator, slots, range, 0, voice)
+    }
+
+    fn play_with_reserve(
+        allocator: &mut VoiceAllocator,
+        slots: &mut [Option<TestVoice>],
+        range: Range<usize>,
+        reserve: usize,
+        voice: TestVoice,
+    ) -> Allocation {
+        let allocation =
+            allocator.allocate(slots, range, reserve, |voice| voice.releasing, |voice| voice.age);
+        slots[allocation.slot()] = Some(voice);
+
+        allocation
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let mut allocator = VoiceAllocator::new();
+        let mut slots = [None; 4];
+
+        let first = play(&mut allocator, &mut slots, 0..4, voice(0, 0));
+        assert_eq!(first, Allocation::Free(0));
+        // Freeing the first slot again doesn't cause it to be reused right away
+        slots[0] = None;
+        let second = play(&mut allocator, &mut slots, 0..4, voice(1, 1));
+        assert_eq!(second, Allocation::Free(1));
+        let third = play(&mut allocator, &mut slots, 0..4, voice(2, 2));
+        assert_eq!(third, Allocation::Free(2));
+    }
+
+    #[test]
+    fn test_round_robin_wraps_and_skips_used_slots() {
+        let mut allocator = VoiceAllocator::new();
+        let mut slots = [None; 4];
+        for age in 0..4 {
+            play(&mut allocator, &mut slots, 0..4, voice(age as i32, age));
+        }
+
+        slots[1] = None;
+        slots[2] = None;
+        let allocation = play(&mut allocator, &mut slots, 0..4, voice(4, 4));
+        assert_eq!(allocation, Allocation::Free(1));
+        let allocation = play(&mut allocator, &mut slots, 0..4, voice(5, 5));
+        assert_eq!(allocation, Allocation::Free(2));
+    }
+
+    #[test]
+    fn test_full_pool_steals_oldest() {
+        let mut allocator = VoiceAllocator::new();
+        let mut slots = [Some(voice(0, 3)), Some(voice(1, 1)), Some(voice(2, 2))];
+
+        let allocation = play(&mut allocator, &mut slots, 0..3, voice(3, 4));
+        assert_eq!(allocation, Allocation::Stolen(1));
+        let allocation = play(&mut allocator, &mut slots, 0..3, voice(4, 5));
+        assert_eq!(allocation, Allocation::Stolen(2));
+    }
+
+    #[test]
+    fn test_reserve_steals_releasing_voices() {
+        let mut allocator = VoiceAllocator::new();
+        let mut slots = [
+            Some(voice(0, 0)),
+            Some(releasing_voice(1, 2)),
+            Some(releasing_voice(2, 1)),
+            None,
+            None,
+        ];
+
+        // Two free slots are enough for a reserve of two
+        let allocation = play_with_reserve(&mut allocator, &mut slots, 0..5, 2, voice(3, 3));
+        assert!(matches!(allocation, Allocation::Free(_)));
+        // With one slot left, the oldest releasing voice makes room instead, never a held one
+        let allocation = play_with_reserve(&mut allocator, &mut slots, 0..5, 2, voice(4, 4));
+        assert_eq!(allocation, Allocation::Stolen(2));
+        let allocation = play_with_reserve(&mut allocator, &mut slots, 0..5, 2, voice(5, 5));
+        assert_eq!(allocation, Allocation::Stolen(1));
+        // Once nothing is releasing anymore, the reserve gets used up
+        let allocation = play_with_reserve(&mut allocator, &mut slots, 0..5, 2, voice(6, 6));
+        assert!(matches!(allocation, Allocation::Free(_)));
+        assert!(slots.iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn test_full_pool_never_panics() {
+        let mut allocator = VoiceAllocator::new();
+        let mut slots = [None; 8];
+        for age in 0..100 {
+            let allocation = play(&mut allocator, &mut slots, 4..8, voice(0, age));
+            assert!((4..8).contains(&allocation.slot()));
+        }
+        assert!(slots[..4].iter().all(Option::is_none));
+
+        // Empty and out of bounds ranges use the whole pool instead
+        let allocation = play(&mut allocator, &mut slots, 3..3, voice(0, 100));
+        assert!(allocation.slot() < 8);
+        let allocation = play(&mut allocator, &mut slots, 4..20, voice(0, 101));
+        assert!(allocation.slot() < 8);
+    }
+
+    #[test]
+    fn test_fallback_voice_ids_are_unique() {
+        let mut allocator = VoiceAllocator::new();
+
+        let mut voice_ids = Vec::new();
+        for _ in 0..4 {
+            for channel in 0..16 {
+                for note in 0..128 {
+                    voice_ids.push(allocator.next_fallback_voice_id(note, channel));
+                }
+            }
+        }
+        assert!(voice_ids.iter().all(|&voice_id| voice_id >= 0));
+
+        let num_voice_ids = voice_ids.len();
+        voice_ids.sort_unstable();
+        voice_ids.dedup();
+        assert_eq!(voice_ids.len(), num_voice_ids);
+    }
+
+    #[test]
+    fn test_fallback_voice_ids_wrap_around() {
+        let mut allocator = VoiceAllocator::new();
+
+        let first = allocator.next_fallback_voice_id(60, 0);
+        for _ in 1..0x8000 {
+            assert_ne!(allocator.next_fallback_voice_id(60, 0), first);
+        }
+        assert_eq!(allocator.next_fallback_voice_id(60, 0), first);
+    }
+
+    #[test]
+    fn test_events_target_the_right_voice() {
+        let mut allocator = VoiceAllocator::new();
+        // The same note played twice without host voice IDs, and once with a host voice ID
+        let voices = [
+            (allocator.next_fallback_voice_id(60, 0), 0, 60),
+            (allocator.next_fallback_voice_id(60, 0), 0, 60),
+            (1234, 1, 60),
+        ];
+        let targets = |voice_id: Option<i32>, channel: u8, note: u8| -> Vec<usize> {
+            voices
+                .iter()
+                .enumerate()
+                .filter(|(_, &(candidate_id, candidate_channel, candidate_note))| {
+                    event_targets_voice(
+                        voice_id,
+                        channel,
+                        note,
+                        candidate_id,
+                        candidate_channel,
+                        candidate_note,
+                    )
+                })
+                .map(|(idx, _)| idx)
+                .collect()
+        };
+
+        // Per-voice events only hit the voice with the matching ID, even when other voices play the
+        // same note
+        assert_eq!(targets(Some(voices[0].0), 0, 60), [0]);
+        assert_eq!(targets(Some(voices[1].0), 0, 60), [1]);
+        assert_eq!(targets(Some(1234), 0, 60), [2]);
+        assert_eq!(targets(Some(4321), 0, 60), [] as [usize; 0]);
+        // Events without an ID fall back to the channel and note
+        assert_eq!(targets(None, 0, 60), [0, 1]);
+        assert_eq!(targets(None, 1, 60), [2]);
+        assert_eq!(targets(None, 0, 61), [] as [usize; 0]);
+    }
+
+    #[test]
+    fn test_duplicate_voice_ids_get_their_own_slots() {
+        let mut allocator = VoiceAllocator::new();
+        let mut slots = [None; 4];
+
+        let first = play(&mut allocator, &mut slots, 0..4, voice(7, 0));
+        let second = play(&mut allocator, &mut slots, 0..4, voice(7, 1));
+        assert_ne!(first.slot(), second.slot());
+        assert!(matches!(second, Allocation::Free(_)));
+        let num_voices = slots
+            .iter()
+            .flatten()
+            .filter(|voice| voice.voice_id == 7)
+            .count();
+        assert_eq!(num_voices, 2);
+    }
+}
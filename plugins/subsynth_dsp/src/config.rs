@@ -0,0 +1,8 @@
+//! Compile time sizes. The `small-footprint` feature lowers these for memory constrained hosts.
+
+/// The number of samples in a single wavetable. A table can hold at most half as many harmonics,
+/// so the smaller tables lose some of the highest harmonics on the lowest notes.
+#[cfg(not(feature = "small-footprint"))]
+pub const WAVETABLE_SIZE: usize = 2048;
+#[cfg(feature = "small-footprint")]
+pub const WAVETABLE_SIZE: usize = 1024;
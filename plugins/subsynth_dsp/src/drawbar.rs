@@ -0,0 +1,120 @@
+//! A tonewheel organ style oscillator. Nine drawbars set the levels of fixed harmonics, and the
+//! percussion adds a quickly decaying second or third harmonic at the start of every note.
+
+use enum_iterator::Sequence;
+use nih_plug::params::enums::Enum;
+
+/// The number of drawbars, the same as on a Hammond organ.
+pub const NUM_DRAWBARS: usize = 9;
+/// The drawbars' labels, as pipe lengths in feet.
+pub const DRAWBAR_NAMES: [&str; NUM_DRAWBARS] = [
+    "16'", "5 1/3'", "8'", "4'", "2 2/3'", "2'", "1 3/5'", "1 1/3'", "1'",
+];
+/// The drawbars' frequencies relative to the 16' drawbar, which sounds an octave below the note.
+const DRAWBAR_HARMONICS: [f64; NUM_DRAWBARS] = [1.0, 3.0, 2.0, 4.0, 6.0, 8.0, 10.0, 12.0, 16.0];
+/// The highest drawbar position. Every position below that is 3 dB quieter.
+pub const MAX_DRAWBAR_LEVEL: i32 = 8;
+
+/// Which harmonic the percussion plays, if any.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum Percussion {
+    Off,
+    /// An octave above the note, like the 4' drawbar.
+    Second,
+    /// An octave and a fifth above the note, like the 2 2/3' drawbar.
+    Third,
+}
+
+impl Percussion {
+    /// The percussion's frequency relative to the 16' drawbar.
+    fn harmonic(&self) -> Option<f64> {
+        match self {
+            Percussion::Off => None,
+            Percussion::Second => Some(4.0),
+            Percussion::Third => Some(6.0),
+        }
+    }
+}
+
+/// The gain for a drawbar position between 0 and [`MAX_DRAWBAR_LEVEL`].
+pub fn drawbar_gain(level: i32) -> f32 {
+    if level <= 0 {
+        0.0
+    } else {
+        10.0f32.powf((level.min(MAX_DRAWBAR_LEVEL) - MAX_DRAWBAR_LEVEL) as f32 * 3.0 / 20.0)
+    }
+}
+
+/// The percussion's settings for a block.
+#[derive(Debug, Clone, Copy)]
+pub struct PercussionSettings {
+    pub percussion: Percussion,
+    pub level: f32,
+    /// What the percussion envelope gets multiplied by every sample.
+    pub decay_per_sample: f32,
+}
+
+/// A voice's organ. All harmonics are computed from the phase of the 16' drawbar, so they stay
+/// locked together.
+#[derive(Debug, Clone, Copy)]
+pub struct DrawbarOrgan {
+    /// The 16' drawbar's phase. Accumulated in double precision for the same reason as the voice's
+    /// own phase.
+    phase: f64,
+    /// The percussion envelope, which starts at 1 when the note starts.
+    percussion_envelope: f32,
+}
+
+impl Default for DrawbarOrgan {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DrawbarOrgan {
+    pub fn new() -> Self {
+        DrawbarOrgan {
+            phase: 0.0,
+            percussion_envelope: 1.0,
+        }
+    }
+
+    /// Render the next sample for a note that advances its phase by `phase_delta` every sample.
+    /// Harmonics that would end up above the Nyquist frequency are left out. The drawbars are
+    /// mixed so that any single drawbar at full level plays at unity gain.
+    pub fn next_sample(
+        &mut self,
+        phase_delta: f32,
+        gains: &[f32; NUM_DRAWBARS],
+        percussion: PercussionSettings,
+    ) -> f32 {
+        let base_delta = phase_delta as f64 * 0.5;
+        let harmonic_sample = |harmonic: f64| -> f32 {
+            if base_delta * harmonic < 0.5 {
+                (self.phase * harmonic * std::f64::consts::TAU).sin() as f32
+            } else {
+                0.0
+            }
+        };
+
+        let mut sample = 0.0;
+        let mut total_gain = 0.0;
+        for (&harmonic, &gain) in DRAWBAR_HARMONICS.iter().zip(gains) {
+            if gain > 0.0 {
+                sample += harmonic_sample(harmonic) * gain;
+                total_gain += gain;
+            }
+        }
+        if let Some(harmonic) = percussion.percussion.harmonic() {
+            sample += harmonic_sample(harmonic) * percussion.level * self.percussion_envelope;
+            self.percussion_envelope *= percussion.decay_per_sample;
+        }
+
+        self.phase += base_delta;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+        }
+
+        sample / f32::max(total_gain, 1.0)
+    }
+}
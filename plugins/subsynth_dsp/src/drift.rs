@@ -0,0 +1,48 @@
+//! Slow random pitch drift to emulate the tuning instability of analog oscillators.
+
+use rand::Rng;
+use rand_pcg::Pcg32;
+use std::f32::consts::PI;
+
+/// A per-voice random walk. Every cycle of the drift rate a new random target is picked, and a
+/// one-pole lowpass filter smooths the jumps between those targets into a slow wander. Every voice
+/// gets its own random number generator seeded from the synth's generator, so the drift is the
+/// same every time a project is rendered after a reset.
+#[derive(Debug, Clone)]
+pub struct Drift {
+    prng: Pcg32,
+    /// The current drift, between -1 and 1.
+    value: f32,
+    target: f32,
+    /// The time left until the next target is picked, in seconds.
+    time_to_next_target: f32,
+}
+
+impl Drift {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        let mut prng = Pcg32::new(rng.gen(), 1337);
+
+        // Like a real analog oscillator, a new voice starts out somewhat detuned already
+        Drift {
+            value: prng.gen_range(-1.0..1.0),
+            target: prng.gen_range(-1.0..1.0),
+            time_to_next_target: 0.0,
+            prng,
+        }
+    }
+
+    /// Get the next drift value, between -1 and 1. `rate` is how often the drift changes direction
+    /// on average, in Hertz.
+    pub fn next_value(&mut self, rate: f32, sample_rate: f32) -> f32 {
+        self.time_to_next_target -= 1.0 / sample_rate;
+        if self.time_to_next_target <= 0.0 {
+            self.target = self.prng.gen_range(-1.0..1.0);
+            self.time_to_next_target = 1.0 / rate;
+        }
+
+        let coefficient = 1.0 - (-2.0 * PI * rate / sample_rate).exp();
+        self.value += (self.target - self.value) * coefficient;
+
+        self.value
+    }
+}
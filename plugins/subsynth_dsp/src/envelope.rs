@@ -0,0 +1,509 @@
+//! The ADSR envelopes used for the amplitude, the filter and the pitch, and the simpler AD
+//! envelope used for the pitch envelope.
+
+use nih_plug::prelude::Enum;
+
+use crate::velocity::VelocityCurve;
+
+/// The interface shared by the envelopes, so the voices can drive them the same way.
+pub trait Envelope {
+    fn get_value(&mut self) -> f32;
+    fn trigger(&mut self);
+    fn release(&mut self);
+    fn set_envelope_stage(&mut self, stage: ADSREnvelopeState);
+    fn get_envelope_stage(&self) -> ADSREnvelopeState;
+    fn set_scale(&mut self, envelope_levels: f32);
+}
+
+/// The shortest attack and release time the ADSR envelope uses, in seconds. Anything shorter would
+/// cause an audible click. This is still short enough to sound instant.
+const MIN_ENVELOPE_TIME: f32 = 0.0005;
+/// The slowest an envelope runs because of the velocity, the same as for the lowest MIDI velocity.
+const MIN_ENVELOPE_VELOCITY: f32 = 1.0 / 127.0;
+
+/// The velocity an ADSR envelope runs with for a note's velocity. The envelopes run faster for
+/// higher velocities. Every envelope has its own sensitivity and curve, and at zero sensitivity the
+/// envelope always runs at the speed it's set to.
+pub fn envelope_velocity(velocity: f32, sensitivity: f32, curve: VelocityCurve) -> f32 {
+    1.0 + (curve.apply(velocity).max(MIN_ENVELOPE_VELOCITY) - 1.0) * sensitivity.clamp(0.0, 1.0)
+}
+
+/// Where the pitch envelope's shape comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum PitchEnvelopeSource {
+    /// A dedicated attack-decay envelope.
+    #[name = "AD Envelope"]
+    Dedicated,
+    /// Reuse the filter cutoff envelope.
+    #[name = "Filter Envelope"]
+    FilterCutoff,
+}
+
+/// A simple attack-decay envelope that rises from 0 to 1 and falls back to 0 again, regardless of
+/// when the note is released. Used for short one-off modulations like pitch blips.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ADEnvelope {
+    /// The attack time in seconds.
+    attack: f32,
+    /// The decay time in seconds.
+    decay: f32,
+    time: f32,
+}
+
+impl ADEnvelope {
+    pub fn new(attack: f32, decay: f32) -> Self {
+        ADEnvelope {
+            attack,
+            decay,
+            time: 0.0,
+        }
+    }
+
+    /// Get the envelope's current value and advance it by one sample.
+    pub fn next_value(&mut self, sample_rate: f32) -> f32 {
+        let value = if self.time < self.attack {
+            self.time / self.attack
+        } else if self.time < self.attack + self.decay {
+            1.0 - (self.time - self.attack) / self.decay
+        } else {
+            0.0
+        };
+        self.time += 1.0 / sample_rate;
+
+        value
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ADSREnvelope {
+    attack: f32,
+    hold: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    state: ADSREnvelopeState,
+    /// The time since the current stage started in seconds, sped up or slowed down by the
+    /// velocity. At the lowest velocities a sample only advances this by a fraction of a
+    /// microsecond, which single precision floats can't add to a few seconds anymore.
+    time: f64,
+    delta_time_per_sample: f32,
+    sample_rate: f32,
+    velocity: f32,
+    /// The envelope level the output gets multiplied by. This is kept apart from the stages so it
+    /// can change every sample. See [`ADSREnvelope::set_scale()`].
+    scale: f32,
+    /// The envelope's value when the release stage was entered. The release ramps down from here,
+    /// since the note can be released before it reached the sustain level.
+    release_level: f32,
+    /// The value the envelope has been frozen at, if any. See [`ADSREnvelope::freeze()`].
+    frozen_value: Option<f32>,
+    /// The value the envelope has been locked at, if any. See [`ADSREnvelope::lock()`].
+    locked_value: Option<f32>,
+    /// Set when the envelope has run out and went idle on its own. Voices are terminated once their
+    /// amp envelope has finished.
+    finished: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Enum)]
+pub enum ADSREnvelopeState {
+    Idle,
+    Attack,
+    Hold,
+    Decay,
+    Sustain,
+    Release,
+}
+
+impl ADSREnvelope {
+    pub fn new(
+        attack: f32,
+        hold: f32,
+        decay: f32,
+        sustain: f32,
+        release: f32,
+        sample_rate: f32,
+        velocity: f32,
+    ) -> Self {
+        ADSREnvelope {
+            attack,
+            hold,
+            decay,
+            sustain,
+            release,
+            state: ADSREnvelopeState::Attack,
+            time: 0.0,
+            sample_rate,
+            delta_time_per_sample: 1.0 / sample_rate,
+            velocity,
+            scale: 1.0,
+            release_level: 0.0,
+            frozen_value: None,
+            locked_value: None,
+            finished: false,
+        }
+    }
+
+    /// Change the velocity the envelope runs with. See [`envelope_velocity()`].
+    pub fn set_velocity(&mut self, velocity: f32) {
+        self.velocity = velocity;
+    }
+
+    pub fn get_time(&mut self) -> f32 {
+        self.time as f32
+    }
+
+    pub fn set_attack(&mut self, attack: f32) {
+        self.attack = attack;
+    }
+
+    pub fn set_decay(&mut self, decay: f32) {
+        self.decay = decay;
+    }
+
+    pub fn set_sustain(&mut self, sustain: f32) {
+        self.sustain = sustain;
+    }
+
+    pub fn set_release(&mut self, release: f32) {
+        self.release = release;
+    }
+
+    pub fn get_state(&self) -> ADSREnvelopeState {
+        self.state
+    }
+
+    /// Whether the envelope has run out. This stays set until the envelope is triggered again or
+    /// moved to another stage.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// How many seconds it takes until the envelope finishes, counting from its release. Envelopes
+    /// that haven't been released yet need their entire release stage. Low velocities slow this
+    /// down, just like they slow down the stages themselves.
+    pub fn remaining_release(&self) -> f32 {
+        let remaining = match self.state {
+            ADSREnvelopeState::Idle => return 0.0,
+            ADSREnvelopeState::Release => self.effective_release() as f64 - self.time,
+            _ => self.effective_release() as f64,
+        };
+
+        // This counts the samples `advance()` needs to get through the rest of the stage
+        let step = (self.delta_time_per_sample * self.velocity) as f64;
+        (remaining / step).ceil().max(0.0) as f32 * self.delta_time_per_sample
+    }
+
+    /// Keep outputting the envelope's current value from now on. The envelope itself keeps running
+    /// through its stages, so it still goes idle at the end of the release stage.
+    pub fn freeze(&mut self) {
+        self.frozen_value = Some(self.previous_value());
+    }
+
+    /// Stop the envelope and keep outputting its sustain level until it's unlocked. Envelopes that
+    /// are already releasing stay at their current value instead so they don't jump back up.
+    pub fn lock(&mut self) {
+        self.locked_value = Some(match self.state {
+            ADSREnvelopeState::Idle | ADSREnvelopeState::Release => self.previous_value(),
+            _ => self.sustain,
+        });
+    }
+
+    /// Let a locked envelope continue from where it was locked.
+    pub fn unlock(&mut self) {
+        self.locked_value = None;
+    }
+
+    pub fn previous_value(&self) -> f32 {
+        if let Some(value) = self.locked_value.or(self.frozen_value) {
+            return value;
+        }
+
+        let time = self.time as f32;
+        match self.state {
+            ADSREnvelopeState::Idle => 0.0,
+            ADSREnvelopeState::Attack => time / self.effective_attack(),
+            // The hold stage holds the attack's peak, the decay stage then falls to the sustain
+            // level
+            ADSREnvelopeState::Hold => 1.0,
+            ADSREnvelopeState::Decay => 1.0 - (1.0 - self.sustain) * self.decay_progress(),
+            ADSREnvelopeState::Sustain => self.sustain,
+            ADSREnvelopeState::Release => {
+                self.release_level * (1.0 - (time / self.effective_release())).max(0.0)
+            }
+        }
+    }
+
+    pub fn advance(&mut self) {
+        if self.locked_value.is_some() {
+            return;
+        }
+
+        // Higher velocities make time pass faster for the envelope. Both the stage transitions
+        // here and the values in `previous_value()` use this same scaled time.
+        self.time += (self.delta_time_per_sample * self.velocity) as f64;
+
+        // Stages that are shorter than a sample are skipped over entirely
+        while let Some(duration) = self.stage_duration() {
+            let duration = duration as f64;
+            if self.time < duration {
+                break;
+            }
+
+            self.time -= duration;
+            self.state = match self.state {
+                ADSREnvelopeState::Attack => ADSREnvelopeState::Hold,
+                ADSREnvelopeState::Hold => ADSREnvelopeState::Decay,
+                ADSREnvelopeState::Decay => ADSREnvelopeState::Sustain,
+                // The envelope only finishes once its release stage has run out. Voices are
+                // terminated when their amp envelope finishes, so this must never happen while the
+                // key is held.
+                _ => {
+                    self.time = 0.0;
+                    self.finished = true;
+                    ADSREnvelopeState::Idle
+                }
+            };
+        }
+    }
+
+    /// How long the current stage lasts in seconds, or `None` for the stages that last until the
+    /// envelope gets triggered or released.
+    fn stage_duration(&self) -> Option<f32> {
+        match self.state {
+            ADSREnvelopeState::Attack => Some(self.effective_attack()),
+            ADSREnvelopeState::Hold => Some(self.hold),
+            ADSREnvelopeState::Decay => Some(self.decay),
+            ADSREnvelopeState::Release => Some(self.effective_release()),
+            // The sustain is a level and not a time, so the sustain stage lasts until the envelope
+            // gets released
+            ADSREnvelopeState::Idle | ADSREnvelopeState::Sustain => None,
+        }
+    }
+
+    /// The attack time in seconds, with a lower limit so instant attacks don't click.
+    fn effective_attack(&self) -> f32 {
+        self.attack.max(MIN_ENVELOPE_TIME)
+    }
+
+    /// How far the decay stage has progressed, from 0 to 1. Instant decays are already done, and
+    /// dividing by their zero length would turn the envelope into NaN.
+    fn decay_progress(&self) -> f32 {
+        if self.decay > 0.0 {
+            (self.time as f32 / self.decay).min(1.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// The release time in seconds, with a lower limit so instant releases don't click.
+    fn effective_release(&self) -> f32 {
+        self.release.max(MIN_ENVELOPE_TIME)
+    }
+
+    pub fn get_attack(&self) -> f32 {
+        self.attack
+    }
+
+    pub fn get_decay(&self) -> f32 {
+        self.decay
+    }
+
+    pub fn get_sustain(&self) -> f32 {
+        self.sustain
+    }
+
+    pub fn get_release(&self) -> f32 {
+        self.release
+    }
+
+    pub fn get_envelope_stage(&self) -> ADSREnvelopeState {
+        self.state
+    }
+
+    // Setter for envelope stage
+    pub fn set_envelope_stage(&mut self, stage: ADSREnvelopeState) {
+        if stage == ADSREnvelopeState::Release {
+            self.release_level = self.previous_value();
+        }
+        // The stage's time is counted from when it was entered
+        self.state = stage;
+        self.time = 0.0;
+        self.finished = false;
+    }
+    /// Set the level [`Envelope::get_value()`]'s output is multiplied by. This only affects the
+    /// output, so the envelope's stages are left untouched.
+    pub fn set_scale(&mut self, envelope_levels: f32) {
+        self.scale = envelope_levels;
+    }
+    pub fn set_hold(&mut self, hold: f32) {
+        self.hold = hold;
+    }
+}
+
+impl Envelope for ADSREnvelope {
+    /// The envelope's value for the current sample, scaled by the envelope level. This doesn't
+    /// move the envelope forward, that's done by [`ADSREnvelope::advance()`].
+    fn get_value(&mut self) -> f32 {
+        self.previous_value() * self.scale
+    }
+
+    fn trigger(&mut self) {
+        self.state = ADSREnvelopeState::Attack;
+        self.time = 0.0;
+        self.frozen_value = None;
+        self.locked_value = None;
+        self.finished = false;
+    }
+
+    fn release(&mut self) {
+        self.set_envelope_stage(ADSREnvelopeState::Release);
+    }
+
+    fn get_envelope_stage(&self) -> ADSREnvelopeState {
+        self.state
+    }
+
+    fn set_envelope_stage(&mut self, stage: ADSREnvelopeState) {
+        self.set_envelope_stage(stage);
+    }
+    fn set_scale(&mut self, envelope_levels: f32) {
+        self.set_scale(envelope_levels);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 1000.0;
+
+    /// Run an envelope for `num_samples` samples the way the voices do, returning the last value.
+    fn run(envelope: &mut ADSREnvelope, num_samples: usize) -> f32 {
+        let mut value = 0.0;
+        for _ in 0..num_samples {
+            envelope.advance();
+            value = envelope.get_value();
+        }
+
+        value
+    }
+
+    #[test]
+    fn test_finishes_after_release() {
+        // A release that's shorter than the attack must not end the note while it's held
+        let mut envelope = ADSREnvelope::new(0.1, 0.0, 0.1, 0.5, 0.01, SAMPLE_RATE, 1.0);
+        run(&mut envelope, 50);
+        assert!(!envelope.is_finished());
+        assert_eq!(envelope.get_state(), ADSREnvelopeState::Attack);
+
+        envelope.release();
+        run(&mut envelope, 5);
+        assert!(!envelope.is_finished());
+        run(&mut envelope, 10);
+        assert!(envelope.is_finished());
+        assert_eq!(envelope.get_state(), ADSREnvelopeState::Idle);
+
+        envelope.trigger();
+        assert!(!envelope.is_finished());
+    }
+
+    #[test]
+    fn test_release_during_attack() {
+        // Releasing a note before it reached the sustain level ramps down from where the attack
+        // was, instead of jumping up to the sustain level first
+        let mut envelope = ADSREnvelope::new(0.1, 0.0, 0.1, 0.8, 0.1, SAMPLE_RATE, 1.0);
+        let attack_value = run(&mut envelope, 20);
+        assert!((attack_value - 0.2).abs() < 1e-6);
+
+        envelope.release();
+        let released_value = run(&mut envelope, 1);
+        assert!(
+            released_value < attack_value && released_value > attack_value * 0.95,
+            "released at {released_value}"
+        );
+        let halfway_value = run(&mut envelope, 49);
+        assert!(
+            (halfway_value - 0.1).abs() < 1e-3,
+            "halfway at {halfway_value}"
+        );
+        run(&mut envelope, 50);
+        assert!(envelope.is_finished());
+    }
+
+    #[test]
+    fn test_holds_sustain_level() {
+        // Holding the note for longer than the attack, decay, and release times combined must
+        // keep the envelope at its sustain level until the note is released
+        let mut envelope = ADSREnvelope::new(0.01, 0.0, 0.02, 0.5, 0.03, SAMPLE_RATE, 1.0);
+        let value = run(&mut envelope, 500);
+        assert_eq!(envelope.get_state(), ADSREnvelopeState::Sustain);
+        assert!(!envelope.is_finished());
+        assert!((value - 0.5).abs() < 1e-6, "held envelope was at {value}");
+
+        envelope.release();
+        run(&mut envelope, 50);
+        assert!(envelope.is_finished());
+    }
+
+    #[test]
+    fn test_scale_does_not_compound() {
+        // The voices set the envelope level every sample, which must not change the stages
+        let mut envelope = ADSREnvelope::new(0.01, 0.0, 0.01, 0.8, 0.01, SAMPLE_RATE, 1.0);
+        for _ in 0..100 {
+            envelope.set_scale(0.5);
+            envelope.advance();
+        }
+
+        assert_eq!(envelope.get_state(), ADSREnvelopeState::Sustain);
+        assert!((envelope.get_value() - 0.4).abs() < 1e-6);
+        assert_eq!(envelope.get_sustain(), 0.8);
+    }
+
+    #[test]
+    fn test_velocity_speeds_up_every_stage() {
+        // The velocity speeds up the stage transitions and the values within the stages alike, so
+        // at twice the speed every stage takes half as long
+        let mut envelope = ADSREnvelope::new(0.1, 0.0, 0.1, 0.5, 0.1, SAMPLE_RATE, 2.0);
+        let expect = |value: f32, expected: f32| {
+            assert!((value - expected).abs() < 1e-3, "expected {expected}, got {value}")
+        };
+
+        expect(run(&mut envelope, 25), 0.5);
+        assert_eq!(envelope.get_state(), ADSREnvelopeState::Attack);
+        expect(run(&mut envelope, 50), 0.75);
+        assert_eq!(envelope.get_state(), ADSREnvelopeState::Decay);
+        expect(run(&mut envelope, 35), 0.5);
+        assert_eq!(envelope.get_state(), ADSREnvelopeState::Sustain);
+
+        envelope.release();
+        expect(run(&mut envelope, 25), 0.25);
+        run(&mut envelope, 30);
+        assert!(envelope.is_finished());
+    }
+
+    #[test]
+    fn test_remaining_release_follows_velocity() {
+        // At the lowest velocity a 0.1 second release takes 127 times as long, give or take a
+        // sample
+        let mut envelope = ADSREnvelope::new(
+            0.01,
+            0.0,
+            0.01,
+            0.5,
+            0.1,
+            SAMPLE_RATE,
+            MIN_ENVELOPE_VELOCITY,
+        );
+        assert!((envelope.remaining_release() - 12.7).abs() < 2.0 / SAMPLE_RATE);
+
+        envelope.release();
+        run(&mut envelope, 6350);
+        assert_eq!(envelope.get_state(), ADSREnvelopeState::Release);
+        let remaining = envelope.remaining_release();
+        assert!((remaining - 6.35).abs() < 0.01, "{remaining} seconds left");
+
+        run(&mut envelope, (remaining * SAMPLE_RATE).ceil() as usize);
+        assert!(envelope.is_finished());
+        assert_eq!(envelope.remaining_release(), 0.0);
+    }
+}
@@ -0,0 +1,403 @@
+//! The filters. [`generate_filter()`] runs a voice's filter for one sample, with the cutoff and
+//! resonance pushed around by the filter envelopes. Every filter type is a response of the voice's
+//! [`MorphingSvf`], so the filters keep their state between samples and even when the filter type
+//! changes.
+
+use enum_iterator::Sequence;
+use nih_plug::params::enums::Enum;
+use std::f32::consts::PI;
+
+use crate::envelope::*;
+
+/// The highest cutoff any filter runs at, as a fraction of the sample rate. The coefficients stop
+/// making sense at the Nyquist frequency.
+const MAX_CUTOFF_RATIO: f32 = 0.49;
+/// The lowest cutoff any filter runs at. Some of the coefficients divide by the cutoff.
+const MIN_CUTOFF_HZ: f32 = 1.0;
+/// The lowest Q the morphing filter accepts. Its damping is the reciprocal of the Q.
+const MIN_SVF_Q: f32 = 0.1;
+
+/// Keep a cutoff frequency within the range every filter can handle. NaN cutoffs end up at the
+/// bottom of the range.
+pub fn clamp_cutoff(cutoff: f32, sample_rate: f32) -> f32 {
+    cutoff.max(MIN_CUTOFF_HZ).min(sample_rate * MAX_CUTOFF_RATIO)
+}
+
+/// The combined filter envelope amount moves the cutoff and the resonance envelopes together, since
+/// patches almost always change them at the same time. Returns the cutoff and the resonance
+/// envelope depths, with `resonance_ratio` setting how much of the amount the resonance gets.
+pub fn filter_envelope_depths(amount: f32, resonance_ratio: f32) -> (f32, f32) {
+    (amount, amount * resonance_ratio)
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum FilterType {
+    None,
+    Lowpass,
+    Bandpass,
+    Highpass,
+    Notch,
+    Statevariable,
+    /// A state variable filter that crossfades between its lowpass, bandpass, highpass, and notch
+    /// outputs using the filter morph parameter.
+    Morph,
+}
+
+/// The Q the filters run at with the resonance all the way up.
+pub const MAX_FILTER_Q: f32 = 10.0;
+
+impl FilterType {
+    /// Map the user facing resonance amount in `[0, 1]` to the filter's Q. This goes from no
+    /// resonance at all at Q = 0.5 to [`MAX_FILTER_Q`].
+    pub fn map_resonance(&self, resonance: f32) -> f32 {
+        let resonance = resonance.clamp(0.0, 1.0);
+        match self {
+            FilterType::None => 0.0,
+            // The notch's bandwidth goes from 1.5 times the cutoff for a wide dip to a tenth of the
+            // cutoff for a narrow notch
+            FilterType::Notch => 1.0 / (1.5 - resonance * 1.4),
+            FilterType::Lowpass
+            | FilterType::Bandpass
+            | FilterType::Highpass
+            | FilterType::Statevariable
+            | FilterType::Morph => 0.5 + resonance * (MAX_FILTER_Q - 0.5),
+        }
+    }
+}
+
+/// A trapezoidal integrated state variable filter, based on Andrew Simper's (Cytomic) SVF. It stays
+/// stable when the cutoff changes every sample, and it produces all of its responses at once. That
+/// makes it possible to continuously morph between them.
+///
+/// The state is stored in double precision so the filter can switch between [`process()`] and
+/// [`process_f64()`] at any time. The single precision version rounds it to `f32` every sample.
+///
+/// [`process()`]: MorphingSvf::process
+/// [`process_f64()`]: MorphingSvf::process_f64
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MorphingSvf {
+    ic1eq: f64,
+    ic2eq: f64,
+}
+
+impl MorphingSvf {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Filter a single sample. `morph` goes from lowpass at 0.0, through bandpass and highpass, to
+    /// a notch at 1.0. All responses have unity gain at the cutoff frequency (or in the passband
+    /// for the notch) so the crossfades don't jump in level.
+    pub fn process(
+        &mut self,
+        input: f32,
+        cutoff: f32,
+        q: f32,
+        morph: f32,
+        sample_rate: f32,
+    ) -> f32 {
+        morph_responses(self.responses(input, cutoff, q, sample_rate), morph)
+    }
+
+    /// The same as [`process()`][Self::process()], but with the coefficients and the state computed
+    /// in double precision.
+    pub fn process_f64(
+        &mut self,
+        input: f32,
+        cutoff: f32,
+        q: f32,
+        morph: f32,
+        sample_rate: f32,
+    ) -> f32 {
+        morph_responses(self.responses_f64(input, cutoff, q, sample_rate), morph)
+    }
+
+    /// Filter a single sample, returning the lowpass, bandpass, highpass, and notch responses.
+    pub fn responses(&mut self, input: f32, cutoff: f32, q: f32, sample_rate: f32) -> [f32; 4] {
+        let cutoff = clamp_cutoff(cutoff, sample_rate);
+        let g = (PI * cutoff / sample_rate).tan();
+        let k = 1.0 / q.max(MIN_SVF_Q);
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+
+        let ic1eq = self.ic1eq as f32;
+        let ic2eq = self.ic2eq as f32;
+        let v3 = input - ic2eq;
+        let v1 = a1 * ic1eq + a2 * v3;
+        let v2 = ic2eq + a2 * ic1eq + a3 * v3;
+        self.ic1eq = (2.0 * v1 - ic1eq) as f64;
+        self.ic2eq = (2.0 * v2 - ic2eq) as f64;
+
+        let lowpass = v2;
+        let bandpass = k * v1;
+        let highpass = input - k * v1 - v2;
+        let notch = input - k * v1;
+        [lowpass, bandpass, highpass, notch]
+    }
+
+    /// The same as [`responses()`][Self::responses()], but in double precision.
+    pub fn responses_f64(
+        &mut self,
+        input: f32,
+        cutoff: f32,
+        q: f32,
+        sample_rate: f32,
+    ) -> [f32; 4] {
+        let input = input as f64;
+        let sample_rate = sample_rate as f64;
+        let cutoff = clamp_cutoff(cutoff, sample_rate as f32) as f64;
+        let g = (std::f64::consts::PI * cutoff / sample_rate).tan();
+        let k = 1.0 / q.max(MIN_SVF_Q) as f64;
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+
+        let v3 = input - self.ic2eq;
+        let v1 = a1 * self.ic1eq + a2 * v3;
+        let v2 = self.ic2eq + a2 * self.ic1eq + a3 * v3;
+        self.ic1eq = 2.0 * v1 - self.ic1eq;
+        self.ic2eq = 2.0 * v2 - self.ic2eq;
+
+        let lowpass = v2;
+        let bandpass = k * v1;
+        let highpass = input - k * v1 - v2;
+        let notch = input - k * v1;
+        [lowpass, bandpass, highpass, notch].map(|response| response as f32)
+    }
+}
+
+/// Crossfade between the morphing filter's lowpass, bandpass, highpass, and notch responses.
+fn morph_responses(responses: [f32; 4], morph: f32) -> f32 {
+    let position = morph.clamp(0.0, 1.0) * (responses.len() - 1) as f32;
+    let index = (position as usize).min(responses.len() - 2);
+    let t = position - index as f32;
+    responses[index] * (1.0 - t) + responses[index + 1] * t
+}
+
+/// A 6 dB per octave lowpass, used as the voices' tone control after the main filter. This uses the
+/// same trapezoidal integration as [`MorphingSvf`], so the cutoff can change every sample.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OnePoleLowpass {
+    ic1eq: f32,
+}
+
+impl OnePoleLowpass {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn process(&mut self, input: f32, cutoff: f32, sample_rate: f32) -> f32 {
+        let cutoff = clamp_cutoff(cutoff, sample_rate);
+        let g = (PI * cutoff / sample_rate).tan();
+        let v = (input - self.ic1eq) * g / (1.0 + g);
+        let output = v + self.ic1eq;
+        self.ic1eq = output + v;
+
+        output
+    }
+}
+
+/// Removes the DC offset from a signal with a very low highpass filter.
+#[derive(Debug, Clone, Copy)]
+pub struct DCBlocker {
+    x1: f32,
+    y1: f32,
+    r: f32,
+}
+
+impl Default for DCBlocker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DCBlocker {
+    pub fn new() -> Self {
+        DCBlocker {
+            x1: 0.0,
+            y1: 0.0,
+            r: 0.995, // The closer this value to 1.0, the lower the cutoff frequency
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.y1 = input - self.x1 + self.r * self.y1;
+        self.x1 = input;
+        self.y1
+    }
+}
+
+/// Run a voice's filter for one sample. The filter envelopes are advanced by one sample, and their
+/// depths set how far they move the cutoff and the resonance, see [`filter_envelope_depths()`].
+#[allow(clippy::too_many_arguments)]
+pub fn generate_filter(
+    filter_type: FilterType,
+    cutoff: f32,
+    resonance: f32,
+    filter_cut_envelope: &mut ADSREnvelope,
+    filter_res_envelope: &mut ADSREnvelope,
+    svf: &mut MorphingSvf,
+    morph: f32,
+    (cutoff_envelope_depth, resonance_envelope_depth): (f32, f32),
+    double_precision: bool,
+    input: f32,
+    sample_rate: f32,
+) -> f32 {
+    filter_cut_envelope.advance();
+    filter_res_envelope.advance();
+    // The envelope depths blend between no envelope at 0 and the full envelope at 1
+    let envelope =
+        (1.0 + (filter_cut_envelope.get_value() - 1.0) * cutoff_envelope_depth).max(0.0);
+    let resonance_envelope =
+        1.0 + (filter_res_envelope.get_value() - 1.0) * resonance_envelope_depth;
+    // The cutoff can't go past the Nyquist frequency, no matter how far the envelope pushes it
+    let filter_cut = clamp_cutoff(envelope * cutoff, sample_rate);
+    let filter_res = filter_type.map_resonance(resonance_envelope * resonance);
+
+    let responses = match filter_type {
+        FilterType::None => return input,
+        _ if double_precision => svf.responses_f64(input, filter_cut, filter_res, sample_rate),
+        _ => svf.responses(input, filter_cut, filter_res, sample_rate),
+    };
+    let [lowpass, bandpass, highpass, notch] = responses;
+    match filter_type {
+        FilterType::None => input,
+        FilterType::Lowpass => lowpass,
+        // The state variable filter type has always been a bandpass
+        FilterType::Bandpass | FilterType::Statevariable => bandpass,
+        FilterType::Highpass => highpass,
+        FilterType::Notch => notch,
+        FilterType::Morph => morph_responses(responses, morph),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATES: [f32; 4] = [22_050.0, 44_100.0, 96_000.0, 192_000.0];
+    /// Cutoffs from below zero to far past the Nyquist frequency.
+    const CUTOFFS: [f32; 8] = [-100.0, 0.0, 1.0, 20.0, 1_000.0, 20_000.0, 90_000.0, 1e9];
+    const RESONANCES: [f32; 5] = [-1.0, 0.0, 0.5, 1.0, 2.0];
+    const MORPHS: [f32; 3] = [0.0, 0.5, 1.0];
+
+    /// A full scale square wave at the filter's cutoff gets amplified by up to the filter's Q, and
+    /// the square wave's fundamental is `4 / PI` times as loud as the square wave itself.
+    const MAX_PEAK: f32 = 4.0 / PI * MAX_FILTER_Q;
+
+    /// Run a filter the same way the voices use it, with the envelopes turned off so it stays at
+    /// `cutoff`. Panics if the output isn't finite.
+    fn run_filter(
+        filter_type: FilterType,
+        (cutoff, resonance, morph): (f32, f32, f32),
+        double_precision: bool,
+        sample_rate: f32,
+        input: impl Iterator<Item = f32>,
+    ) -> Vec<f32> {
+        let mut filter_cut_envelope = ADSREnvelope::new(0.0, 0.0, 0.0, 1.0, 0.0, sample_rate, 1.0);
+        let mut filter_res_envelope = filter_cut_envelope;
+        let mut svf = MorphingSvf::new();
+
+        input
+            .map(|input| {
+                let output = generate_filter(
+                    filter_type,
+                    cutoff,
+                    resonance,
+                    &mut filter_cut_envelope,
+                    &mut filter_res_envelope,
+                    &mut svf,
+                    morph,
+                    (0.0, 0.0),
+                    double_precision,
+                    input,
+                    sample_rate,
+                );
+                assert!(
+                    output.is_finite(),
+                    "{filter_type:?} at {cutoff} Hz with resonance {resonance}, morph {morph}, \
+                     and a {sample_rate} Hz sample rate produced {output}"
+                );
+
+                output
+            })
+            .collect()
+    }
+
+    /// A full scale sine wave.
+    fn sine(frequency: f32, sample_rate: f32, num_samples: usize) -> impl Iterator<Item = f32> {
+        (0..num_samples).map(move |idx| (2.0 * PI * frequency * idx as f32 / sample_rate).sin())
+    }
+
+    /// The RMS level of the second half of a signal, after the filter has settled.
+    fn settled_rms(signal: &[f32]) -> f32 {
+        let settled = &signal[signal.len() / 2..];
+        (settled.iter().map(|sample| sample * sample).sum::<f32>() / settled.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_extreme_settings_stay_bounded() {
+        for filter_type in enum_iterator::all::<FilterType>() {
+            for sample_rate in SAMPLE_RATES {
+                for (cutoff, resonance, morph) in CUTOFFS.into_iter().flat_map(|cutoff| {
+                    RESONANCES.into_iter().flat_map(move |resonance| {
+                        MORPHS.into_iter().map(move |morph| (cutoff, resonance, morph))
+                    })
+                }) {
+                    for double_precision in [false, true] {
+                        let square =
+                            (0..4096).map(|idx| if (idx / 50) % 2 == 0 { 1.0 } else { -1.0 });
+                        let peak = run_filter(
+                            filter_type,
+                            (cutoff, resonance, morph),
+                            double_precision,
+                            sample_rate,
+                            square,
+                        )
+                        .into_iter()
+                        .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+                        assert!(
+                            peak <= MAX_PEAK,
+                            "{filter_type:?} at {cutoff} Hz with resonance {resonance}, morph \
+                             {morph}, and a {sample_rate} Hz sample rate peaked at {peak}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_responses() {
+        const SAMPLE_RATE: f32 = 48_000.0;
+        let rms = |filter_type, resonance, frequency| {
+            let settings = (1_000.0, resonance, 0.0);
+            let input = sine(frequency, SAMPLE_RATE, 9600);
+            settled_rms(&run_filter(filter_type, settings, false, SAMPLE_RATE, input))
+        };
+        let full_scale = std::f32::consts::FRAC_1_SQRT_2;
+
+        // Two octaves away from the cutoff the 12 dB per octave slopes take off about 24 dB
+        assert!(rms(FilterType::Lowpass, 0.0, 4_000.0) < full_scale / 10.0);
+        assert!(rms(FilterType::Lowpass, 0.0, 100.0) > full_scale * 0.95);
+        assert!(rms(FilterType::Highpass, 0.0, 250.0) < full_scale / 10.0);
+        assert!(rms(FilterType::Highpass, 0.0, 10_000.0) > full_scale * 0.95);
+        assert!(rms(FilterType::Bandpass, 0.0, 1_000.0) > full_scale * 0.95);
+        assert!(rms(FilterType::Bandpass, 1.0, 2_000.0) < full_scale / 10.0);
+        assert!(rms(FilterType::Notch, 1.0, 1_000.0) < full_scale / 10.0);
+        assert!(rms(FilterType::Notch, 1.0, 2_000.0) > full_scale * 0.9);
+
+        // The resonance boosts the cutoff frequency by the filter's Q
+        let boost = rms(FilterType::Lowpass, 1.0, 1_000.0) / full_scale;
+        assert!((boost - MAX_FILTER_Q).abs() < 0.5, "boosted by {boost}");
+    }
+
+    #[test]
+    fn test_clamp_cutoff() {
+        assert_eq!(clamp_cutoff(0.0, 48_000.0), MIN_CUTOFF_HZ);
+        assert_eq!(clamp_cutoff(f32::NAN, 48_000.0), MIN_CUTOFF_HZ);
+        assert_eq!(clamp_cutoff(1_000.0, 48_000.0), 1_000.0);
+        assert_eq!(clamp_cutoff(f32::INFINITY, 48_000.0), 48_000.0 * MAX_CUTOFF_RATIO);
+    }
+}
@@ -0,0 +1,88 @@
+//! Gliding from one note's pitch to the next.
+
+use enum_iterator::Sequence;
+use nih_plug::params::enums::Enum;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum GlideMode {
+    Off,
+    Portamento,
+    Glissando,
+}
+
+/// Scale the base glide time by the interval between the two notes and by the new note's velocity.
+/// With full key tracking the base glide time is the time it takes to glide a full octave, so
+/// smaller intervals glide faster and larger ones slower. With full velocity tracking harder hit
+/// notes glide faster.
+pub fn scaled_glide_time(
+    glide_time: f32,
+    start_note: f32,
+    target_note: f32,
+    velocity: f32,
+    key_tracking: f32,
+    velocity_tracking: f32,
+) -> f32 {
+    let octaves = (target_note - start_note).abs() / 12.0;
+    let key_scale = 1.0 + (octaves - 1.0) * key_tracking;
+    let velocity_scale = 1.0 - velocity * velocity_tracking;
+
+    glide_time * key_scale * velocity_scale
+}
+
+/// A pitch transition between two notes. The pitch is tracked in (fractional) MIDI note numbers so
+/// the glide moves at a constant rate in semitones.
+#[derive(Debug, Clone, Copy)]
+pub struct Glide {
+    mode: GlideMode,
+    start_note: f32,
+    target_note: f32,
+    current_note: f32,
+    step: f32,
+}
+
+impl Glide {
+    pub fn new(
+        mode: GlideMode,
+        start_note: f32,
+        target_note: f32,
+        glide_time: f32,
+        sample_rate: f32,
+    ) -> Self {
+        let num_samples = (glide_time * sample_rate).max(1.0);
+
+        Glide {
+            mode,
+            start_note,
+            target_note,
+            current_note: start_note,
+            step: (target_note - start_note) / num_samples,
+        }
+    }
+
+    /// The note the glide is currently at, without glissando's quantization.
+    pub fn current_note(&self) -> f32 {
+        self.current_note
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current_note == self.target_note
+    }
+
+    /// Advance the glide by one sample and return the note the voice should currently play at. In
+    /// glissando mode this steps through the intermediate semitones instead of sliding smoothly.
+    pub fn next_note(&mut self) -> f32 {
+        self.current_note += self.step;
+        if (self.step >= 0.0 && self.current_note >= self.target_note)
+            || (self.step < 0.0 && self.current_note <= self.target_note)
+        {
+            self.current_note = self.target_note;
+        }
+
+        match self.mode {
+            GlideMode::Glissando if !self.is_finished() => {
+                self.start_note + (self.current_note - self.start_note).trunc()
+            }
+            _ => self.current_note,
+        }
+    }
+}
@@ -0,0 +1,17 @@
+//! SubSynth's DSP building blocks: the oscillators, filters, envelopes, LFOs, glide, and stereo
+//! effects. Nothing in here talks to the host. Everything runs on plain samples, note numbers and
+//! sample rates, so these can be reused outside of the synth, for instance in an effect plugin.
+//!
+//! The enums derive nih-plug's `Enum` trait so a plugin can use them as `EnumParam`s as they are.
+
+pub mod config;
+pub mod drawbar;
+pub mod drift;
+pub mod envelope;
+pub mod filter;
+pub mod glide;
+pub mod modulator;
+pub mod stereo;
+pub mod velocity;
+pub mod waveform;
+pub mod wavetable;
@@ -0,0 +1,211 @@
+//! The LFOs.
+
+use std::f32::consts::PI;
+use enum_iterator::Sequence;
+use nih_plug::params::enums::Enum;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum OscillatorShape {
+    Sine,
+    Triangle,
+    Sawtooth,
+    Square,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum LfoMode {
+    /// The LFO keeps running with a global phase, notes pick up wherever it currently is.
+    Free,
+    /// The LFO's phase restarts on every note-on.
+    #[name = "Key Sync"]
+    KeySync,
+    /// The LFO runs a single cycle after note-on and then holds its last value, like a simple
+    /// envelope.
+    #[name = "One Shot"]
+    OneShot,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum LfoScope {
+    /// Every voice runs its own LFO with an independent phase.
+    #[name = "Per Voice"]
+    PerVoice,
+    /// A single LFO shared by all voices, modulating them in unison.
+    Global,
+}
+
+/// The note length an LFO cycle is synced to, if any.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum SyncDivision {
+    Off,
+    #[name = "1/1"]
+    Whole,
+    #[name = "1/2"]
+    Half,
+    #[name = "1/4"]
+    Quarter,
+    #[name = "1/8"]
+    Eighth,
+    #[name = "1/16"]
+    Sixteenth,
+    #[name = "1/32"]
+    ThirtySecond,
+    #[name = "1/4T"]
+    QuarterTriplet,
+    #[name = "1/8T"]
+    EighthTriplet,
+    #[name = "1/16T"]
+    SixteenthTriplet,
+}
+
+impl SyncDivision {
+    /// The length of one cycle in quarter notes, or `None` when tempo sync is disabled.
+    pub fn beats(&self) -> Option<f64> {
+        match self {
+            SyncDivision::Off => None,
+            SyncDivision::Whole => Some(4.0),
+            SyncDivision::Half => Some(2.0),
+            SyncDivision::Quarter => Some(1.0),
+            SyncDivision::Eighth => Some(0.5),
+            SyncDivision::Sixteenth => Some(0.25),
+            SyncDivision::ThirtySecond => Some(0.125),
+            SyncDivision::QuarterTriplet => Some(2.0 / 3.0),
+            SyncDivision::EighthTriplet => Some(1.0 / 3.0),
+            SyncDivision::SixteenthTriplet => Some(1.0 / 6.0),
+        }
+    }
+
+    /// The rate in Hertz at the given tempo, or `None` when tempo sync is disabled.
+    pub fn rate_hz(&self, tempo: f64) -> Option<f32> {
+        self.beats().map(|beats| (tempo / 60.0 / beats) as f32)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Modulator {
+    modulation_rate: f32,
+    peak_intensity: f32,
+    attack_duration: f32,
+    oscillator_shape: OscillatorShape,
+    mode: LfoMode,
+    current_time: f32,
+    phase: f32,
+    triggered: bool,
+    /// Multiplies the rate, set by the mod matrix.
+    rate_scale: f32,
+    /// The oscillator's last output between -1 and 1, without the intensity applied.
+    last_value: f32,
+}
+
+impl Modulator {
+    pub fn new(
+        modulation_rate: f32,
+        peak_intensity: f32,
+        attack_duration: f32,
+        oscillator_shape: OscillatorShape,
+        mode: LfoMode,
+    ) -> Self {
+        Modulator {
+            modulation_rate,
+            peak_intensity,
+            attack_duration,
+            oscillator_shape,
+            mode,
+            current_time: 0.0,
+            phase: 0.0,
+            triggered: true,
+            rate_scale: 1.0,
+            last_value: 0.0,
+        }
+    }
+
+    pub fn trigger(&mut self) {
+        self.current_time = 0.0;
+        self.triggered = true;
+        if self.mode != LfoMode::Free {
+            self.phase = 0.0;
+        }
+    }
+
+    pub fn phase(&self) -> f32 {
+        self.phase
+    }
+
+    /// Set the oscillator's phase. Used to pick up the global phase for free running LFOs.
+    pub fn set_phase(&mut self, phase: f32) {
+        self.phase = phase;
+    }
+
+    /// Speed up or slow down the LFO relative to its rate, for modulating it through the mod
+    /// matrix.
+    pub fn set_rate_scale(&mut self, rate_scale: f32) {
+        self.rate_scale = rate_scale;
+    }
+
+    /// The oscillator's output from the last [`get_modulation()`][Self::get_modulation()] call,
+    /// between -1 and 1 and without the intensity or attack applied.
+    pub fn last_value(&self) -> f32 {
+        self.last_value
+    }
+
+    /// Update the LFO's settings without resetting its phase. Used for the global LFOs, which are
+    /// never retriggered.
+    pub fn set_parameters(
+        &mut self,
+        modulation_rate: f32,
+        peak_intensity: f32,
+        oscillator_shape: OscillatorShape,
+    ) {
+        self.modulation_rate = modulation_rate;
+        self.peak_intensity = peak_intensity;
+        self.oscillator_shape = oscillator_shape;
+    }
+
+    fn update(&mut self, dt: f32) {
+        if self.triggered {
+            self.current_time += dt;
+            self.current_time = self.current_time.min(self.attack_duration); // Clamp current time to attack duration
+            if self.current_time >= self.attack_duration {
+                self.triggered = false;
+            }
+        } else {
+            self.current_time += dt; // Continue incrementing the current time even after the attack duration
+        }
+
+        self.phase += self.modulation_rate * self.rate_scale * dt;
+        if self.mode == LfoMode::OneShot {
+            self.phase = self.phase.min(1.0);
+        } else if self.phase >= 1.0 {
+            self.phase = self.phase.fract();
+        }
+    }
+
+    pub fn get_modulation(&mut self, sample_rate: f32) -> f32 {
+        let dt = 1.0 / sample_rate;
+        self.update(dt);
+
+        let attack_progress = self.current_time / self.attack_duration;
+        let intensity = if attack_progress < 1.0 {
+            self.peak_intensity * attack_progress
+        } else {
+            self.peak_intensity
+        };
+
+        let modulation = match self.oscillator_shape {
+            OscillatorShape::Sine => (2.0 * PI * self.phase).sin(),
+            OscillatorShape::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            OscillatorShape::Sawtooth => self.phase * 2.0 - 1.0,
+            OscillatorShape::Square => {
+                if self.phase >= 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+        };
+        self.last_value = modulation;
+
+        modulation * intensity
+    }
+
+}
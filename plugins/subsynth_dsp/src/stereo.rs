@@ -0,0 +1,114 @@
+//! Stereo width, the Haas effect, and the correlation meter used to check widened patches for mono
+//! compatibility.
+
+/// How long it takes for the correlation meter to respond to a change, in milliseconds.
+const CORRELATION_TIME_MS: f32 = 300.0;
+
+/// Change a stereo pair's width through mid/side processing. A width of 0 results in mono, 1 leaves
+/// the signal unchanged, and 2 doubles the side signal.
+pub fn apply_width(left: f32, right: f32, width: f32) -> (f32, f32) {
+    let mid = (left + right) * 0.5;
+    let side = (left - right) * 0.5 * width;
+
+    (mid + side, mid - side)
+}
+
+/// Measures the correlation between the left and right channels. The result ranges from -1 for
+/// fully out of phase channels, through 0 for unrelated channels, to 1 for mono.
+#[derive(Debug, Clone, Copy)]
+pub struct CorrelationMeter {
+    /// The smoothed products of the two channels.
+    left_right: f32,
+    left_left: f32,
+    right_right: f32,
+    /// The weight of the previous sums for every new sample.
+    weight: f32,
+}
+
+impl CorrelationMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            left_right: 0.0,
+            left_left: 0.0,
+            right_right: 0.0,
+            weight: (-1000.0 / (CORRELATION_TIME_MS * sample_rate)).exp(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.left_right = 0.0;
+        self.left_left = 0.0;
+        self.right_right = 0.0;
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) {
+        let new_weight = 1.0 - self.weight;
+        self.left_right = self.left_right * self.weight + left * right * new_weight;
+        self.left_left = self.left_left * self.weight + left * left * new_weight;
+        self.right_right = self.right_right * self.weight + right * right * new_weight;
+    }
+
+    /// The current correlation. Silence counts as mono.
+    pub fn correlation(&self) -> f32 {
+        let energy = (self.left_left * self.right_right).sqrt();
+        if energy > 1e-10 {
+            (self.left_right / energy).clamp(-1.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// The longest delay the Haas effect can use, in milliseconds.
+pub const MAX_HAAS_DELAY_MS: f32 = 20.0;
+
+/// A short delay line for the Haas effect. Every voice slot gets its own, allocated ahead of time
+/// since the length depends on the sample rate.
+#[derive(Debug, Clone)]
+pub struct HaasDelay {
+    buffer: Vec<f32>,
+    write_pos: usize,
+    /// The internal ID of the voice whose audio is in the buffer. When a new voice takes over the
+    /// slot, the buffer is cleared so the previous voice's audio doesn't leak into it.
+    owner: Option<u64>,
+}
+
+impl HaasDelay {
+    pub fn new(sample_rate: f32) -> Self {
+        // Two extra samples leave room for the interpolation at the maximum delay
+        let len = (MAX_HAAS_DELAY_MS / 1000.0 * sample_rate).ceil() as usize + 2;
+
+        Self {
+            buffer: vec![0.0; len],
+            write_pos: 0,
+            owner: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.write_pos = 0;
+        self.owner = None;
+    }
+
+    /// Write a sample for the voice with the given internal ID, and read the linearly interpolated
+    /// sample from `delay_samples` ago.
+    pub fn process(&mut self, voice_id: u64, input: f32, delay_samples: f32) -> f32 {
+        if self.owner != Some(voice_id) {
+            self.reset();
+            self.owner = Some(voice_id);
+        }
+
+        let len = self.buffer.len();
+        self.buffer[self.write_pos] = input;
+
+        let delay_samples = delay_samples.clamp(0.0, (len - 2) as f32);
+        let whole_samples = delay_samples as usize;
+        let fraction = delay_samples - whole_samples as f32;
+        let newer = self.buffer[(self.write_pos + len - whole_samples) % len];
+        let older = self.buffer[(self.write_pos + len - whole_samples - 1) % len];
+        self.write_pos = (self.write_pos + 1) % len;
+
+        newer + (older - newer) * fraction
+    }
+}
@@ -0,0 +1,37 @@
+//! Velocity response curves, shared by the envelopes and the plugin's input transform.
+
+use enum_iterator::Sequence;
+use nih_plug::prelude::Enum;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum VelocityCurve {
+    Linear,
+    /// Light touches play louder, for stiff keyboards.
+    Soft,
+    /// Light touches play quieter, for keyboards that are too sensitive.
+    Hard,
+}
+
+impl VelocityCurve {
+    pub fn apply(self, velocity: f32) -> f32 {
+        let velocity = velocity.clamp(0.0, 1.0);
+        match self {
+            VelocityCurve::Linear => velocity,
+            VelocityCurve::Soft => velocity.sqrt(),
+            VelocityCurve::Hard => velocity * velocity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curves() {
+        assert_eq!(VelocityCurve::Linear.apply(0.25), 0.25);
+        assert_eq!(VelocityCurve::Soft.apply(0.25), 0.5);
+        assert_eq!(VelocityCurve::Hard.apply(0.5), 0.25);
+        assert_eq!(VelocityCurve::Soft.apply(2.0), 1.0);
+    }
+}
@@ -0,0 +1,550 @@
+//! The oscillators. [`generate_waveform()`] computes a single sample of the basic waveforms, the
+//! supersaw and the phase distortion oscillator keep their own state.
+
+use enum_iterator::Sequence;
+use nih_plug::params::enums::Enum;
+use rand::Rng;
+
+/// The number of detuned sawtooth oscillators in a supersaw.
+pub const SUPERSAW_OSCILLATORS: usize = 7;
+
+/// The relative detune of every sawtooth in the supersaw stack, scaled by the detune amount. These
+/// follow the (slightly asymmetric) spread of the original JP-8000 supersaw.
+const SUPERSAW_SPREAD: [f32; SUPERSAW_OSCILLATORS] = [
+    -0.110_023_13,
+    -0.062_884_39,
+    -0.019_523_56,
+    0.0,
+    0.019_912_21,
+    0.062_165_38,
+    0.107_452_42,
+];
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum Waveform {
+    Sine,
+    Triangle,
+    Sawtooth,
+    Square,
+    Pulse,
+    Noise,
+    Supersaw,
+    /// A Casio CZ style phase distortion oscillator, see [`phase_distortion()`].
+    #[name = "Phase Distortion"]
+    PhaseDistortion,
+    /// A tonewheel organ with nine drawbars, see [`DrawbarOrgan`][crate::drawbar::DrawbarOrgan].
+    Drawbar,
+}
+
+/// How the phase distortion oscillator warps its phase.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum PdCurve {
+    /// Sweeps from a sine to a sawtooth.
+    Sawtooth,
+    /// Sweeps from a sine to a square.
+    Square,
+    /// A windowed sine at a higher frequency, sounding like a resonant filter sweep.
+    Resonant,
+}
+
+/// What the voices run through their filters.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum OscSource {
+    Oscillator,
+    /// The main audio input, gated by the notes. The filter's cutoff follows the note, so this
+    /// turns the synth into a playable filter bank.
+    #[name = "Audio Input"]
+    AudioInput,
+}
+
+/// How the oscillators' discontinuities are smoothed out to reduce aliasing.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Enum, Sequence)]
+pub enum OscQuality {
+    /// A polyBLEP residual spanning two samples.
+    Standard,
+    /// A polyBLEP residual spanning four samples, based on a cubic B-spline. This aliases less on
+    /// high notes at a slightly higher CPU cost.
+    High,
+    /// The high quality polyBLEP, with the filter computed in double precision. This avoids
+    /// coefficient quantization at very low cutoffs relative to the sample rate, like 30 Hz at
+    /// 192 kHz. The sine is computed exactly instead of approximated.
+    Ultra,
+}
+
+impl OscQuality {
+    /// Whether the filter should run in double precision.
+    pub fn double_precision_filter(&self) -> bool {
+        *self == OscQuality::Ultra
+    }
+
+    /// Whether the sine should use `sin()` instead of [`fast_sine()`].
+    pub fn precise_sine(&self) -> bool {
+        *self == OscQuality::Ultra
+    }
+}
+
+/// Wrap a phase to the `[0, 1)` range. Unlike `%`, this also wraps negative phases.
+pub fn wrap_phase(phase: f32) -> f32 {
+    let wrapped = phase.rem_euclid(1.0);
+    // `rem_euclid()` rounds tiny negative phases up to exactly 1
+    if wrapped >= 1.0 {
+        0.0
+    } else {
+        wrapped
+    }
+}
+
+/// A sine wave at a phase in `[0, 1)`, approximated with a polynomial. This takes about half as
+/// long as `sin()`, and the error stays below 1e-5, or about -100 dB.
+pub fn fast_sine(phase: f32) -> f32 {
+    // Fold the cycle onto the rising quarter, from -1 at the trough to 1 at the peak, so only the
+    // odd Taylor series of `sin(x * PI / 2)` between -1 and 1 is needed
+    let x = if phase < 0.25 {
+        4.0 * phase
+    } else if phase < 0.75 {
+        2.0 - 4.0 * phase
+    } else {
+        4.0 * phase - 4.0
+    };
+    let x2 = x * x;
+
+    x * (std::f32::consts::FRAC_PI_2
+        + x2 * (-0.645_964_1 + x2 * (0.079_692_63 + x2 * (-0.004_681_754 + x2 * 0.000_160_441_2))))
+}
+
+/// Compute a sine at a phase in `[0, 1)`. Only the ultra quality uses the exact sine.
+pub fn sine(phase: f32, quality: OscQuality) -> f32 {
+    if quality.precise_sine() {
+        (phase * 2.0 * std::f32::consts::PI).sin()
+    } else {
+        fast_sine(phase)
+    }
+}
+
+/// Compute a waveform's value at a phase. The phase is wrapped to `[0, 1)` first. All waveforms
+/// except for the sawtooth start at zero and rise like a sine. The sawtooth falls from 1 to -1 so
+/// its discontinuity lines up with the end of the cycle. The waveforms with discontinuities are
+/// corrected with [`poly_blep()`] for a phase that advances by `phase_delta` every sample, and a
+/// phase delta of zero results in the naive waveforms. The quality decides how they're corrected
+/// and whether the sine is approximated, see [`sine()`].
+pub fn generate_waveform(
+    waveform: Waveform,
+    phase: f32,
+    phase_delta: f32,
+    quality: OscQuality,
+) -> f32 {
+    let phase = wrap_phase(phase);
+    // The residual for a rising step of 2 at `offset` into the cycle
+    let step_at = |offset: f32| poly_blep(wrap_phase(phase - offset), phase_delta, quality);
+    match waveform {
+        // Without any distortion, the phase distortion oscillator is a plain sine
+        Waveform::Sine | Waveform::PhaseDistortion => sine(phase, quality),
+        // The organ is rendered by `DrawbarOrgan`, this is only its 8' drawbar
+        Waveform::Drawbar => sine(phase, quality),
+        // Peaks at a quarter and three quarters of the cycle, just like the sine
+        Waveform::Triangle => 1.0 - 4.0 * (wrap_phase(phase + 0.25) - 0.5).abs(),
+        // The detuned stack is rendered by `Supersaw`, this is only the center oscillator
+        Waveform::Sawtooth | Waveform::Supersaw => 1.0 - phase * 2.0 + step_at(0.0),
+        // The square rises at the start of the cycle and falls halfway through
+        Waveform::Square => {
+            let square = if phase < 0.5 { 1.0 } else { -1.0 };
+            square + step_at(0.0) - step_at(0.5)
+        }
+        // The pulse falls after a quarter of the cycle and rises again after three quarters
+        Waveform::Pulse => {
+            let pulse = if (0.25..0.75).contains(&phase) {
+                -1.0
+            } else {
+                1.0
+            };
+            pulse - step_at(0.25) + step_at(0.75)
+        }
+        // The synth's voices use their own seeded generators for this instead
+        Waveform::Noise => rand::random::<f32>() * 2.0 - 1.0,
+    }
+}
+
+/// The phase distortion oscillator. The phase is warped according to the curve before looking up
+/// the sine, with `amount` between 0 and 1 setting how strongly. An amount of zero results in a
+/// plain sine for the sawtooth and square curves.
+pub fn phase_distortion(phase: f32, amount: f32, curve: PdCurve) -> f32 {
+    let phase = wrap_phase(phase);
+    // Fully distorting the phase would cause discontinuities
+    let amount = amount.clamp(0.0, 1.0) * 0.98;
+    let warped_phase = match curve {
+        // The first half of the cycle gets squeezed into a shorter part of the phase
+        PdCurve::Sawtooth => {
+            let knee = 0.5 - 0.5 * amount;
+            if phase < knee {
+                0.5 * phase / knee
+            } else {
+                0.5 + 0.5 * (phase - knee) / (1.0 - knee)
+            }
+        }
+        // Every quarter rushes to its peak and then holds, or holds and then falls back
+        PdCurve::Square => {
+            let quarter = (phase * 4.0).floor().min(3.0);
+            let local = phase * 4.0 - quarter;
+            let length = 1.0 - amount;
+            let warped_local = match quarter as u8 {
+                0 | 2 => (local / length).min(1.0),
+                _ => ((local - amount) / length).max(0.0),
+            };
+            (quarter + warped_local) * 0.25
+        }
+        PdCurve::Resonant => {
+            // The falling sawtooth window hides the discontinuity at the end of the cycle
+            let ratio = 1.0 + amount * 15.0;
+            return (1.0 - phase) * (phase * ratio * 2.0 * std::f32::consts::PI).sin();
+        }
+    };
+
+    (warped_phase * 2.0 * std::f32::consts::PI).sin()
+}
+
+/// Maps the detune knob to the amount of detuning using the curve of the original supersaw. Most of
+/// the knob's travel is spent on subtle detuning, with the last part quickly ramping up.
+pub fn supersaw_detune_curve(detune: f32) -> f32 {
+    let x = detune.clamp(0.0, 1.0) as f64;
+    let curve = 10_028.731_289_163_4 * x.powi(11) - 50_818.865_204_592_4 * x.powi(10)
+        + 111_363.480_872_936_8 * x.powi(9)
+        - 138_150.676_108_054_8 * x.powi(8)
+        + 106_649.667_915_829_2 * x.powi(7)
+        - 53_046.964_275_187_5 * x.powi(6)
+        + 17_019.951_858_008 * x.powi(5)
+        - 3_425.083_659_131_8 * x.powi(4)
+        + 404.270_393_838_8 * x.powi(3)
+        - 24.187_882_439_1 * x.powi(2)
+        + 0.671_741_763_4 * x
+        + 0.003_011_559_6;
+
+    curve as f32
+}
+
+/// Seven detuned sawtooth oscillators rendered inside of a single voice. Every voice starts the
+/// oscillators at random phases so the stack doesn't phase in the same way on every note.
+#[derive(Debug, Clone, Copy)]
+pub struct Supersaw {
+    /// Accumulated in double precision for the same reason as the voice's own phase.
+    phases: [f64; SUPERSAW_OSCILLATORS],
+}
+
+impl Supersaw {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        Supersaw {
+            phases: [0.0; SUPERSAW_OSCILLATORS].map(|_| rng.gen()),
+        }
+    }
+
+    /// Render the next sample. `detune` should already have been passed through
+    /// [`supersaw_detune_curve()`], and `mix` sets the balance between the center oscillator and
+    /// the six side oscillators.
+    pub fn next_sample(
+        &mut self,
+        phase_delta: f32,
+        detune: f32,
+        mix: f32,
+        quality: OscQuality,
+    ) -> f32 {
+        let center_gain = -0.553_66 * mix + 0.997_85;
+        let side_gain = -0.737_64 * mix * mix + 1.284_1 * mix + 0.044_372;
+
+        let mut sample = 0.0;
+        for (i, (phase, spread)) in self.phases.iter_mut().zip(SUPERSAW_SPREAD).enumerate() {
+            let dt = phase_delta * (1.0 + spread * detune);
+            let saw = 1.0 - *phase as f32 * 2.0 + poly_blep(*phase as f32, dt, quality);
+            sample += if i == SUPERSAW_OSCILLATORS / 2 {
+                saw * center_gain
+            } else {
+                saw * side_gain
+            };
+
+            *phase += dt as f64;
+            if *phase >= 1.0 {
+                *phase -= 1.0;
+            }
+        }
+
+        // The six side oscillators would otherwise make the stack a lot louder than the other
+        // waveforms
+        sample / 3.0
+    }
+}
+
+/// The polyBLEP residual for a rising step of 2 at the end of the phase, for a phase `t` that
+/// advances by `dt` every sample. This is added to a naive sawtooth to band limit it.
+pub fn poly_blep(t: f32, dt: f32, quality: OscQuality) -> f32 {
+    match quality {
+        // The wider kernel's two halves would overlap at very high pitches
+        OscQuality::High | OscQuality::Ultra if dt < 0.25 => poly_blep_4(t, dt),
+        _ => poly_blep_2(t, dt),
+    }
+}
+
+/// The residual of a linear B-spline, spanning one sample on each side of the discontinuity.
+fn poly_blep_2(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let t = t / dt;
+        // 2 * (t - t^2/2 - 0.5)
+        return t + t - t * t - 1.0;
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        // 2 * (t^2/2 + t + 0.5)
+        return t * t + t + t + 1.0;
+    }
+    0.0
+}
+
+/// The residual of a cubic B-spline, spanning two samples on each side of the discontinuity. These
+/// are the integrated B-spline's four segments minus the unit step, scaled by 2.
+fn poly_blep_4(t: f32, dt: f32) -> f32 {
+    if t < 2.0 * dt {
+        let x = t / dt;
+        if x < 1.0 {
+            let x2 = x * x;
+            2.0 * ((4.0 * x - 2.0 * x2 * x + 0.75 * x2 * x2) / 6.0 - 0.5)
+        } else {
+            let y = 2.0 - x;
+            -2.0 * (y * y * y * y) / 24.0
+        }
+    } else if t > 1.0 - 2.0 * dt {
+        let x = (t - 1.0) / dt;
+        if x > -1.0 {
+            let x2 = x * x;
+            2.0 * ((4.0 * x - 2.0 * x2 * x - 0.75 * x2 * x2) / 6.0 + 0.5)
+        } else {
+            let y = x + 2.0;
+            2.0 * (y * y * y * y) / 24.0
+        }
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn assert_shape(waveform: Waveform, expected: &[(f32, f32)]) {
+        for &(phase, value) in expected {
+            let actual = generate_waveform(waveform, phase, 0.0, OscQuality::Standard);
+            assert!(
+                (actual - value).abs() < EPSILON,
+                "{waveform:?} at phase {phase}: expected {value}, got {actual}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_wrap_phase() {
+        assert_eq!(wrap_phase(0.0), 0.0);
+        assert_eq!(wrap_phase(0.25), 0.25);
+        assert_eq!(wrap_phase(1.0), 0.0);
+        assert_eq!(wrap_phase(1.25), 0.25);
+        assert_eq!(wrap_phase(-0.25), 0.75);
+        assert_eq!(wrap_phase(-f32::EPSILON / 4.0), 0.0);
+    }
+
+    #[test]
+    fn test_sine_shape() {
+        assert_shape(
+            Waveform::Sine,
+            &[(0.0, 0.0), (0.25, 1.0), (0.5, 0.0), (0.75, -1.0)],
+        );
+    }
+
+    #[test]
+    fn test_fast_sine() {
+        for i in 0..1000 {
+            let phase = i as f32 / 1000.0;
+            let expected = (phase * 2.0 * std::f32::consts::PI).sin();
+            assert!((fast_sine(phase) - expected).abs() < EPSILON, "phase {phase}");
+            assert_eq!(sine(phase, OscQuality::Ultra), expected);
+        }
+    }
+
+    #[test]
+    fn test_triangle_shape() {
+        assert_shape(
+            Waveform::Triangle,
+            &[
+                (0.0, 0.0),
+                (0.125, 0.5),
+                (0.25, 1.0),
+                (0.5, 0.0),
+                (0.75, -1.0),
+                (0.875, -0.5),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_triangle_symmetry() {
+        for i in 0..100 {
+            let phase = i as f32 / 100.0;
+            let value = generate_waveform(Waveform::Triangle, phase, 0.0, OscQuality::Standard);
+            let mirrored =
+                generate_waveform(Waveform::Triangle, 1.0 - phase, 0.0, OscQuality::Standard);
+            assert!((value + mirrored).abs() < EPSILON, "phase {phase}");
+        }
+    }
+
+    #[test]
+    fn test_sawtooth_shape() {
+        assert_shape(
+            Waveform::Sawtooth,
+            &[(0.0, 1.0), (0.25, 0.5), (0.5, 0.0), (0.75, -0.5)],
+        );
+    }
+
+    #[test]
+    fn test_square_shape() {
+        assert_shape(
+            Waveform::Square,
+            &[(0.0, 1.0), (0.25, 1.0), (0.5, -1.0), (0.75, -1.0)],
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_phases() {
+        for waveform in [
+            Waveform::Sine,
+            Waveform::Triangle,
+            Waveform::Sawtooth,
+            Waveform::Square,
+            Waveform::Pulse,
+        ] {
+            for phase in [0.1, 0.3, 0.6, 0.9] {
+                let value = generate_waveform(waveform, phase, 0.0, OscQuality::Standard);
+                for offset in [-1.0, 1.0, 2.0] {
+                    let wrapped_value =
+                        generate_waveform(waveform, phase + offset, 0.0, OscQuality::Standard);
+                    assert!((wrapped_value - value).abs() < EPSILON, "{waveform:?} at {phase}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_undistorted_phase_distortion() {
+        for curve in [PdCurve::Sawtooth, PdCurve::Square] {
+            for i in 0..100 {
+                let phase = i as f32 / 100.0;
+                let expected = generate_waveform(Waveform::Sine, phase, 0.0, OscQuality::Ultra);
+                assert!((phase_distortion(phase, 0.0, curve) - expected).abs() < EPSILON);
+            }
+        }
+    }
+
+    /// The share of a signal's energy that lies between the harmonics of its fundamental. The
+    /// signal needs to contain exactly `cycles` periods, so for these test signals that energy can
+    /// only come from aliasing.
+    fn aliasing_ratio(signal: &[f32], cycles: usize) -> f64 {
+        let n = signal.len();
+        let (mut harmonic_energy, mut aliased_energy) = (0.0, 0.0);
+        for bin in 1..n / 2 {
+            let (mut re, mut im) = (0.0, 0.0);
+            for (i, &sample) in signal.iter().enumerate() {
+                let angle = 2.0 * std::f64::consts::PI * ((bin * i) % n) as f64 / n as f64;
+                re += sample as f64 * angle.cos();
+                im -= sample as f64 * angle.sin();
+            }
+
+            if bin % cycles == 0 {
+                harmonic_energy += re * re + im * im;
+            } else {
+                aliased_energy += re * re + im * im;
+            }
+        }
+
+        aliased_energy / (harmonic_energy + aliased_energy)
+    }
+
+    // 37 cycles in the window puts the fundamental at about 400 Hz at 44.1 kHz, and none of the
+    // aliased harmonics land on a harmonic
+    const NUM_SAMPLES: usize = 4096;
+    const CYCLES: usize = 37;
+
+    /// Render `NUM_SAMPLES` samples of a waveform, correcting it for `corrected_phase_delta`.
+    fn render(waveform: Waveform, phase_delta: f32, corrected_phase_delta: f32) -> Vec<f32> {
+        (0..NUM_SAMPLES)
+            .map(|i| {
+                let phase = i as f32 * phase_delta;
+                generate_waveform(waveform, phase, corrected_phase_delta, OscQuality::Standard)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_poly_blep_aliasing() {
+        let dt = CYCLES as f32 / NUM_SAMPLES as f32;
+        for waveform in [Waveform::Sawtooth, Waveform::Square, Waveform::Pulse] {
+            let naive_ratio = aliasing_ratio(&render(waveform, dt, 0.0), CYCLES);
+            let corrected_ratio = aliasing_ratio(&render(waveform, dt, dt), CYCLES);
+            assert!(
+                corrected_ratio < naive_ratio / 4.0,
+                "{waveform:?}: the corrected aliasing ratio of {corrected_ratio} isn't far enough \
+                 below the naive waveform's {naive_ratio}"
+            );
+        }
+
+        // The waveforms without discontinuities don't get corrected
+        for waveform in [Waveform::Sine, Waveform::Triangle] {
+            assert_eq!(render(waveform, dt, 0.0), render(waveform, dt, dt), "{waveform:?}");
+        }
+    }
+
+    #[test]
+    fn test_supersaw_aliasing() {
+        let dt = CYCLES as f32 / NUM_SAMPLES as f32;
+
+        let naive_ratio = aliasing_ratio(&render(Waveform::Sawtooth, dt, 0.0), CYCLES);
+
+        // Without any detuning all of the supersaw's saws share the same harmonics
+        let mut supersaw = Supersaw::new(&mut rand::thread_rng());
+        for quality in [OscQuality::Standard, OscQuality::High] {
+            let supersaw_signal: Vec<f32> = (0..NUM_SAMPLES)
+                .map(|_| supersaw.next_sample(dt, 0.0, 0.5, quality))
+                .collect();
+            let supersaw_ratio = aliasing_ratio(&supersaw_signal, CYCLES);
+            assert!(
+                supersaw_ratio < naive_ratio / 4.0,
+                "{quality:?}: the supersaw's aliasing ratio of {supersaw_ratio} isn't far enough \
+                 below the naive sawtooth's {naive_ratio}"
+            );
+
+            // Correcting the stack again as if it were a single sawtooth only adds aliasing
+            let double_corrected: Vec<f32> = supersaw_signal
+                .iter()
+                .enumerate()
+                .map(|(i, sample)| sample - poly_blep(wrap_phase(i as f32 * dt), dt, quality))
+                .collect();
+            let double_corrected_ratio = aliasing_ratio(&double_corrected, CYCLES);
+            assert!(
+                double_corrected_ratio > supersaw_ratio,
+                "{quality:?}: correcting the supersaw twice resulted in an aliasing ratio of \
+                 {double_corrected_ratio}, compared to {supersaw_ratio}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_poly_blep_outside_discontinuity() {
+        for quality in [OscQuality::Standard, OscQuality::High] {
+            assert_eq!(poly_blep(0.5, 0.01, quality), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_poly_blep_4_continuity() {
+        // The kernel's segments need to meet at every sample boundary
+        let dt = 0.01;
+        for t in [dt, 2.0 * dt, 1.0 - dt, 1.0 - 2.0 * dt] {
+            let before = poly_blep_4(t - 1e-4, dt);
+            let after = poly_blep_4(t + 1e-4, dt);
+            assert!((before - after).abs() < 1e-2, "t = {t}: {before} != {after}");
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Band-limited wavetables for the classic waveforms. Every waveform gets one table per octave,
+//! built from its Fourier series with only the harmonics that stay below the Nyquist frequency for
+//! the highest note in that octave. These are computed once in `initialize()` since they depend on
+//! the sample rate.
+
+use crate::config::WAVETABLE_SIZE as TABLE_SIZE;
+use crate::waveform::{wrap_phase, Waveform};
+
+/// The highest fundamental frequency the first table is built for. Every following table covers
+/// the octave above the previous one.
+const LOWEST_FREQUENCY: f32 = 20.0;
+/// Enough octaves to go from 20 Hz to past 20 kHz.
+const NUM_OCTAVES: usize = 11;
+/// The waveforms that have tables, in the order they're stored in.
+const TABLE_WAVEFORMS: [Waveform; 4] = [
+    Waveform::Triangle,
+    Waveform::Sawtooth,
+    Waveform::Square,
+    Waveform::Pulse,
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct WavetableBank {
+    /// All tables stored back to back, first by waveform and then by octave. Every table has one
+    /// extra sample at the end that repeats the first one, so the interpolation doesn't need to
+    /// wrap around. Empty until the tables have been built.
+    tables: Vec<f32>,
+    sample_rate: f32,
+}
+
+impl WavetableBank {
+    pub fn new(sample_rate: f32) -> Self {
+        // The sines of all table positions, so every harmonic is a lookup instead of a `sin()`
+        let sines: Vec<f64> = (0..TABLE_SIZE)
+            .map(|idx| (idx as f64 / TABLE_SIZE as f64 * std::f64::consts::TAU).sin())
+            .collect();
+
+        let mut tables = Vec::with_capacity(TABLE_WAVEFORMS.len() * NUM_OCTAVES * (TABLE_SIZE + 1));
+        let mut table = vec![0.0f64; TABLE_SIZE];
+        for waveform in TABLE_WAVEFORMS {
+            for octave in 0..NUM_OCTAVES {
+                let highest_frequency = LOWEST_FREQUENCY * 2.0f32.powi(octave as i32);
+                let num_harmonics =
+                    ((sample_rate * 0.5 / highest_frequency) as usize).clamp(1, TABLE_SIZE / 2 - 1);
+
+                table.fill(0.0);
+                for harmonic in 1..=num_harmonics {
+                    let (gain, phase_offset) = match harmonic_gain(waveform, harmonic) {
+                        Some(harmonic_gain) => harmonic_gain,
+                        None => continue,
+                    };
+                    for (idx, sample) in table.iter_mut().enumerate() {
+                        *sample += gain * sines[(harmonic * idx + phase_offset) % TABLE_SIZE];
+                    }
+                }
+
+                tables.extend(table.iter().map(|&sample| sample as f32));
+                tables.push(table[0] as f32);
+            }
+        }
+
+        Self {
+            tables,
+            sample_rate,
+        }
+    }
+
+    /// Look up a waveform at a phase for a note that advances its phase by `phase_delta` every
+    /// sample. Returns `None` for waveforms without a table, or if the tables haven't been built
+    /// yet.
+    pub fn sample(&self, waveform: Waveform, phase: f32, phase_delta: f32) -> Option<f32> {
+        let waveform_idx = TABLE_WAVEFORMS.iter().position(|&w| w == waveform)?;
+        if self.tables.is_empty() {
+            return None;
+        }
+
+        // The first table whose highest frequency is at or above the note's frequency
+        let frequency = phase_delta.abs() * self.sample_rate;
+        let octave = (frequency / LOWEST_FREQUENCY)
+            .log2()
+            .ceil()
+            .clamp(0.0, (NUM_OCTAVES - 1) as f32) as usize;
+        let table_start = (waveform_idx * NUM_OCTAVES + octave) * (TABLE_SIZE + 1);
+        let table = &self.tables[table_start..table_start + TABLE_SIZE + 1];
+
+        let position = wrap_phase(phase) * TABLE_SIZE as f32;
+        let idx = (position as usize).min(TABLE_SIZE - 1);
+        let t = position - idx as f32;
+        Some(table[idx] + (table[idx + 1] - table[idx]) * t)
+    }
+
+    /// Whether `waveform` is rendered from these tables. Those waveforms are already band-limited
+    /// and don't need any further anti-aliasing.
+    pub fn is_band_limited(&self, waveform: Waveform) -> bool {
+        !self.tables.is_empty() && TABLE_WAVEFORMS.contains(&waveform)
+    }
+}
+
+/// The gain and the phase offset of a waveform's harmonic, with the phase offset in table samples.
+/// Harmonics that aren't part of the waveform return `None`. These are the Fourier series of the
+/// naive waveforms from [`generate_waveform()`][crate::waveform::generate_waveform()], so both
+/// versions line up.
+fn harmonic_gain(waveform: Waveform, harmonic: usize) -> Option<(f64, usize)> {
+    use std::f64::consts::PI;
+
+    let k = harmonic as f64;
+    let odd = harmonic % 2 == 1;
+    // Alternates between 1 and -1 for the odd harmonics
+    let sign = if harmonic % 4 == 1 { 1.0 } else { -1.0 };
+    match waveform {
+        Waveform::Triangle if odd => Some((sign * 8.0 / (PI * PI * k * k), 0)),
+        // The sawtooth falls from 1 to -1
+        Waveform::Sawtooth => Some((2.0 / (PI * k), 0)),
+        Waveform::Square if odd => Some((4.0 / (PI * k), 0)),
+        // The pulse is a square shifted a quarter cycle to the left, which makes it a sum of cosines
+        Waveform::Pulse if odd => Some((sign * 4.0 / (PI * k), TABLE_SIZE / 4)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::waveform::{generate_waveform, OscQuality};
+
+    #[test]
+    fn test_low_notes_match_naive_waveforms() {
+        let bank = WavetableBank::new(48_000.0);
+        let phase_delta = 20.0 / 48_000.0;
+        for waveform in TABLE_WAVEFORMS {
+            // The band-limited versions ring around the discontinuities, so those are skipped
+            for phase in [0.1, 0.2, 0.3, 0.4, 0.6, 0.7, 0.8, 0.9] {
+                let expected = generate_waveform(waveform, phase, 0.0, OscQuality::Standard);
+                let actual = bank.sample(waveform, phase, phase_delta).unwrap();
+                assert!(
+                    (actual - expected).abs() < 0.02,
+                    "{waveform:?} at phase {phase}: expected {expected}, got {actual}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_high_notes_are_sines() {
+        let bank = WavetableBank::new(48_000.0);
+        // Only the fundamental fits below the Nyquist frequency
+        let phase_delta = 15_000.0 / 48_000.0;
+        for phase in [0.0, 0.125, 0.25, 0.6] {
+            let expected = (phase * std::f32::consts::TAU).sin() * 2.0 / std::f32::consts::PI;
+            let actual = bank.sample(Waveform::Sawtooth, phase, phase_delta).unwrap();
+            assert!((actual - expected).abs() < 1e-3, "phase {phase}");
+        }
+    }
+
+    #[test]
+    fn test_empty_bank() {
+        let bank = WavetableBank::default();
+        assert_eq!(bank.sample(Waveform::Sawtooth, 0.5, 0.01), None);
+        assert!(!bank.is_band_limited(Waveform::Sawtooth));
+        assert_eq!(
+            WavetableBank::new(48_000.0).sample(Waveform::Sine, 0.5, 0.01),
+            None
+        );
+    }
+}
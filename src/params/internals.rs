@@ -78,6 +78,25 @@ impl ParamPtr {
     param_ptr_forward!(pub(crate) unsafe fn modulate_value(&self, modulation_offset: f32) -> bool);
     param_ptr_forward!(pub(crate) unsafe fn update_smoother(&self, sample_rate: f32, reset: bool));
 
+    /// Set the parameter's normalized value and update its smoother the same way the wrappers do
+    /// when the host changes it. This is only meant for plugins that drive their own `process()`
+    /// function in tests, and it's not part of the public API.
+    ///
+    /// # Safety
+    ///
+    /// Calling this function is only safe as long as the object this `ParamPtr` was created for is
+    /// still alive.
+    #[doc(hidden)]
+    pub unsafe fn set_normalized_value_for_tests(
+        &self,
+        normalized: f32,
+        sample_rate: f32,
+        reset_smoother: bool,
+    ) {
+        self.set_normalized_value(normalized);
+        self.update_smoother(sample_rate, reset_smoother);
+    }
+
     // These functions involve casts since the plugin formats only do floating point types, so we
     // can't generate them with the macro:
 